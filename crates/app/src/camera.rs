@@ -1,113 +0,0 @@
-use glam::{Mat4, Vec3};
-use windows::Win32::UI::Input::KeyboardAndMouse::{VK_SHIFT, VK_SPACE};
-
-use crate::{HEIGHT, InputState, WIDTH};
-
-const MOUSE_SENSITIVITY: f32 = 0.5;
-const SPEED_MULTIPLIER: f32 = 10.0;
-
-pub struct Camera {
-    position: Vec3,
-    world_to_view: Mat4,
-    view_to_clip: Mat4,
-}
-
-impl Camera {
-    pub fn new(position: Vec3) -> Self {
-        let fov_y = 90_f32.to_radians();
-        let aspect_ratio = WIDTH as f32 / HEIGHT as f32;
-        let near_z = 0.1;
-
-        Self {
-            position,
-            world_to_view: Mat4::IDENTITY,
-            view_to_clip: Mat4::perspective_infinite_reverse_lh(fov_y, aspect_ratio, near_z),
-        }
-    }
-
-    pub fn position(&self) -> &Vec3 {
-        &self.position
-    }
-
-    pub fn world_to_clip(&self) -> Mat4 {
-        self.view_to_clip * self.world_to_view
-    }
-}
-
-pub struct CameraController {
-    pub speed: f32,
-
-    yaw: f32,
-    pitch: f32,
-}
-
-impl CameraController {
-    pub fn control(&mut self, dt: f32, input: &InputState, camera: &mut Camera) {
-        if input.right_mouse_down {
-            self.yaw += input.mouse_dx as f32 * MOUSE_SENSITIVITY;
-            self.pitch += input.mouse_dy as f32 * MOUSE_SENSITIVITY;
-
-            self.yaw = self.yaw.rem_euclid(360.0);
-            self.pitch = self.pitch.clamp(-89.0, 89.0);
-        }
-
-        let yaw_rad = self.yaw.to_radians();
-        let pitch_rad = self.pitch.to_radians();
-
-        let front_dir = Vec3::new(
-            yaw_rad.cos() * pitch_rad.cos(),
-            pitch_rad.sin(),
-            yaw_rad.sin() * pitch_rad.cos(),
-        )
-        .normalize();
-
-        let mut speed = self.speed * dt;
-        if input.keys[VK_SHIFT.0 as usize] {
-            speed *= SPEED_MULTIPLIER;
-        }
-
-        if input.keys[b'W' as usize] {
-            camera.position += front_dir * speed;
-        }
-
-        if input.keys[b'S' as usize] {
-            camera.position -= front_dir * speed;
-        }
-
-        if input.keys[b'A' as usize] {
-            camera.position += front_dir.cross(Vec3::Y).normalize() * speed;
-        }
-
-        if input.keys[b'D' as usize] {
-            camera.position -= front_dir.cross(Vec3::Y).normalize() * speed;
-        }
-
-        if input.keys[VK_SPACE.0 as usize] {
-            camera.position.y += speed;
-        }
-
-        if input.keys[b'C' as usize] {
-            camera.position.y -= speed;
-        }
-
-        camera.world_to_view = Mat4::look_to_lh(camera.position, front_dir, Vec3::Y);
-    }
-
-    pub fn yaw(&self) -> f32 {
-        self.yaw
-    }
-
-    pub fn pitch(&self) -> f32 {
-        self.pitch
-    }
-}
-
-impl Default for CameraController {
-    fn default() -> Self {
-        Self {
-            speed: 50.0,
-            yaw: -90.0,
-            pitch: 0.0,
-        }
-    }
-}
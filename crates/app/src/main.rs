@@ -1,11 +1,15 @@
-mod camera;
 mod d3d12_utils;
 mod terrain;
+mod win32_input;
 
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use glam::Vec3;
+use camera::{Camera, CameraController, CameraMode, CameraModeKind, Easing, FlyCamera, LookAngles};
+use glam::{Vec2, Vec3};
+use input::{Action, ActionMapFile, ActionState, InputFrame, InputRecording, InputReplay, InputState};
+use terrain_core::{DiskPatchCache, FbmHeightSource, HeightSource, TerrainConfig};
 use windows::Win32::Foundation::*;
 use windows::Win32::Graphics::Direct3D::*;
 use windows::Win32::Graphics::Direct3D12::*;
@@ -20,6 +24,7 @@ use windows::core::{BOOL, Interface, PCSTR, s};
 use d3d12_utils::*;
 use imgui_sys::*;
 use terrain::*;
+use win32_input::*;
 
 const WINDOW_REGISTRY_NAME: PCSTR = s!("rust-window");
 const WIDTH: u32 = 1920;
@@ -27,6 +32,13 @@ const HEIGHT: u32 = 1080;
 const FRAME_COUNT: u32 = 3;
 const BACK_BUFFER_FORMAT: DXGI_FORMAT = DXGI_FORMAT_R8G8B8A8_UNORM;
 const DEPTH_BUFFER_FORMAT: DXGI_FORMAT = DXGI_FORMAT_D32_FLOAT;
+const TERRAIN_CACHE_DIR: &str = "terrain_cache";
+const TERRAIN_CACHE_MAX_BYTES: u64 = 1 << 30;
+const CURSOR_RAY_LENGTH: f32 = 10000.0;
+const INPUT_BINDINGS_PATH: &str = "assets/input_bindings.json";
+const INPUT_RECORDING_PATH: &str = "input_recording.bin";
+const REPLAY_TICK: f32 = 1.0 / 120.0;
+const CAMERA_START_POSITION: Vec3 = Vec3::new(0.0, 100.0, 0.0);
 
 #[macro_export]
 macro_rules! imgui_text {
@@ -57,27 +69,17 @@ enum GpuResource {
     Count,
 }
 
-struct InputState {
-    keys: [bool; 256],
-    mouse_x: i32,
-    mouse_y: i32,
-    mouse_dx: i32,
-    mouse_dy: i32,
-    right_mouse_down: bool,
-}
-
 fn main() -> Result<()> {
-    let mut camera = camera::Camera::new(Vec3::new(0.0, 100.0, 0.0));
-    let mut camera_controller = camera::CameraController::default();
-
-    let mut input = InputState {
-        keys: [false; 256],
-        mouse_x: 0,
-        mouse_y: 0,
-        mouse_dx: 0,
-        mouse_dy: 0,
-        right_mouse_down: false,
-    };
+    let mut camera = Camera::new(CAMERA_START_POSITION, Vec2::new(WIDTH as f32, HEIGHT as f32));
+    let mut camera_controller = CameraController::default();
+
+    let mut input = InputState::new();
+    let mut bindings = ActionMapFile::new(INPUT_BINDINGS_PATH);
+    let mut actions = ActionState::new();
+
+    let mut recording: Option<InputRecording> = None;
+    let mut replay: Option<InputReplay> = None;
+    let mut recording_error: Option<String> = None;
 
     unsafe {
         let class_atom = RegisterClassA(&WNDCLASSA {
@@ -424,7 +426,21 @@ fn main() -> Result<()> {
             });
         }
 
-        let mut terrain = TerrainData::new(&device, &resource_heap, &root_signature)?;
+        let height_source = Arc::new(FbmHeightSource::default());
+        let disk_cache = height_source
+            .cache_key()
+            .map(|hash| DiskPatchCache::new(TERRAIN_CACHE_DIR, hash, TERRAIN_CACHE_MAX_BYTES))
+            .transpose()?
+            .map(Arc::new);
+
+        let mut terrain = TerrainData::new(
+            &device,
+            &resource_heap,
+            &root_signature,
+            TerrainConfig::default(),
+            height_source,
+            disk_cache,
+        )?;
 
         let mut cpu_frame_index = 0;
         let mut gpu_frame_index = 0;
@@ -455,10 +471,46 @@ fn main() -> Result<()> {
             let (dt, fps) = frame_timer.tick();
 
             {
-                camera_controller.control(dt, &input, &mut camera);
+                // A replay stands in for live input and frame time. It hands out steps of a fixed tick, so the camera
+                // takes the same path on every replay whatever the frame rate.
+                let replay_steps = match replay.as_mut().map(InputReplay::next_frame) {
+                    Some(Some(steps)) => Some(steps),
+                    Some(None) => {
+                        replay = None;
+                        input = InputState::new();
+                        None
+                    }
+                    None => None,
+                };
+                let steps = replay_steps.unwrap_or_else(|| {
+                    vec![InputFrame {
+                        dt,
+                        input: input.clone(),
+                    }]
+                });
+
+                bindings.reload_if_changed();
+
+                for step in steps {
+                    input = step.input;
+
+                    if let Some(recording) = &mut recording {
+                        recording.push(step.dt, &input);
+                    }
+
+                    actions.update(bindings.map(), &input);
+
+                    let ground_height = |pos| terrain.height_at(pos).map(|s| s.value);
+                    camera_controller.control(step.dt, &input, &actions, &ground_height, &mut camera);
+                    terrain.handle_actions(&actions);
+                }
 
-                input.mouse_dx = 0;
-                input.mouse_dy = 0;
+                input.end_frame();
+            }
+
+            if terrain.update_coverage(&camera) {
+                wait_for_gpu(&fence, fence_event, cpu_frame_index - 1)?;
+                terrain.reallocate_coverage_buffers(&device, &resource_heap)?;
             }
 
             // Render
@@ -506,7 +558,8 @@ fn main() -> Result<()> {
                 gpu_frame_index,
                 active_frame_index
             ));
-            let collect_patches_ms = measure_ms!(terrain.collect_leaf_patches(camera.position(), active_frame_index));
+            let collect_patches_ms =
+                measure_ms!(terrain.collect_leaf_patches(&camera, cpu_frame_index, active_frame_index));
             let upload_indirection_ms =
                 measure_ms!(terrain.upload_indirection_data(&device, &cmd_list, active_frame_index));
 
@@ -538,9 +591,172 @@ fn main() -> Result<()> {
                     ImGui_NewLine();
                     ImGui_SeparatorText(c"Camera".as_ptr());
                     imgui_text!("Position: {}", camera.position());
-                    imgui_text!("Yaw:  {:>7.2}", camera_controller.yaw());
-                    imgui_text!("Pitch: {:>6.2}", camera_controller.pitch());
-                    ImGui_DragFloat(c"Speed".as_ptr(), &mut camera_controller.speed);
+                    let look = LookAngles::from_dir(camera.forward());
+                    imgui_text!("Yaw:  {:>7.2}", look.yaw);
+                    imgui_text!("Pitch: {:>6.2}", look.pitch);
+
+                    let modes = [
+                        (c"Fly", CameraModeKind::Fly),
+                        (c"Orbit", CameraModeKind::Orbit),
+                        (c"Walk", CameraModeKind::Walk),
+                        (c"Flythrough", CameraModeKind::Flythrough),
+                    ];
+                    for (i, (label, kind)) in modes.into_iter().enumerate() {
+                        if i > 0 {
+                            ImGui_SameLine();
+                        }
+                        if ImGui_RadioButton(label.as_ptr(), camera_controller.mode.kind() == kind) {
+                            camera_controller.set_mode(kind, &camera);
+                        }
+                    }
+
+                    match &mut camera_controller.mode {
+                        CameraMode::Fly(fly) => {
+                            imgui_text!("Speed: {:.1}", fly.velocity().length());
+                            ImGui_DragFloat(c"Max speed".as_ptr(), &mut fly.max_speed);
+                            ImGui_DragFloat(c"Acceleration".as_ptr(), &mut fly.acceleration);
+                            ImGui_DragFloat(c"Drag".as_ptr(), &mut fly.drag);
+                            ImGui_DragFloat(c"Look smoothing".as_ptr(), &mut fly.look_smoothing);
+                        }
+                        CameraMode::Orbit(orbit) => {
+                            imgui_text!("Target: {:.1}", orbit.target);
+                            ImGui_DragFloat(c"Distance".as_ptr(), &mut orbit.distance);
+                            ImGui_DragFloat(c"Look smoothing".as_ptr(), &mut orbit.look_smoothing);
+                        }
+                        CameraMode::Walk(walk) => {
+                            imgui_text!("Grounded: {}", walk.is_grounded());
+                            ImGui_DragFloat(c"Speed".as_ptr(), &mut walk.speed);
+                            ImGui_DragFloat(c"Eye height".as_ptr(), &mut walk.eye_height);
+                            ImGui_DragFloat(c"Gravity".as_ptr(), &mut walk.gravity);
+                            ImGui_DragFloat(c"Look smoothing".as_ptr(), &mut walk.look_smoothing);
+                        }
+                        CameraMode::Flythrough(flythrough) => {
+                            imgui_text!("Time: {:.2} / {:.2} s", flythrough.time(), flythrough.end_time());
+                            ImGui_Checkbox(c"Loop".as_ptr(), &mut flythrough.looping);
+                            if ImGui_Button(c"Restart".as_ptr()) {
+                                flythrough.set_time(flythrough.start_time());
+                            }
+                        }
+                    }
+
+                    imgui_text!("Keyframes: {}", camera_controller.keyframes.len());
+                    ImGui_SameLine();
+                    if ImGui_Button(c"Add keyframe".as_ptr()) {
+                        camera_controller.add_keyframe(&camera);
+                    }
+                    ImGui_SameLine();
+                    if ImGui_Button(c"Clear keyframes".as_ptr()) {
+                        camera_controller.keyframes.clear();
+                    }
+
+                    // The last keyframe has no segment to ease, a running flythrough keeps the easing it started with.
+                    let segment_count = camera_controller.keyframes.len().saturating_sub(1);
+                    for (i, keyframe) in camera_controller.keyframes[..segment_count].iter_mut().enumerate() {
+                        ImGui_PushIDInt(i as i32);
+                        imgui_text!("{:.1} s", keyframe.time);
+                        let easings = [(c"Linear", Easing::Linear), (c"Ease in/out", Easing::EaseInOut)];
+                        for (label, easing) in easings {
+                            ImGui_SameLine();
+                            if ImGui_RadioButton(label.as_ptr(), keyframe.easing == easing) {
+                                keyframe.easing = easing;
+                            }
+                        }
+                        ImGui_PopID();
+                    }
+
+                    ImGui_NewLine();
+                    ImGui_SeparatorText(c"Input".as_ptr());
+                    imgui_text!("Bindings: {}", bindings.path().display());
+                    if let Some(error) = bindings.error() {
+                        let text = std::ffi::CString::new(error).unwrap();
+                        ImGui_TextColoredUnformatted(
+                            ImVec4 {
+                                x: 1.0,
+                                y: 0.3,
+                                z: 0.3,
+                                w: 1.0,
+                            },
+                            text.as_ptr(),
+                        );
+                    }
+                    if ImGui_CollapsingHeader(c"Actions".as_ptr(), 0) {
+                        for action in Action::ALL {
+                            let chords = bindings.map().bindings(action).iter().map(|c| c.to_string());
+                            imgui_text!("{:?}: {}", action, chords.collect::<Vec<_>>().join(", "));
+                        }
+                    }
+
+                    ImGui_NewLine();
+                    ImGui_SeparatorText(c"Recording".as_ptr());
+                    if let Some(active_recording) = &recording {
+                        imgui_text!(
+                            "Recording: {} frames ({:.1} s)",
+                            active_recording.frames().len(),
+                            active_recording.duration()
+                        );
+                        if ImGui_Button(c"Stop recording".as_ptr()) {
+                            recording_error = active_recording
+                                .save(INPUT_RECORDING_PATH)
+                                .err()
+                                .map(|e| format!("{}: {}", INPUT_RECORDING_PATH, e));
+                            recording = None;
+                        }
+                    } else if let Some(active_replay) = &replay {
+                        imgui_text!(
+                            "Replay: {} / {}",
+                            active_replay.frame_index(),
+                            active_replay.frame_count()
+                        );
+                        if ImGui_Button(c"Stop replay".as_ptr()) {
+                            replay = None;
+                            input = InputState::new();
+                        }
+                    } else {
+                        // Both start from the same camera, the recording only holds input.
+                        let record = ImGui_Button(c"Record".as_ptr());
+                        ImGui_SameLine();
+                        let play = ImGui_Button(c"Replay".as_ptr());
+
+                        if record || play {
+                            camera = Camera::new(CAMERA_START_POSITION, Vec2::new(WIDTH as f32, HEIGHT as f32));
+                            camera_controller.mode = CameraMode::Fly(FlyCamera::new());
+                            recording_error = None;
+                        }
+
+                        if record {
+                            recording = Some(InputRecording::new());
+                        }
+
+                        if play {
+                            match InputRecording::load(INPUT_RECORDING_PATH) {
+                                Ok(loaded) => replay = Some(InputReplay::new(loaded, REPLAY_TICK)),
+                                Err(e) => recording_error = Some(format!("{}: {}", INPUT_RECORDING_PATH, e)),
+                            }
+                        }
+                    }
+                    if let Some(error) = &recording_error {
+                        let text = std::ffi::CString::new(error.as_str()).unwrap();
+                        ImGui_TextColoredUnformatted(
+                            ImVec4 {
+                                x: 1.0,
+                                y: 0.3,
+                                z: 0.3,
+                                w: 1.0,
+                            },
+                            text.as_ptr(),
+                        );
+                    }
+
+                    ImGui_NewLine();
+                    ImGui_SeparatorText(c"Cursor".as_ptr());
+                    let cursor_dir = camera.screen_ray_dir(input.mouse_pos());
+                    match terrain.raycast(*camera.position(), cursor_dir, CURSOR_RAY_LENGTH) {
+                        Some(hit) => {
+                            imgui_text!("Terrain hit: {:.1}", hit.position);
+                            imgui_text!("Distance: {:.1} (LOD {})", hit.distance, hit.key.lod_index);
+                        }
+                        None => imgui_text!("Terrain hit: -"),
+                    }
                 }
                 ImGui_End();
 
@@ -646,42 +862,11 @@ extern "system" fn handle_window_message(window_handle: HWND, message: u32, wpar
     let imgui_mouse_capture =
         unsafe { !ImGui_GetCurrentContext().is_null() && ImGui_GetIO().as_ref().unwrap().WantCaptureMouse };
 
-    match message {
-        WM_KEYDOWN => {
-            input.keys[wparam.0] = true;
-            LRESULT::default()
-        }
-        WM_KEYUP => {
-            input.keys[wparam.0] = false;
-            LRESULT::default()
-        }
-        WM_MOUSEMOVE => {
-            let x = (lparam.0 & 0xFFFF) as i16 as i32;
-            let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
-            let dx = x - input.mouse_x;
-            let dy = y - input.mouse_y;
-
-            input.mouse_x = x;
-            input.mouse_y = y;
-
-            if !imgui_mouse_capture {
-                input.mouse_dx = dx;
-                input.mouse_dy = dy;
-            } else {
-                input.mouse_dx = 0;
-                input.mouse_dy = 0;
-            }
+    if handle_input_message(input, message, wparam, lparam, imgui_mouse_capture) {
+        return LRESULT::default();
+    }
 
-            LRESULT::default()
-        }
-        WM_RBUTTONDOWN => {
-            input.right_mouse_down = true;
-            LRESULT::default()
-        }
-        WM_RBUTTONUP => {
-            input.right_mouse_down = false;
-            LRESULT::default()
-        }
+    match message {
         WM_DESTROY => {
             unsafe { PostQuitMessage(0) };
             LRESULT::default()
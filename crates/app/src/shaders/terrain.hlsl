@@ -10,14 +10,38 @@ struct VsOutput {
     float height : Height;
 };
 
+static const uint INDIRECTION_TEXTURE_INDEX = 1;
+static const uint HEIGHT_ATLAS_INDEX = 2;
+static const uint PATCH_INDEX_BUFFER_INDEX = 3;
+
+static const uint PATCH_WORLD_SIZE = 64;
+static const uint MAX_PATCH_LOD_COUNT = 8;
+
+// D3D12 mesh shader output limits.
+static const uint MAX_MESH_VERTEX_COUNT = 256;
+static const uint MAX_MESH_PRIMITIVE_COUNT = 256;
+
+static const uint TOP_STITCH_BIT = 1 << 0;
+static const uint BOTTOM_STITCH_BIT = 1 << 1;
+static const uint LEFT_STITCH_BIT = 1 << 2;
+static const uint RIGHT_STITCH_BIT = 1 << 3;
+static const uint FINER_TOP_BIT = 1 << 4;
+static const uint FINER_BOTTOM_BIT = 1 << 5;
+static const uint FINER_LEFT_BIT = 1 << 6;
+static const uint FINER_RIGHT_BIT = 1 << 7;
+
 struct TerrainConsts {
     float4x4 world_to_clip;
-    int2 cam_world_index;
+    float4 morph_ranges[MAX_PATCH_LOD_COUNT]; // x: start distance, y: end distance
+    float3 camera_position;
+    uint morphing_enabled;
     float world_scale;
     float height_scale;
     uint wireframe_pass;
     uint stitching_enabled;
     uint active_patch_buffer_index;
+    uint patch_pixel_size;
+    uint indirection_slot_count;
 };
 
 struct TerrainPatch {
@@ -86,25 +110,6 @@ float3 patch_color(TerrainPatch patch) {
     return hsv_to_rgb(hue, 0.75 + 0.25 * frac((float)(hash >> 16) / 65535.0), 0.9);
 }
 
-static const uint INDIRECTION_TEXTURE_INDEX = 1;
-static const uint HEIGHT_ATLAS_INDEX = 2;
-static const uint PATCH_INDEX_BUFFER_INDEX = 3;
-
-static const uint PATCH_PIXEL_SIZE = 128;
-static const uint PATCH_WORLD_SIZE = 64;
-static const uint PATCH_LOD_COUNT = 5;
-static const uint PATCH_QUAD_COUNT = PATCH_PIXEL_SIZE;
-static const uint PATCH_VERTEX_COUNT = (PATCH_QUAD_COUNT + 1) * (PATCH_QUAD_COUNT + 1);
-static const uint PATCH_TRIANGLE_COUNT = PATCH_QUAD_COUNT * PATCH_QUAD_COUNT * 2;
-
-static const uint ATLAS_PATCH_PIXEL_SIZE = PATCH_PIXEL_SIZE + 1; // for pixel overlap
-static const uint INDIRECTION_SLOT_COUNT = 128;
-
-static const uint TOP_STITCH_BIT = 1 << 0;
-static const uint BOTTOM_STITCH_BIT = 1 << 1;
-static const uint LEFT_STITCH_BIT = 1 << 2;
-static const uint RIGHT_STITCH_BIT = 1 << 3;
-
 VsOutput ProcessVertex(uint vertex_id, uint instance_id) {
     const StructuredBuffer<TerrainPatch> patches = ResourceDescriptorHeap[consts.active_patch_buffer_index];
     const Texture2D<uint2> indirection_texture = ResourceDescriptorHeap[INDIRECTION_TEXTURE_INDEX];
@@ -112,13 +117,21 @@ VsOutput ProcessVertex(uint vertex_id, uint instance_id) {
 
     const TerrainPatch patch = patches[instance_id];
 
-    uint ix = vertex_id % (PATCH_QUAD_COUNT + 1);
-    uint iz = vertex_id / (PATCH_QUAD_COUNT + 1);
+    const uint quad_count = consts.patch_pixel_size;
+    const uint atlas_patch_pixel_size = consts.patch_pixel_size + 1; // for pixel overlap
+
+    uint ix = vertex_id % (quad_count + 1);
+    uint iz = vertex_id / (quad_count + 1);
+
+    const uint mask = patch.stitch_mask;
+
+    // Same as `place_vertex` in morph.rs, finer neighbours stitch onto these edges so they must not morph.
+    const bool morph_locked = (iz == 0 && mask & FINER_TOP_BIT) || (iz == quad_count && mask & FINER_BOTTOM_BIT) ||
+        (ix == 0 && mask & FINER_LEFT_BIT) || (ix == quad_count && mask & FINER_RIGHT_BIT);
 
     if (consts.stitching_enabled) {
-        const uint mask = patch.stitch_mask;
-        const bool stitch_x = (iz == 0 && mask & TOP_STITCH_BIT) || (iz == PATCH_QUAD_COUNT && mask & BOTTOM_STITCH_BIT);
-        const bool stitch_z = (ix == 0 && mask & LEFT_STITCH_BIT) || (ix == PATCH_QUAD_COUNT && mask & RIGHT_STITCH_BIT);
+        const bool stitch_x = (iz == 0 && mask & TOP_STITCH_BIT) || (iz == quad_count && mask & BOTTOM_STITCH_BIT);
+        const bool stitch_z = (ix == 0 && mask & LEFT_STITCH_BIT) || (ix == quad_count && mask & RIGHT_STITCH_BIT);
 
         if (stitch_x) {
             ix = (ix / 2) * 2;
@@ -129,20 +142,53 @@ VsOutput ProcessVertex(uint vertex_id, uint instance_id) {
         }
     }
 
-    const float2 uv = float2(ix, iz) / (float)PATCH_QUAD_COUNT; // 0..1
     const float world_size = PATCH_WORLD_SIZE * 1 << patch.lod_index;
-    const float2 world_xz = patch.world_index * (int)PATCH_WORLD_SIZE + world_size * uv;
 
     const uint lod_index = patch.lod_index;
-    const int2 relative_index = (patch.world_index >> lod_index) - (consts.cam_world_index >> lod_index);
-    const int2 indirection_index = relative_index + (INDIRECTION_SLOT_COUNT >> lod_index) / 2;
+    // Same as `TerrainConfig::toroidal_slot`, the mask wraps negative indices like `rem_euclid`.
+    const uint slot_mask = (consts.indirection_slot_count >> lod_index) - 1;
+    const uint2 indirection_index = uint2(patch.world_index >> lod_index) & slot_mask;
     const uint2 atlas_index = indirection_texture.mips[lod_index][indirection_index];
 
-    const float height = height_atlas[atlas_index * ATLAS_PATCH_PIXEL_SIZE + uint2(ix, iz)];
+    float2 grid_pos = float2(ix, iz);
+
+    // Same as `morph_factor` and `morph_vertex` in morph.rs, the distance is taken to the unmorphed vertex.
+    if (consts.morphing_enabled && !morph_locked) {
+        const float2 vertex_xz = patch.world_index * (int)PATCH_WORLD_SIZE + world_size * grid_pos / quad_count;
+        const float vertex_height = height_atlas[atlas_index * atlas_patch_pixel_size + uint2(ix, iz)];
+        const float3 vertex_position = float3(
+            vertex_xz.x * consts.world_scale,
+            vertex_height * consts.height_scale,
+            vertex_xz.y * consts.world_scale
+        );
+
+        const float2 range = consts.morph_ranges[lod_index].xy;
+        const float morph = saturate((distance(consts.camera_position, vertex_position) - range.x) / max(range.y - range.x, 1e-6));
+
+        const uint2 odd = uint2(ix, iz) % 2;
+        const float x_dir = (odd.x & odd.y) && ((ix / 2 + iz / 2) % 2) ? 1.0 : -1.0;
+
+        grid_pos += float2(x_dir, -1.0) * odd * morph;
+    }
+
+    const float2 uv = grid_pos / (float)quad_count; // 0..1
+    const float2 world_xz = patch.world_index * (int)PATCH_WORLD_SIZE + world_size * uv;
+
+    // Bilinear between the four surrounding texels, matches `morphed_height` in morph.rs.
+    const uint2 p0 = min((uint2)floor(grid_pos), quad_count - 1);
+    const float2 t = grid_pos - p0;
+    const uint2 base = atlas_index * atlas_patch_pixel_size + p0;
 
+    const float height = lerp(
+        lerp(height_atlas[base], height_atlas[base + uint2(1, 0)], t.x),
+        lerp(height_atlas[base + uint2(0, 1)], height_atlas[base + uint2(1, 1)], t.x),
+        t.y
+    );
+
+    // `TerrainScale` in height_field.rs mirrors this, the CPU height queries must land on the drawn surface.
     const float3 world_position = float3(
         world_xz.x * consts.world_scale,
-        height * 100.0,
+        height * consts.height_scale,
         world_xz.y * consts.world_scale
     );
 
@@ -164,18 +210,21 @@ VsOutput vs_main(VsInput input) {
 void ms_main(
     uint gtid : SV_GroupThreadID,
     uint gid : SV_GroupID,
-    out vertices VsOutput vertices[PATCH_VERTEX_COUNT],
-    out indices uint3 triangles[PATCH_TRIANGLE_COUNT]
+    out vertices VsOutput vertices[MAX_MESH_VERTEX_COUNT],
+    out indices uint3 triangles[MAX_MESH_PRIMITIVE_COUNT]
 ) {
-    SetMeshOutputCounts(PATCH_VERTEX_COUNT, PATCH_TRIANGLE_COUNT);
+    const uint vertex_count = (consts.patch_pixel_size + 1) * (consts.patch_pixel_size + 1);
+    const uint triangle_count = consts.patch_pixel_size * consts.patch_pixel_size * 2;
+
+    SetMeshOutputCounts(vertex_count, triangle_count);
 
-    if (gtid < PATCH_VERTEX_COUNT) {
+    if (gtid < vertex_count) {
         vertices[gtid] = ProcessVertex(gtid, gid);
     }
 
     const Buffer<uint> index_buffer = ResourceDescriptorHeap[PATCH_INDEX_BUFFER_INDEX];
 
-    if (gtid < PATCH_TRIANGLE_COUNT) { 
+    if (gtid < triangle_count) { 
         triangles[gtid] = uint3(
             index_buffer[gtid * 3 + 0],
             index_buffer[gtid * 3 + 1],
@@ -1,48 +1,20 @@
-use std::collections::HashMap;
 use std::ptr::null_mut;
-use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use anyhow::Result;
-use bitflags::bitflags;
-use glam::{IVec2, Mat4, UVec2, Vec2, Vec3, Vec3Swizzles, f32};
-use noise::utils::{NoiseMapBuilder, PlaneMapBuilder};
-use noise::{Fbm, MultiFractal, Perlin};
+use camera::Camera;
+use glam::{IVec2, Mat4, UVec2, Vec2, Vec3, Vec3Swizzles, Vec4, f32};
+use input::{Action, ActionState};
+use terrain_core::*;
 use windows::Win32::Graphics::Direct3D::*;
 use windows::Win32::Graphics::Direct3D12::*;
 use windows::Win32::Graphics::Dxgi::Common::*;
 
-use crate::camera::Camera;
 use crate::d3d12_utils::*;
-use crate::{BACK_BUFFER_FORMAT, DEPTH_BUFFER_FORMAT, FRAME_COUNT, GpuResource, imgui_text};
+use crate::{BACK_BUFFER_FORMAT, DEPTH_BUFFER_FORMAT, FRAME_COUNT, GpuResource, HEIGHT, imgui_text};
 use imgui_sys::*;
 
-const PATCH_GEN_WORKER_COUNT: usize = 16;
-
-const PATCH_LOD_COUNT: u32 = 5;
-const PATCH_PIXEL_SIZE: u32 = 128;
-const PATCH_WORLD_SIZE: u32 = PATCH_PIXEL_SIZE / 2;
-
-const ATLAS_PATCH_PIXEL_SIZE: u32 = PATCH_PIXEL_SIZE + 1; // for pixel overlap
-const ATLAS_PATCH_COUNT: u32 = 32;
-const ATLAS_SIZE: u32 = ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_COUNT;
 const HEIGHT_ATLAS_FORMAT: DXGI_FORMAT = DXGI_FORMAT_R32_FLOAT;
-const INDIRECTION_SLOT_COUNT: u32 = 128;
-
-const PATCH_SIDE_QUAD_COUNT: u32 = PATCH_PIXEL_SIZE;
-const PATCH_SIDE_VERTEX_COUNT: u32 = PATCH_PIXEL_SIZE + 1;
-const PATCH_INDEX_COUNT: u32 = PATCH_SIDE_QUAD_COUNT.pow(2) * 6;
-
-bitflags! {
-    #[repr(transparent)]
-    #[derive(Clone, Copy, Debug)]
-    struct StitchMask: u32 {
-        const TOP = 1 << 0;
-        const BOTTOM = 1 << 1;
-        const LEFT = 1 << 2;
-        const RIGHT = 1 << 3;
-    }
-}
 
 #[repr(C)]
 struct GpuTerrainPatch {
@@ -54,45 +26,54 @@ struct GpuTerrainPatch {
 #[repr(C)]
 struct GpuTerrainConsts {
     world_to_clip: Mat4,
-    cam_world_index: IVec2,
+    morph_ranges: [Vec4; MAX_PATCH_LOD_COUNT as usize],
+    camera_position: Vec3,
+    morphing_enabled: u32,
     world_scale: f32,
     height_scale: f32,
     wireframe_pass: u32,
     stitching_enabled: u32,
     active_patch_buffer_index: u32,
+    patch_pixel_size: u32,
+    indirection_slot_count: u32,
 }
 
 pub struct TerrainData {
-    render_distance: u32,
-    lod_factor: f32,
+    planner: TerrainPlanner,
 
     height_scale: f32,
     world_scale: f32,
+    lod_factor: f32,
+    screen_space_error_lod: bool,
+    max_pixel_error: f32,
+    coverage: TerrainCoverage,
+    coverage_error: Option<TerrainConfigError>,
 
     solid_mode: bool,
     wireframe_mode: bool,
     stitching_enabled: bool,
+    morphing_enabled: bool,
     freeze_camera: bool,
     camera_pos: Vec3,
+    frustum_culling: bool,
+    freeze_culling: bool,
+    culling_frustum: Option<Frustum>,
 
-    cam_world_index: IVec2,
-    leaf_patches: Vec<PatchKey>,
     gpu_patch_count: u32,
 
-    patch_cache: HashMap<PatchKey, PatchState>,
-    patch_gen_pool: PatchGenPool,
-    atlas_free_slots: Vec<UVec2>,
-
     patch_index_buffer: ID3D12Resource,
     #[allow(unused)]
     patch_buffer: ID3D12Resource,
     patch_buffer_item_count: u32,
+    dropped_patch_count: u32,
     patch_buffer_ptr: *mut GpuTerrainPatch,
 
     indirection_texture: ID3D12Resource,
     indirection_texture_upload: ID3D12Resource,
     indirection_texture_ptr: *mut UVec2,
     indirection_texture_size: usize,
+    indirection_slot_count: u32,
+    indirection_uploaded_slot_count: usize,
 
     height_atlas: ID3D12Resource,
     height_atlas_upload: ID3D12Resource,
@@ -115,77 +96,31 @@ impl TerrainData {
         device: &ID3D12Device4,
         resource_heap: &DescriptorHeap,
         root_signature: &ID3D12RootSignature,
+        config: TerrainConfig,
+        height_source: Arc<dyn HeightSource>,
+        disk_cache: Option<Arc<DiskPatchCache>>,
     ) -> Result<Self> {
-        let patch_indices = {
-            let mut indices = Vec::with_capacity(PATCH_INDEX_COUNT as usize);
-
-            for z in 0..PATCH_SIDE_QUAD_COUNT {
-                for x in 0..PATCH_SIDE_QUAD_COUNT {
-                    let top_left = z * PATCH_SIDE_VERTEX_COUNT + x;
-                    let top_right = top_left + 1;
-                    let bottom_left = top_left + PATCH_SIDE_VERTEX_COUNT;
-                    let bottom_right = bottom_left + 1;
-
-                    if (x + z) % 2 == 0 {
-                        indices.extend_from_slice(&[
-                            top_left,
-                            bottom_left,
-                            bottom_right,
-                            top_left,
-                            bottom_right,
-                            top_right,
-                        ]);
-                    } else {
-                        indices.extend_from_slice(&[
-                            top_left,
-                            bottom_left,
-                            top_right,
-                            top_right,
-                            bottom_left,
-                            bottom_right,
-                        ]);
-                    }
-                }
-            }
+        let render_distance = 2048;
+        let lod_factor = 3.0;
 
-            indices
-        };
+        config.validate(render_distance)?;
+
+        let planner = TerrainPlanner::new(
+            config,
+            render_distance,
+            LodPolicy::Distance { lod_factor },
+            height_source,
+            disk_cache,
+        );
+        let coverage = planner.coverage()?;
+
+        let patch_indices = patch_indices(config.patch_pixel_size);
         let patch_index_buffer =
             ID3D12Resource::new_buffer(device, D3D12_HEAP_TYPE_UPLOAD, size_of_val(patch_indices.as_slice()))?;
 
         patch_index_buffer.map_and_write(patch_indices.as_slice())?;
 
-        let render_distance = 2048;
-        let lod_factor = 3.0;
-
-        let max_patch_count = ((render_distance * 2) / PATCH_WORLD_SIZE).pow(2); // should be somehow recalculated
-        let patch_buffer = ID3D12Resource::new_buffer(
-            device,
-            D3D12_HEAP_TYPE_UPLOAD,
-            (max_patch_count * FRAME_COUNT) as usize * size_of::<GpuTerrainPatch>(),
-        )?;
-
         unsafe {
-            for i in 0..FRAME_COUNT {
-                device.CreateShaderResourceView(
-                    &patch_buffer,
-                    Some(&D3D12_SHADER_RESOURCE_VIEW_DESC {
-                        Format: DXGI_FORMAT_UNKNOWN,
-                        ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
-                        Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
-                        Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
-                            Buffer: D3D12_BUFFER_SRV {
-                                FirstElement: (i * max_patch_count) as u64,
-                                NumElements: max_patch_count,
-                                StructureByteStride: size_of::<GpuTerrainPatch>() as u32,
-                                Flags: D3D12_BUFFER_SRV_FLAG_NONE,
-                            },
-                        },
-                    }),
-                    resource_heap.get_cpu_handle(GpuResource::TerrainPatchBufferFirst as u32 + i),
-                );
-            }
-
             device.CreateShaderResourceView(
                 &patch_index_buffer,
                 Some(&D3D12_SHADER_RESOURCE_VIEW_DESC {
@@ -205,66 +140,13 @@ impl TerrainData {
             );
         }
 
-        let get_texture_size = |texture: &ID3D12Resource| -> usize {
-            let desc = unsafe { texture.GetDesc() };
-            let mut size = 0;
-
-            unsafe {
-                device.GetCopyableFootprints(
-                    &desc,
-                    0,
-                    (desc.MipLevels * desc.DepthOrArraySize) as u32,
-                    0,
-                    None,
-                    None,
-                    None,
-                    Some(&mut size),
-                );
-            }
-
-            size as usize
-        };
-
-        let indirection_format = DXGI_FORMAT_R32G32_UINT;
-        let indirection_texture = ID3D12Resource::new_texture_2d(
-            device,
-            indirection_format,
-            INDIRECTION_SLOT_COUNT,
-            INDIRECTION_SLOT_COUNT,
-            PATCH_LOD_COUNT,
-        )?;
-        let indirection_texture_size = get_texture_size(&indirection_texture);
-        let indirection_texture_upload = ID3D12Resource::new_buffer(
-            device,
-            D3D12_HEAP_TYPE_UPLOAD,
-            indirection_texture_size * FRAME_COUNT as usize,
-        )?;
-
-        indirection_texture.set_debug_name("TerrainIndirection")?;
-        indirection_texture_upload.set_debug_name("TerrainIndirectionUpload")?;
-
-        unsafe {
-            device.CreateShaderResourceView(
-                &indirection_texture,
-                Some(&D3D12_SHADER_RESOURCE_VIEW_DESC {
-                    Format: indirection_format,
-                    ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
-                    Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
-                    Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
-                        Texture2D: D3D12_TEX2D_SRV {
-                            MostDetailedMip: 0,
-                            MipLevels: PATCH_LOD_COUNT,
-                            PlaneSlice: 0,
-                            ResourceMinLODClamp: 0.0,
-                        },
-                    },
-                }),
-                resource_heap.get_cpu_handle(GpuResource::TerrainIndirectionTexture as u32),
-            );
-        }
+        let patch_buffer = create_patch_buffer(device, resource_heap, coverage.patch_capacity)?;
+        let (indirection_texture, indirection_texture_upload, indirection_texture_size) =
+            create_indirection_texture(device, resource_heap, &config, coverage.indirection_slot_count)?;
 
-        let height_atlas = ID3D12Resource::new_texture_2d(device, HEIGHT_ATLAS_FORMAT, ATLAS_SIZE, ATLAS_SIZE, 1)?;
-        let height_atlas_size = get_texture_size(&height_atlas);
+        let atlas_size = config.atlas_size();
+        let height_atlas = ID3D12Resource::new_texture_2d(device, HEIGHT_ATLAS_FORMAT, atlas_size, atlas_size, 1)?;
+        let height_atlas_size = copyable_size(device, &height_atlas);
         let height_atlas_upload =
             ID3D12Resource::new_buffer(device, D3D12_HEAP_TYPE_UPLOAD, height_atlas_size * FRAME_COUNT as usize)?;
 
@@ -352,37 +234,31 @@ impl TerrainData {
             };
 
         Ok(Self {
-            render_distance,
-            lod_factor,
+            planner,
 
-            height_scale: 15.0,
-            world_scale: 1.0,
+            height_scale: TerrainScale::default().height_scale,
+            world_scale: TerrainScale::default().world_scale,
+            lod_factor,
+            screen_space_error_lod: false,
+            max_pixel_error: 2.0,
+            coverage,
+            coverage_error: None,
 
             solid_mode: false,
             wireframe_mode: true,
             stitching_enabled: true,
+            morphing_enabled: true,
             freeze_camera: false,
             camera_pos: Vec3::ZERO,
+            frustum_culling: true,
+            freeze_culling: false,
+            culling_frustum: None,
 
-            cam_world_index: IVec2::ZERO,
-            leaf_patches: Vec::new(),
             gpu_patch_count: 0,
 
-            patch_cache: HashMap::new(),
-            patch_gen_pool: PatchGenPool::new(),
-            atlas_free_slots: {
-                let mut free_slots = Vec::with_capacity((ATLAS_PATCH_COUNT * ATLAS_PATCH_COUNT) as usize);
-                for y in (0..ATLAS_PATCH_COUNT).rev() {
-                    for x in (0..ATLAS_PATCH_COUNT).rev() {
-                        free_slots.push(UVec2::new(x, y));
-                    }
-                }
-
-                free_slots
-            },
-
             patch_index_buffer,
-            patch_buffer_item_count: max_patch_count,
+            patch_buffer_item_count: coverage.patch_capacity,
+            dropped_patch_count: 0,
             patch_buffer_ptr: patch_buffer.map::<GpuTerrainPatch>()?,
             patch_buffer,
 
@@ -390,6 +266,8 @@ impl TerrainData {
             indirection_texture_upload,
             indirection_texture,
             indirection_texture_size,
+            indirection_slot_count: coverage.indirection_slot_count,
+            indirection_uploaded_slot_count: 0,
 
             height_atlas_ptr: height_atlas_upload.map::<f32>()?,
             height_atlas_upload,
@@ -407,84 +285,122 @@ impl TerrainData {
         })
     }
 
-    pub fn collect_leaf_patches(&mut self, camera_pos: &Vec3, active_frame_index: u32) -> Result<()> {
-        if !self.freeze_camera {
-            self.camera_pos = *camera_pos;
+    // The same debug toggles as the checkboxes, for bound actions.
+    pub fn handle_actions(&mut self, actions: &ActionState) {
+        let toggles = [
+            (Action::ToggleSolid, &mut self.solid_mode),
+            (Action::ToggleWireframe, &mut self.wireframe_mode),
+            (Action::ToggleStitching, &mut self.stitching_enabled),
+            (Action::ToggleMorphing, &mut self.morphing_enabled),
+            (Action::FreezeCamera, &mut self.freeze_camera),
+            (Action::ToggleFrustumCulling, &mut self.frustum_culling),
+            (Action::FreezeCulling, &mut self.freeze_culling),
+        ];
+
+        for (action, enabled) in toggles {
+            if actions.pressed(action) {
+                *enabled = !*enabled;
+            }
         }
+    }
 
-        let qtree = PatchQuadTree::new(&self.camera_pos, self.render_distance, self.lod_factor);
-
-        self.leaf_patches = qtree.collect_leafs();
-        self.cam_world_index = self.camera_pos.xz().as_ivec2() / PATCH_WORLD_SIZE as i32;
+    // Applies the LOD settings and clamps a render distance the indirection cannot grow to. Returns whether the patch
+    // buffer or indirection texture no longer fit, they are replaced by `reallocate_coverage_buffers`.
+    pub fn update_coverage(&mut self, camera: &Camera) -> bool {
+        self.planner.scale = self.scale();
+        self.planner.lod_policy = if self.screen_space_error_lod {
+            LodPolicy::screen_space_error(self.max_pixel_error, camera.view_to_clip(), HEIGHT, self.lod_factor)
+        } else {
+            LodPolicy::Distance {
+                lod_factor: self.lod_factor,
+            }
+        };
 
-        let mut missing_patches = self
-            .leaf_patches
-            .iter()
-            .filter(|l| !self.patch_cache.contains_key(l))
-            .collect::<Vec<_>>();
+        self.coverage = match self.planner.coverage() {
+            Ok(coverage) => {
+                if coverage.render_distance != self.coverage.render_distance {
+                    self.coverage_error = None;
+                }
+                coverage
+            }
+            Err(error) => {
+                self.coverage_error = Some(error);
+                self.planner.render_distance = self
+                    .planner
+                    .config()
+                    .clamp_render_distance(self.planner.render_distance);
+                self.planner.coverage().unwrap()
+            }
+        };
 
-        missing_patches.sort_unstable_by(|a, b| {
-            let distance_a = (self.camera_pos - a.world_center().extend(0).xzy().as_vec3()).length_squared();
-            let distance_b = (self.camera_pos - b.world_center().extend(0).xzy().as_vec3()).length_squared();
+        self.coverage.patch_capacity != self.patch_buffer_item_count
+            || self.coverage.indirection_slot_count != self.indirection_slot_count
+    }
 
-            distance_a.total_cmp(&distance_b)
-        });
+    // The GPU must be done with every frame in flight, the descriptors are rewritten in place.
+    pub fn reallocate_coverage_buffers(
+        &mut self,
+        device: &ID3D12Device4,
+        resource_heap: &DescriptorHeap,
+    ) -> Result<()> {
+        let coverage = self.coverage;
 
-        for result in self.patch_gen_pool.drain_results() {
-            self.patch_cache
-                .insert(result.request, PatchState::Generated(result.height_map));
+        if coverage.patch_capacity != self.patch_buffer_item_count {
+            self.patch_buffer = create_patch_buffer(device, resource_heap, coverage.patch_capacity)?;
+            self.patch_buffer_ptr = self.patch_buffer.map::<GpuTerrainPatch>()?;
+            self.patch_buffer_item_count = coverage.patch_capacity;
         }
 
-        for &key in missing_patches {
-            self.patch_gen_pool.requst_patch_generation(key);
-            self.patch_cache.insert(key, PatchState::Requested);
+        if coverage.indirection_slot_count != self.indirection_slot_count {
+            let (texture, upload, size) = create_indirection_texture(
+                device,
+                resource_heap,
+                self.planner.config(),
+                coverage.indirection_slot_count,
+            )?;
+
+            self.indirection_texture_ptr = upload.map::<UVec2>()?;
+            self.indirection_texture = texture;
+            self.indirection_texture_upload = upload;
+            self.indirection_texture_size = size;
+            self.indirection_slot_count = coverage.indirection_slot_count;
         }
 
-        let is_neighbor_coarser = |node: &PatchKey, direction: IVec2| -> bool {
-            let probe = node.world_center() + direction * node.world_size() as i32;
-
-            let neighbor_lod_index = self
-                .leaf_patches
-                .iter()
-                .find(|l| (l.world_center() - probe).length_squared() < node.world_size().pow(2) as i32)
-                .map(|l| l.lod_index)
-                .unwrap_or(node.lod_index);
+        Ok(())
+    }
 
-            neighbor_lod_index > node.lod_index
-        };
+    pub fn collect_leaf_patches(
+        &mut self,
+        camera: &Camera,
+        cpu_frame_index: u64,
+        active_frame_index: u32,
+    ) -> Result<()> {
+        if !self.freeze_camera {
+            self.camera_pos = *camera.position();
+        }
 
-        let gpu_patches: Vec<_> = self
-            .leaf_patches
-            .iter()
-            .filter(|l| {
-                self.patch_cache
-                    .get(l)
-                    .is_some_and(|s| matches!(s, PatchState::Resident(_)))
-            })
-            .map(|l| {
-                let directions = [
-                    (StitchMask::TOP, IVec2::NEG_Y),
-                    (StitchMask::BOTTOM, IVec2::Y),
-                    (StitchMask::LEFT, IVec2::NEG_X),
-                    (StitchMask::RIGHT, IVec2::X),
-                ];
-
-                let mut stitch_mask = StitchMask::empty();
-
-                for &(flag, direction) in &directions {
-                    if is_neighbor_coarser(l, direction) {
-                        stitch_mask.insert(flag);
-                    }
-                }
+        if !self.freeze_culling || self.culling_frustum.is_none() {
+            self.culling_frustum = Some(Frustum::from_world_to_clip(&camera.world_to_clip()));
+        }
 
-                GpuTerrainPatch {
-                    world_index: l.world_index,
-                    lod_index: l.lod_index,
-                    stitch_mask,
-                }
+        let frustum = self.culling_frustum.as_ref().filter(|_| self.frustum_culling);
+        let mut gpu_patches: Vec<_> = self
+            .planner
+            .collect_leaf_patches(&self.camera_pos, frustum, cpu_frame_index)
+            .into_iter()
+            .map(|p| GpuTerrainPatch {
+                world_index: p.key.world_index,
+                lod_index: p.key.lod_index,
+                stitch_mask: p.stitch_mask,
             })
             .collect();
 
+        // `TerrainCoverage::patch_capacity` bounds the leafs for any camera position. Should that ever be wrong, patches
+        // go missing and are reported instead of the copy below writing past the buffer.
+        let capacity = self.patch_buffer_item_count as usize;
+        self.dropped_patch_count = gpu_patches.len().saturating_sub(capacity) as u32;
+        gpu_patches.truncate(capacity);
+
         unsafe {
             std::ptr::copy_nonoverlapping(
                 gpu_patches.as_ptr(),
@@ -499,6 +415,31 @@ impl TerrainData {
         Ok(())
     }
 
+    pub fn scale(&self) -> TerrainScale {
+        TerrainScale {
+            world_scale: self.world_scale,
+            height_scale: self.height_scale,
+        }
+    }
+
+    pub fn height_at(&self, pos: Vec2) -> Option<TerrainSample<f32>> {
+        self.planner.height_field().height_at(pos, &self.scale())
+    }
+
+    pub fn normal_at(&self, pos: Vec2) -> Option<TerrainSample<Vec3>> {
+        self.planner.height_field().normal_at(pos, &self.scale())
+    }
+
+    pub fn slope_at(&self, pos: Vec2) -> Option<TerrainSample<f32>> {
+        self.planner.height_field().slope_at(pos, &self.scale())
+    }
+
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, max_distance: f32) -> Option<TerrainHit> {
+        self.planner
+            .height_field()
+            .raycast(origin, dir, max_distance, &self.scale())
+    }
+
     pub fn upload_atlas_data(
         &mut self,
         device: &ID3D12Device,
@@ -522,36 +463,24 @@ impl TerrainData {
         }
 
         let upload_byte_offset = active_frame_index as usize * self.height_atlas_size;
+        let atlas_patch_pixel_size = self.planner.config().atlas_patch_pixel_size();
 
-        let mut patches_to_update = Vec::new();
-
-        for (&key, state) in &self.patch_cache {
-            if let PatchState::Uploading(atlas_index, frame_index) = state
-                && *frame_index <= gpu_frame_index
-            {
-                patches_to_update.push((key, PatchState::Resident(*atlas_index)));
-                continue;
-            }
-
-            let PatchState::Generated(height_data) = state else {
-                continue;
-            };
-
-            let atlas_index = self.atlas_free_slots.pop().unwrap();
+        for upload in self.planner.collect_atlas_uploads(cpu_frame_index, gpu_frame_index) {
+            let atlas_index = upload.atlas_index;
             let atlas_row_pitch = atlas_layout.Footprint.RowPitch;
 
-            let patch_offset_bytes = atlas_index.y * ATLAS_PATCH_PIXEL_SIZE * atlas_row_pitch
-                + atlas_index.x * ATLAS_PATCH_PIXEL_SIZE * size_of::<f32>() as u32;
+            let patch_offset_bytes = atlas_index.y * atlas_patch_pixel_size * atlas_row_pitch
+                + atlas_index.x * atlas_patch_pixel_size * size_of::<f32>() as u32;
 
-            for row in 0..ATLAS_PATCH_PIXEL_SIZE {
-                let src_offset = row * ATLAS_PATCH_PIXEL_SIZE;
+            for row in 0..atlas_patch_pixel_size {
+                let src_offset = row * atlas_patch_pixel_size;
                 let dst_offset = patch_offset_bytes + row * atlas_row_pitch;
 
                 unsafe {
                     std::ptr::copy_nonoverlapping(
-                        height_data.as_ptr().add(src_offset as usize),
+                        upload.height_map.as_ptr().add(src_offset as usize),
                         self.height_atlas_ptr.byte_add(upload_byte_offset + dst_offset as usize),
-                        ATLAS_PATCH_PIXEL_SIZE as usize,
+                        atlas_patch_pixel_size as usize,
                     );
                 }
             }
@@ -563,8 +492,8 @@ impl TerrainData {
                         Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
                         Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 { SubresourceIndex: 0 },
                     },
-                    atlas_index.x * ATLAS_PATCH_PIXEL_SIZE,
-                    atlas_index.y * ATLAS_PATCH_PIXEL_SIZE,
+                    atlas_index.x * atlas_patch_pixel_size,
+                    atlas_index.y * atlas_patch_pixel_size,
                     0,
                     &D3D12_TEXTURE_COPY_LOCATION {
                         pResource: std::mem::transmute_copy(&self.height_atlas_upload),
@@ -574,8 +503,8 @@ impl TerrainData {
                                 Offset: upload_byte_offset as u64 + patch_offset_bytes as u64,
                                 Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
                                     Format: HEIGHT_ATLAS_FORMAT,
-                                    Width: ATLAS_PATCH_PIXEL_SIZE,
-                                    Height: ATLAS_PATCH_PIXEL_SIZE,
+                                    Width: atlas_patch_pixel_size,
+                                    Height: atlas_patch_pixel_size,
                                     Depth: 1,
                                     RowPitch: atlas_row_pitch,
                                 },
@@ -585,58 +514,35 @@ impl TerrainData {
                     None,
                 );
             }
-
-            patches_to_update.push((key, PatchState::Uploading(atlas_index, cpu_frame_index)));
-        }
-
-        for (key, state) in patches_to_update {
-            self.patch_cache.insert(key, state);
         }
 
         Ok(())
     }
 
+    // Copies only the rows that changed, each one from where the full footprint would place it in the upload buffer.
     pub fn upload_indirection_data(
-        &self,
+        &mut self,
         device: &ID3D12Device,
         cmd_list: &ID3D12GraphicsCommandList,
         active_frame_index: u32,
     ) -> Result<()> {
-        let empty_patch = UVec2::splat(ATLAS_PATCH_COUNT);
-
-        let mut resident_patch_lods: [Vec<UVec2>; PATCH_LOD_COUNT as usize] = std::array::from_fn(|i| {
-            let slot_count = INDIRECTION_SLOT_COUNT >> i;
-            vec![empty_patch; slot_count.pow(2) as usize]
-        });
-
-        for (key, state) in &self.patch_cache {
-            let PatchState::Resident(atlas_index) = state else {
-                continue;
-            };
-
-            let lod_index = key.lod_index;
-            let slot_count = INDIRECTION_SLOT_COUNT >> lod_index;
-
-            let relative_index = (key.world_index >> lod_index) - (self.cam_world_index >> lod_index);
-            let indirection_index = relative_index + slot_count as i32 / 2;
-
-            let range = 0..slot_count as i32;
-            if !range.contains(&indirection_index.x) || !range.contains(&indirection_index.y) {
-                continue;
-            }
-
-            let flat_indirection_index = indirection_index.y as u32 * slot_count + indirection_index.x as u32;
-            resident_patch_lods[lod_index as usize][flat_indirection_index as usize] = *atlas_index;
+        let updates = self.planner.take_indirection_updates();
+        self.indirection_uploaded_slot_count = updates.iter().map(|u| u.columns.len()).sum();
+        if updates.is_empty() {
+            return Ok(());
         }
 
+        let indirection = self.planner.indirection();
+        debug_assert_eq!(indirection.slot_count(0), self.indirection_slot_count);
+
         let desc = unsafe { self.indirection_texture.GetDesc() };
-        let mut layouts = vec![D3D12_PLACED_SUBRESOURCE_FOOTPRINT::default(); PATCH_LOD_COUNT as usize];
+        let mut layouts = vec![D3D12_PLACED_SUBRESOURCE_FOOTPRINT::default(); indirection.lod_count() as usize];
 
         unsafe {
             device.GetCopyableFootprints(
                 &desc,
                 0,
-                PATCH_LOD_COUNT,
+                indirection.lod_count(),
                 0,
                 Some(layouts.as_mut_ptr()),
                 None,
@@ -647,30 +553,24 @@ impl TerrainData {
 
         let upload_byte_offset = active_frame_index as usize * self.indirection_texture_size;
 
-        for lod_index in 0..PATCH_LOD_COUNT {
-            let slot_count = INDIRECTION_SLOT_COUNT >> lod_index;
+        for update in &updates {
+            let lod_index = update.lod_index;
+            let slot_count = indirection.slot_count(lod_index);
 
             let gpu_layout = layouts[lod_index as usize];
-            let gpu_row_pitch = gpu_layout.Footprint.RowPitch;
-            let gpu_lod_offset = gpu_layout.Offset;
-
-            for row_index in 0..slot_count {
-                let cpu_offset = row_index * slot_count;
-                let gpu_offset = gpu_lod_offset + (row_index * gpu_row_pitch) as u64;
-
-                unsafe {
-                    std::ptr::copy_nonoverlapping(
-                        resident_patch_lods[lod_index as usize]
-                            .as_ptr()
-                            .add(cpu_offset as usize),
-                        self.indirection_texture_ptr
-                            .byte_add(upload_byte_offset + gpu_offset as usize),
-                        slot_count as usize,
-                    );
-                }
-            }
+            let gpu_offset = gpu_layout.Offset
+                + (update.row * gpu_layout.Footprint.RowPitch) as u64
+                + (update.columns.start as usize * size_of::<UVec2>()) as u64;
+            let cpu_offset = update.row * slot_count + update.columns.start;
 
             unsafe {
+                std::ptr::copy_nonoverlapping(
+                    indirection.lod(lod_index).as_ptr().add(cpu_offset as usize),
+                    self.indirection_texture_ptr
+                        .byte_add(upload_byte_offset + gpu_offset as usize),
+                    update.columns.len(),
+                );
+
                 cmd_list.CopyTextureRegion(
                     &D3D12_TEXTURE_COPY_LOCATION {
                         pResource: std::mem::transmute_copy(&self.indirection_texture),
@@ -679,20 +579,27 @@ impl TerrainData {
                             SubresourceIndex: lod_index,
                         },
                     },
-                    0,
-                    0,
+                    update.columns.start,
+                    update.row,
                     0,
                     &D3D12_TEXTURE_COPY_LOCATION {
                         pResource: std::mem::transmute_copy(&self.indirection_texture_upload),
                         Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
                         Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
                             PlacedFootprint: D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
-                                Offset: upload_byte_offset as u64 + gpu_lod_offset,
-                                Footprint: layouts[lod_index as usize].Footprint,
+                                Offset: upload_byte_offset as u64 + gpu_layout.Offset,
+                                Footprint: gpu_layout.Footprint,
                             },
                         },
                     },
-                    None,
+                    Some(&D3D12_BOX {
+                        left: update.columns.start,
+                        top: update.row,
+                        front: 0,
+                        right: update.columns.end,
+                        bottom: update.row + 1,
+                        back: 1,
+                    }),
                 );
             }
         }
@@ -701,18 +608,29 @@ impl TerrainData {
     }
 
     pub fn render(&self, cmd_list: &ID3D12GraphicsCommandList, camera: &Camera, active_frame_index: u32) {
+        let config = self.planner.config();
+
+        let mut morph_ranges = [Vec4::ZERO; MAX_PATCH_LOD_COUNT as usize];
+        for (gpu_range, range) in morph_ranges.iter_mut().zip(self.planner.morph_ranges()) {
+            *gpu_range = Vec4::new(range.start, range.end, 0.0, 0.0);
+        }
+
         let mut consts = GpuTerrainConsts {
             world_to_clip: camera.world_to_clip(),
-            cam_world_index: self.cam_world_index,
+            morph_ranges,
+            camera_position: self.camera_pos,
+            morphing_enabled: self.morphing_enabled.into(),
             world_scale: self.world_scale,
             height_scale: self.height_scale,
             wireframe_pass: false.into(),
             stitching_enabled: self.stitching_enabled.into(),
             active_patch_buffer_index: GpuResource::TerrainPatchBufferFirst as u32 + active_frame_index,
+            patch_pixel_size: config.patch_pixel_size,
+            indirection_slot_count: self.indirection_slot_count,
         };
 
         let render_terrain = |vertex_pso: &ID3D12PipelineState| {
-            if self.leaf_patches.is_empty() {
+            if self.planner.leaf_patches().is_empty() {
                 return;
             }
 
@@ -721,11 +639,11 @@ impl TerrainData {
                 cmd_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
                 cmd_list.IASetIndexBuffer(Some(&D3D12_INDEX_BUFFER_VIEW {
                     BufferLocation: self.patch_index_buffer.GetGPUVirtualAddress(),
-                    SizeInBytes: PATCH_INDEX_COUNT * size_of::<f32>() as u32,
+                    SizeInBytes: config.patch_index_count() * size_of::<f32>() as u32,
                     Format: DXGI_FORMAT_R32_UINT,
                 }));
 
-                cmd_list.DrawIndexedInstanced(PATCH_INDEX_COUNT, self.gpu_patch_count, 0, 0, 0);
+                cmd_list.DrawIndexedInstanced(config.patch_index_count(), self.gpu_patch_count, 0, 0, 0);
             }
         };
 
@@ -754,8 +672,47 @@ impl TerrainData {
         unsafe {
             ImGui_Begin(c"Terrain".as_ptr(), null_mut(), 0);
 
-            ImGui_InputInt(c"Render distance".as_ptr(), &mut self.render_distance as *mut u32 as _);
+            ImGui_InputInt(
+                c"Render distance".as_ptr(),
+                &mut self.planner.render_distance as *mut u32 as _,
+            );
+            if let Some(error) = &self.coverage_error {
+                let text = std::ffi::CString::new(format!("Clamped: {}", error)).unwrap();
+                ImGui_TextColoredUnformatted(
+                    ImVec4 {
+                        x: 1.0,
+                        y: 0.3,
+                        z: 0.3,
+                        w: 1.0,
+                    },
+                    text.as_ptr(),
+                );
+            }
+            if self.dropped_patch_count > 0 {
+                let text = std::ffi::CString::new(format!(
+                    "Patch buffer full: {} patches not drawn",
+                    self.dropped_patch_count
+                ))
+                .unwrap();
+                ImGui_TextColoredUnformatted(
+                    ImVec4 {
+                        x: 1.0,
+                        y: 0.3,
+                        z: 0.3,
+                        w: 1.0,
+                    },
+                    text.as_ptr(),
+                );
+            }
+            if ImGui_RadioButton(c"Distance LOD".as_ptr(), !self.screen_space_error_lod) {
+                self.screen_space_error_lod = false;
+            }
+            ImGui_SameLine();
+            if ImGui_RadioButton(c"Screen space error LOD".as_ptr(), self.screen_space_error_lod) {
+                self.screen_space_error_lod = true;
+            }
             ImGui_InputFloat(c"LOD factor".as_ptr(), &mut self.lod_factor);
+            ImGui_InputFloat(c"Max pixel error".as_ptr(), &mut self.max_pixel_error);
             ImGui_InputFloat(c"Height scale".as_ptr(), &mut self.height_scale);
             ImGui_InputFloat(c"World scale".as_ptr(), &mut self.world_scale);
 
@@ -763,33 +720,127 @@ impl TerrainData {
             ImGui_Checkbox(c"Solid mode".as_ptr(), &mut self.solid_mode);
             ImGui_Checkbox(c"Wireframe mode".as_ptr(), &mut self.wireframe_mode);
             ImGui_Checkbox(c"Stitching".as_ptr(), &mut self.stitching_enabled);
+            ImGui_Checkbox(c"Geomorphing".as_ptr(), &mut self.morphing_enabled);
+            ImGui_Checkbox(c"Freeze camera".as_ptr(), &mut self.freeze_camera);
+            ImGui_Checkbox(c"Frustum culling".as_ptr(), &mut self.frustum_culling);
+            ImGui_Checkbox(c"Freeze culling".as_ptr(), &mut self.freeze_culling);
 
             ImGui_NewLine();
 
-            let render_count = (self.render_distance * 2) / PATCH_WORLD_SIZE;
+            let render_count = (self.planner.render_distance * 2) / PATCH_WORLD_SIZE;
             let mut requested_count = 0;
             let mut generated_count = 0;
             let mut uploading_count = 0;
             let mut resident_count = 0;
 
-            for state in self.patch_cache.values() {
+            for state in self.planner.patch_cache().values() {
                 match state {
                     PatchState::Requested => requested_count += 1,
-                    PatchState::Generated(_) => generated_count += 1,
+                    PatchState::Generated(..) => generated_count += 1,
                     PatchState::Uploading(_, _) => uploading_count += 1,
                     PatchState::Resident(_) => resident_count += 1,
                 }
             }
 
+            imgui_text!(
+                "Indirection slots: {} ({} max render distance)",
+                self.indirection_slot_count,
+                TerrainConfig {
+                    indirection_slot_count: self.indirection_slot_count,
+                    ..*self.planner.config()
+                }
+                .max_render_distance()
+            );
+            imgui_text!("Indirection slots uploaded: {}", self.indirection_uploaded_slot_count);
+            imgui_text!(
+                "Patch capacity: {} (max leafs {})",
+                self.patch_buffer_item_count,
+                self.coverage.max_leaf_count
+            );
             imgui_text!("Render patch count: {}", render_count);
             imgui_text!("Render patch count ^2: {}", render_count.pow(2));
-            imgui_text!("Terrain patches (leafs): {}", self.leaf_patches.len());
-            imgui_text!("Cached: {}", self.patch_cache.len());
+            imgui_text!("Terrain patches (leafs): {}", self.planner.leaf_patches().len());
+            imgui_text!("Visible: {}", self.gpu_patch_count);
+            imgui_text!("Culled: {}", self.planner.culled_leaf_count());
+            imgui_text!(
+                "Leafs added/removed: {}/{}",
+                self.planner.leaf_diff().added.len(),
+                self.planner.leaf_diff().removed.len()
+            );
+            imgui_text!("Evaluated nodes: {}", self.planner.quad_tree().evaluated_node_count());
+            imgui_text!("Cached: {}", self.planner.patch_cache().len());
             imgui_text!("Requested: {}", requested_count);
             imgui_text!("Generated: {}", generated_count);
             imgui_text!("Uploading: {}", uploading_count);
             imgui_text!("Resident: {}", resident_count);
 
+            ImGui_NewLine();
+
+            let residency = self.planner.residency();
+            imgui_text!(
+                "Atlas free slots: {} / {}",
+                residency.free_slot_count(),
+                self.planner.config().atlas_patch_count.pow(2)
+            );
+            imgui_text!("Evicted: {}", residency.evicted_count());
+
+            ImGui_NewLine();
+
+            let ground_pos = self.camera_pos.xz();
+            match (
+                self.height_at(ground_pos),
+                self.normal_at(ground_pos),
+                self.slope_at(ground_pos),
+            ) {
+                (Some(height), Some(normal), Some(slope)) => {
+                    imgui_text!("Ground height: {:.2} (LOD {})", height.value, height.lod_index);
+                    imgui_text!(
+                        "Ground normal: {:.2} {:.2} {:.2}",
+                        normal.value.x,
+                        normal.value.y,
+                        normal.value.z
+                    );
+                    imgui_text!("Ground slope: {:.1} deg", slope.value.to_degrees());
+                    imgui_text!("Ground fallback LOD: {}", height.is_fallback);
+                }
+                _ => imgui_text!("Ground height: -"),
+            }
+
+            ImGui_NewLine();
+
+            let patch_gen_pool = self.planner.patch_gen_pool();
+            imgui_text!("Generation queue: {}", patch_gen_pool.queued_count());
+            imgui_text!("Generation cancelled: {}", patch_gen_pool.cancelled_count());
+            let generation_count = patch_gen_pool.generated_count();
+            imgui_text!(
+                "Generated patches: {} ({:.2} ms avg)",
+                generation_count,
+                patch_gen_pool.generation_time().as_secs_f64() * 1000.0 / generation_count.max(1) as f64
+            );
+
+            if let Some(disk_cache) = patch_gen_pool.disk_cache() {
+                let stats = disk_cache.stats();
+                imgui_text!(
+                    "Disk cache: {:.1} MB",
+                    disk_cache.total_bytes() as f64 / (1024.0 * 1024.0)
+                );
+                imgui_text!("Disk cache hits/misses: {} / {}", stats.hits, stats.misses);
+                imgui_text!("Disk cache corrupted/evicted: {} / {}", stats.corrupted, stats.evicted);
+
+                if let Some(error) = self.planner.cache_error() {
+                    let text = std::ffi::CString::new(format!("Disk cache: {}", error)).unwrap();
+                    ImGui_TextColoredUnformatted(
+                        ImVec4 {
+                            x: 1.0,
+                            y: 0.3,
+                            z: 0.3,
+                            w: 1.0,
+                        },
+                        text.as_ptr(),
+                    );
+                }
+            }
+
             ImGui_End();
         }
     }
@@ -804,7 +855,7 @@ impl TerrainData {
             }
 
             ImGui_SameLine();
-            imgui_text!("Render distance: {:.2}", self.render_distance);
+            imgui_text!("Render distance: {:.2}", self.planner.render_distance);
 
             let minimap_pos = Vec2::new(ImGui_GetCursorScreenPos().x, ImGui_GetCursorScreenPos().y);
             let minimap_size = {
@@ -844,11 +895,11 @@ impl TerrainData {
             }
 
             let minimap_center = minimap_pos + minimap_size * 0.5 + self.minimap_offset;
-            let minimap_scale = minimap_size / (self.render_distance as f32 * 2.0) * self.minimap_zoom;
+            let minimap_scale = minimap_size / (self.planner.render_distance as f32 * 2.0) * self.minimap_zoom;
 
             let draw_list = ImGui_GetWindowDrawList();
 
-            for leaf in &self.leaf_patches {
+            for leaf in self.planner.leaf_patches() {
                 let minimap_leaf_pos = minimap_center + leaf.world_pos().as_vec2() * minimap_scale;
                 let minimap_leaf_size = leaf.world_size() as f32 * minimap_scale;
 
@@ -899,12 +950,14 @@ impl TerrainData {
             );
 
             let start = self
-                .leaf_patches
+                .planner
+                .leaf_patches()
                 .iter()
                 .map(|l| l.world_pos())
                 .fold(IVec2::MAX, |acc, p| acc.min(p));
             let end = self
-                .leaf_patches
+                .planner
+                .leaf_patches()
                 .iter()
                 .map(|l| l.world_pos() + l.world_size() as i32)
                 .fold(IVec2::MIN, |acc, p| acc.max(p));
@@ -974,343 +1027,108 @@ impl TerrainData {
     }
 }
 
-enum PatchState {
-    Requested,
-    Generated(Vec<f32>),
-    Uploading(UVec2, u64),
-    Resident(UVec2),
-}
-
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-struct PatchKey {
-    world_index: IVec2,
-    lod_index: u32,
-}
-
-impl PatchKey {
-    fn world_pos(&self) -> IVec2 {
-        self.world_index * PATCH_WORLD_SIZE as i32
-    }
-
-    fn world_size(&self) -> u32 {
-        PATCH_WORLD_SIZE * 2_u32.pow(self.lod_index)
-    }
-
-    fn world_center(&self) -> IVec2 {
-        self.world_pos() + self.world_size() as i32 / 2
-    }
-}
-
-type PatchGenRequest = PatchKey;
-
-struct PatchGenResult {
-    request: PatchGenRequest,
-    height_map: Vec<f32>,
-}
-
-struct PatchGenPool {
-    workers: Vec<std::thread::JoinHandle<()>>,
-    request_sender: Option<Sender<PatchGenRequest>>,
-    result_receiver: Receiver<PatchGenResult>,
-}
-
-impl Drop for PatchGenPool {
-    fn drop(&mut self) {
-        drop(self.request_sender.take());
-
-        for worker in self.workers.drain(..) {
-            worker.join().unwrap();
-        }
-    }
-}
-
-impl PatchGenPool {
-    fn new() -> Self {
-        let (request_sender, request_receiver) = std::sync::mpsc::channel::<PatchGenRequest>();
-        let (result_sender, result_receiver) = std::sync::mpsc::channel::<PatchGenResult>();
-
-        let request_receiver = Arc::new(Mutex::new(request_receiver));
-
-        let fbm = Fbm::<Perlin>::new(123)
-            .set_octaves(8)
-            .set_frequency(1.0)
-            .set_lacunarity(2.0)
-            .set_persistence(0.5);
-
-        let workers = (0..PATCH_GEN_WORKER_COUNT)
-            .map(|i| {
-                let request_receiver = Arc::clone(&request_receiver);
-                let result_sender = result_sender.clone();
-                let fbm = fbm.clone();
-
-                std::thread::Builder::new()
-                    .name(format!("tile-generator-{}", i))
-                    .spawn(move || {
-                        loop {
-                            let request = request_receiver.lock().unwrap().recv();
-                            let Ok(request) = request else {
-                                break;
-                            };
-
-                            let noise_scale = 4.0_f64;
-                            let world_scale = 2048.0_f64;
-
-                            let fbm_pos = request.world_pos().as_dvec2() / world_scale * noise_scale;
-                            let fbm_size = request.world_size() as f64 / world_scale * noise_scale;
-                            let fbm_pixel_size =
-                                request.world_size() as f64 / PATCH_PIXEL_SIZE as f64 / world_scale * noise_scale;
-
-                            let instant = std::time::Instant::now();
-
-                            let height_map = PlaneMapBuilder::new(&fbm)
-                                .set_size(ATLAS_PATCH_PIXEL_SIZE as usize, ATLAS_PATCH_PIXEL_SIZE as usize)
-                                .set_x_bounds(fbm_pos.x, fbm_pos.x + fbm_size + fbm_pixel_size) // pixel overlap
-                                .set_y_bounds(fbm_pos.y, fbm_pos.y + fbm_size + fbm_pixel_size) // pixel overlap
-                                .build()
-                                .into_iter()
-                                .map(|n| (n as f32 * 1.5 + 0.3).clamp(0.0, 1.0))
-                                .collect::<Vec<_>>();
-
-                            {
-                                let ms = instant.elapsed().as_secs_f32() * 1000.0;
-                                let min = height_map.iter().cloned().fold(f32::INFINITY, f32::min);
-                                let max = height_map.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-
-                                println!("world={}, min={}, max={} ({:.2} ms)", request.world_index, min, max, ms);
-                            }
-
-                            result_sender.send(PatchGenResult { request, height_map }).unwrap();
-                        }
-                    })
-                    .unwrap()
-            })
-            .collect();
-
-        Self {
-            workers,
-            request_sender: Some(request_sender),
-            result_receiver,
-        }
-    }
-
-    fn requst_patch_generation(&self, request: PatchGenRequest) {
-        self.request_sender.as_ref().unwrap().send(request).unwrap()
-    }
-
-    fn drain_results(&self) -> impl Iterator<Item = PatchGenResult> + '_ {
-        self.result_receiver.try_iter()
-    }
-}
-
-#[derive(Clone)]
-struct PatchQuadNode {
-    key: PatchKey,
-    children: Option<Box<[PatchQuadNode; 4]>>,
-}
-
-impl PatchQuadNode {
-    fn root(cam_pos: &Vec3, render_distance: u32) -> Self {
-        let snap_size = PATCH_WORLD_SIZE * 2_u32.pow(PATCH_LOD_COUNT - 1);
-        let snapped_cam_pos = (cam_pos.xz() / snap_size as f32).round().as_ivec2() * snap_size as i32;
-
-        Self::new(
-            (snapped_cam_pos / PATCH_WORLD_SIZE as i32) - (render_distance / PATCH_WORLD_SIZE) as i32,
-            (render_distance * 2 / PATCH_WORLD_SIZE).ilog2(),
-        )
+fn copyable_size(device: &ID3D12Device4, texture: &ID3D12Resource) -> usize {
+    let desc = unsafe { texture.GetDesc() };
+    let mut size = 0;
+
+    unsafe {
+        device.GetCopyableFootprints(
+            &desc,
+            0,
+            (desc.MipLevels * desc.DepthOrArraySize) as u32,
+            0,
+            None,
+            None,
+            None,
+            Some(&mut size),
+        );
     }
 
-    fn new(world_index: IVec2, lod_index: u32) -> Self {
-        Self {
-            key: PatchKey { world_index, lod_index },
-            children: None,
-        }
-    }
+    size as usize
 }
 
-struct PatchQuadTree {
-    root: PatchQuadNode,
-}
-
-impl PatchQuadTree {
-    fn new(cam_pos: &Vec3, render_distance: u32, lod_factor: f32) -> Self {
-        let mut root = PatchQuadNode::root(cam_pos, render_distance);
-        Self::split_recursive(&mut root, cam_pos, lod_factor);
-
-        Self { root }
-    }
-
-    fn collect_leafs(&self) -> Vec<PatchKey> {
-        let mut leafs = Vec::new();
-        Self::traverse_node(&self.root, &mut leafs);
-
-        leafs
-    }
-
-    fn split_recursive(node: &mut PatchQuadNode, cam_pos: &Vec3, lod_factor: f32) {
-        let distance = (cam_pos - node.key.world_center().extend(0).xzy().as_vec3()).length();
-        if distance >= (node.key.world_size() as f32 * 0.5 * lod_factor) && node.key.lod_index <= (PATCH_LOD_COUNT - 1)
-        {
-            return;
-        }
-
-        let next_lod_index = node.key.lod_index - 1;
-        let next_offset = 2_u32.pow(next_lod_index) as i32;
-
-        node.children = Some(Box::new([
-            PatchQuadNode::new(node.key.world_index + IVec2::ZERO * next_offset, next_lod_index),
-            PatchQuadNode::new(node.key.world_index + IVec2::X * next_offset, next_lod_index),
-            PatchQuadNode::new(node.key.world_index + IVec2::Y * next_offset, next_lod_index),
-            PatchQuadNode::new(node.key.world_index + IVec2::ONE * next_offset, next_lod_index),
-        ]));
-
-        if next_lod_index == 0 {
-            return;
-        }
-
-        for child in node.children.as_mut().unwrap().iter_mut() {
-            Self::split_recursive(child, cam_pos, lod_factor);
-        }
-    }
-
-    fn traverse_node(node: &PatchQuadNode, leafs: &mut Vec<PatchKey>) {
-        if node.children.is_none() {
-            leafs.push(node.key);
-            return;
-        }
-
-        for child in node.children.as_ref().unwrap().iter() {
-            Self::traverse_node(child, leafs);
+// One range of `patch_capacity` patches per frame in flight.
+fn create_patch_buffer(
+    device: &ID3D12Device4,
+    resource_heap: &DescriptorHeap,
+    patch_capacity: u32,
+) -> Result<ID3D12Resource> {
+    let patch_buffer = ID3D12Resource::new_buffer(
+        device,
+        D3D12_HEAP_TYPE_UPLOAD,
+        (patch_capacity * FRAME_COUNT) as usize * size_of::<GpuTerrainPatch>(),
+    )?;
+
+    patch_buffer.set_debug_name("TerrainPatches")?;
+
+    unsafe {
+        for i in 0..FRAME_COUNT {
+            device.CreateShaderResourceView(
+                &patch_buffer,
+                Some(&D3D12_SHADER_RESOURCE_VIEW_DESC {
+                    Format: DXGI_FORMAT_UNKNOWN,
+                    ViewDimension: D3D12_SRV_DIMENSION_BUFFER,
+                    Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+                    Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                        Buffer: D3D12_BUFFER_SRV {
+                            FirstElement: (i * patch_capacity) as u64,
+                            NumElements: patch_capacity,
+                            StructureByteStride: size_of::<GpuTerrainPatch>() as u32,
+                            Flags: D3D12_BUFFER_SRV_FLAG_NONE,
+                        },
+                    },
+                }),
+                resource_heap.get_cpu_handle(GpuResource::TerrainPatchBufferFirst as u32 + i),
+            );
         }
     }
-}
 
-#[allow(unused)]
-struct MapData {
-    height_mips: Vec<Vec<f32>>,
-    normal_mips: Vec<Vec<Vec3>>,
+    Ok(patch_buffer)
 }
 
-#[allow(unused)]
-struct MapGeneratorParams {
-    size: usize,
-    scale: f32,
-    octaves: usize,
-    frequency: f64,
-    lacunarity: f64,
-    persistence: f64,
-    seed: u32,
-}
-
-#[allow(unused)]
-impl MapGeneratorParams {
-    fn new(size: usize) -> Self {
-        Self {
-            size,
-            scale: 7.0,
-            octaves: 6,
-            frequency: 1.0,
-            lacunarity: 2.0,
-            persistence: 0.5,
-            seed: 123,
-        }
-    }
-
-    fn generate(&self, terrain_size: usize) -> MapData {
-        let fbm = Fbm::<Perlin>::new(self.seed)
-            .set_octaves(self.octaves)
-            .set_frequency(self.frequency)
-            .set_lacunarity(self.lacunarity)
-            .set_persistence(self.persistence);
-
-        let height_map = PlaneMapBuilder::new(fbm)
-            .set_size(self.size, self.size)
-            .set_x_bounds(0.0, self.scale as f64)
-            .set_y_bounds(0.0, self.scale as f64)
-            .build()
-            .into_iter()
-            .map(|n| n as f32)
-            .collect::<Vec<_>>();
-
-        let min = height_map.iter().cloned().fold(f32::INFINITY, f32::min);
-        let max = height_map.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
-        println!("height map: min={}, max={}", min, max);
-
-        let height_map = height_map.iter().map(|n| (n - min) / (max - min)).collect::<Vec<_>>();
-        let normal_map = self.generate_normals(height_map.as_slice(), terrain_size);
-
-        MapData {
-            height_mips: self.generate_mips(height_map, |s0, s1, s2, s3| (s0 + s1 + s2 + s3) * 0.25),
-            normal_mips: self.generate_mips(normal_map, |s0, s1, s2, s3| {
-                let n = (s0 + s1 + s2 + s3) * 0.25;
-
-                if n.length_squared() > 1e-8 {
-                    n.normalize()
-                } else {
-                    Vec3::Y
-                }
+// Returns the texture with one mip per LOD, its upload buffer for every frame in flight and the upload size of a frame.
+fn create_indirection_texture(
+    device: &ID3D12Device4,
+    resource_heap: &DescriptorHeap,
+    config: &TerrainConfig,
+    slot_count: u32,
+) -> Result<(ID3D12Resource, ID3D12Resource, usize)> {
+    let indirection_format = DXGI_FORMAT_R32G32_UINT;
+    let indirection_texture =
+        ID3D12Resource::new_texture_2d(device, indirection_format, slot_count, slot_count, config.lod_count)?;
+    let indirection_texture_size = copyable_size(device, &indirection_texture);
+    let indirection_texture_upload = ID3D12Resource::new_buffer(
+        device,
+        D3D12_HEAP_TYPE_UPLOAD,
+        indirection_texture_size * FRAME_COUNT as usize,
+    )?;
+
+    indirection_texture.set_debug_name("TerrainIndirection")?;
+    indirection_texture_upload.set_debug_name("TerrainIndirectionUpload")?;
+
+    unsafe {
+        device.CreateShaderResourceView(
+            &indirection_texture,
+            Some(&D3D12_SHADER_RESOURCE_VIEW_DESC {
+                Format: indirection_format,
+                ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+                Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+                Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    Texture2D: D3D12_TEX2D_SRV {
+                        MostDetailedMip: 0,
+                        MipLevels: config.lod_count,
+                        PlaneSlice: 0,
+                        ResourceMinLODClamp: 0.0,
+                    },
+                },
             }),
-        }
+            resource_heap.get_cpu_handle(GpuResource::TerrainIndirectionTexture as u32),
+        );
     }
 
-    fn generate_mips<T, F>(&self, data: Vec<T>, mut downsample: F) -> Vec<Vec<T>>
-    where
-        T: Copy,
-        F: FnMut(T, T, T, T) -> T,
-    {
-        let mut mips = vec![data];
-        let mut current_size = self.size;
-
-        while current_size > 1 {
-            current_size /= 2;
-
-            let prev = mips.last().unwrap();
-            let mip = (0..current_size * current_size)
-                .map(|i| {
-                    let x = (i % current_size) * 2;
-                    let y = (i / current_size) * 2;
-                    let prev_size = current_size * 2;
-
-                    let s00 = prev[y * prev_size + x];
-                    let s10 = prev[y * prev_size + x + 1];
-                    let s01 = prev[(y + 1) * prev_size + x];
-                    let s11 = prev[(y + 1) * prev_size + x + 1];
-
-                    downsample(s00, s10, s01, s11)
-                })
-                .collect();
-
-            mips.push(mip);
-        }
-
-        mips
-    }
-
-    fn generate_normals(&self, height_map: &[f32], terrain_size: usize) -> Vec<Vec3> {
-        let world_scale = terrain_size as f32 / self.size as f32;
-        let mut normals = vec![Vec3::ZERO; self.size * self.size];
-
-        for z in 0..self.size {
-            for x in 0..self.size {
-                let sample = |sx: i32, sz: i32| -> f32 {
-                    let cx = sx.clamp(0, self.size as i32 - 1) as usize;
-                    let cz = sz.clamp(0, self.size as i32 - 1) as usize;
-
-                    height_map[cz * self.size + cx]
-                };
-
-                let hl = sample(x as i32 - 1, z as i32);
-                let hr = sample(x as i32 + 1, z as i32);
-                let hb = sample(x as i32, z as i32 - 1);
-                let ht = sample(x as i32, z as i32 + 1);
-
-                let height_scale = 10.0;
-                let dx = (hl - hr) * height_scale;
-                let dz = (hb - ht) * height_scale;
-
-                normals[z * self.size + x] = glam::Vec3::new(dx, 2.0 * world_scale, dz).normalize();
-            }
-        }
-
-        normals
-    }
+    Ok((
+        indirection_texture,
+        indirection_texture_upload,
+        indirection_texture_size,
+    ))
 }
@@ -0,0 +1,153 @@
+use glam::Vec2;
+use input::{InputState, Key, MouseButton};
+use windows::Win32::Foundation::{LPARAM, WPARAM};
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+// Feeds window messages into `input`, returns whether the message needs no `DefWindowProcA`. Alt combinations arrive as
+// system key messages, those still have to reach it for Alt+F4 and the window menu.
+pub fn handle_input_message(
+    input: &mut InputState,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    ui_mouse_capture: bool,
+) -> bool {
+    match message {
+        WM_KEYDOWN | WM_SYSKEYDOWN => {
+            if let Some(key) = key_from_virtual_key(wparam.0 as u16) {
+                input.press(key);
+            }
+            message == WM_KEYDOWN
+        }
+        WM_KEYUP | WM_SYSKEYUP => {
+            if let Some(key) = key_from_virtual_key(wparam.0 as u16) {
+                input.release(key);
+            }
+            message == WM_KEYUP
+        }
+        WM_MOUSEMOVE => {
+            let pos = Vec2::new(
+                (lparam.0 & 0xFFFF) as i16 as f32,
+                ((lparam.0 >> 16) & 0xFFFF) as i16 as f32,
+            );
+
+            if ui_mouse_capture {
+                input.set_mouse_pos(pos);
+            } else {
+                input.move_mouse(pos);
+            }
+            true
+        }
+        WM_MOUSEWHEEL => {
+            if !ui_mouse_capture {
+                let delta = ((wparam.0 >> 16) & 0xFFFF) as i16;
+                input.scroll(delta as f32 / WHEEL_DELTA as f32);
+            }
+            true
+        }
+        WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN => {
+            // Clicks on the UI do not start a drag, releases always go through so nothing stays held.
+            if !ui_mouse_capture {
+                input.press(mouse_button(message));
+            }
+            true
+        }
+        WM_LBUTTONUP | WM_RBUTTONUP | WM_MBUTTONUP => {
+            input.release(mouse_button(message));
+            true
+        }
+        WM_KILLFOCUS => {
+            input.release_all();
+            false
+        }
+        _ => false,
+    }
+}
+
+fn mouse_button(message: u32) -> MouseButton {
+    match message {
+        WM_LBUTTONDOWN | WM_LBUTTONUP => MouseButton::Left,
+        WM_RBUTTONDOWN | WM_RBUTTONUP => MouseButton::Right,
+        _ => MouseButton::Middle,
+    }
+}
+
+fn key_from_virtual_key(virtual_key: u16) -> Option<Key> {
+    const LETTERS: [Key; 26] = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+    ];
+    const DIGITS: [Key; 10] = [
+        Key::Digit0,
+        Key::Digit1,
+        Key::Digit2,
+        Key::Digit3,
+        Key::Digit4,
+        Key::Digit5,
+        Key::Digit6,
+        Key::Digit7,
+        Key::Digit8,
+        Key::Digit9,
+    ];
+    const FUNCTION_KEYS: [Key; 12] = [
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+    ];
+
+    // Letters and digits share their ASCII codes.
+    let key = match VIRTUAL_KEY(virtual_key) {
+        VIRTUAL_KEY(code @ 0x41..=0x5A) => LETTERS[(code - 0x41) as usize],
+        VIRTUAL_KEY(code @ 0x30..=0x39) => DIGITS[(code - 0x30) as usize],
+        VIRTUAL_KEY(code) if (VK_F1.0..=VK_F12.0).contains(&code) => FUNCTION_KEYS[(code - VK_F1.0) as usize],
+        VK_UP => Key::Up,
+        VK_DOWN => Key::Down,
+        VK_LEFT => Key::Left,
+        VK_RIGHT => Key::Right,
+        VK_SPACE => Key::Space,
+        VK_RETURN => Key::Enter,
+        VK_ESCAPE => Key::Escape,
+        VK_TAB => Key::Tab,
+        VK_BACK => Key::Backspace,
+        VK_SHIFT => Key::Shift,
+        VK_CONTROL => Key::Control,
+        VK_MENU => Key::Alt,
+        _ => return None,
+    };
+
+    Some(key)
+}
@@ -0,0 +1,168 @@
+use glam::{Mat4, Vec2, Vec3};
+use input::{Action, ActionState, InputState};
+
+const MOUSE_SENSITIVITY: f32 = 0.5;
+
+pub struct Camera {
+    position: Vec3,
+    // Window pixels.
+    viewport_size: Vec2,
+    world_to_view: Mat4,
+    view_to_clip: Mat4,
+}
+
+impl Camera {
+    pub fn new(position: Vec3, viewport_size: Vec2) -> Self {
+        let fov_y = 90_f32.to_radians();
+        let aspect_ratio = viewport_size.x / viewport_size.y;
+        let near_z = 0.1;
+
+        let mut camera = Self {
+            position,
+            viewport_size,
+            world_to_view: Mat4::IDENTITY,
+            view_to_clip: Mat4::perspective_infinite_reverse_lh(fov_y, aspect_ratio, near_z),
+        };
+        camera.look_to(position, LookAngles::default().dir());
+
+        camera
+    }
+
+    // A zero `dir` keeps the current direction. Looking straight up or down, the top of the screen points where the
+    // camera looked before.
+    pub fn look_to(&mut self, position: Vec3, dir: Vec3) {
+        let dir = dir.try_normalize().unwrap_or_else(|| self.forward());
+        let up = if dir.cross(Vec3::Y).length_squared() > 1e-6 {
+            Vec3::Y
+        } else {
+            let level_forward = (self.forward() * Vec3::new(1.0, 0.0, 1.0))
+                .try_normalize()
+                .unwrap_or(Vec3::Z);
+            level_forward * -dir.y.signum()
+        };
+
+        self.position = position;
+        self.world_to_view = Mat4::look_to_lh(position, dir, up);
+    }
+
+    pub fn look_at(&mut self, position: Vec3, target: Vec3) {
+        self.look_to(position, target - position);
+    }
+
+    pub fn position(&self) -> &Vec3 {
+        &self.position
+    }
+
+    // Unit view direction.
+    pub fn forward(&self) -> Vec3 {
+        self.world_to_view.row(2).truncate()
+    }
+
+    pub fn world_to_view(&self) -> &Mat4 {
+        &self.world_to_view
+    }
+
+    pub fn view_to_clip(&self) -> &Mat4 {
+        &self.view_to_clip
+    }
+
+    pub fn world_to_clip(&self) -> Mat4 {
+        self.view_to_clip * self.world_to_view
+    }
+
+    // World space direction through a window pixel, the projection is reversed so depth 1 is the near plane.
+    pub fn screen_ray_dir(&self, pixel: Vec2) -> Vec3 {
+        let ndc = Vec2::new(
+            pixel.x / self.viewport_size.x * 2.0 - 1.0,
+            1.0 - pixel.y / self.viewport_size.y * 2.0,
+        );
+        let clip_to_world = self.world_to_clip().inverse();
+
+        let near = clip_to_world.project_point3(ndc.extend(1.0));
+        let far = clip_to_world.project_point3(ndc.extend(0.5));
+
+        (far - near).normalize()
+    }
+}
+
+// View direction in degrees, yaw around the up axis starting at +X and pitch up from the horizon.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LookAngles {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl LookAngles {
+    pub fn from_dir(dir: Vec3) -> Self {
+        let dir = dir.normalize();
+
+        Self {
+            yaw: dir.z.atan2(dir.x).to_degrees().rem_euclid(360.0),
+            pitch: dir.y.clamp(-1.0, 1.0).asin().to_degrees(),
+        }
+    }
+
+    pub fn dir(&self) -> Vec3 {
+        let yaw_rad = self.yaw.to_radians();
+        let pitch_rad = self.pitch.to_radians();
+
+        Vec3::new(
+            yaw_rad.cos() * pitch_rad.cos(),
+            pitch_rad.sin(),
+            yaw_rad.sin() * pitch_rad.cos(),
+        )
+        .normalize()
+    }
+
+    // Horizontal part of the view direction, for moving on the ground.
+    pub fn ground_dir(&self) -> Vec3 {
+        let yaw_rad = self.yaw.to_radians();
+
+        Vec3::new(yaw_rad.cos(), 0.0, yaw_rad.sin())
+    }
+
+    // Mouse look while the look action is held.
+    pub fn control(&mut self, input: &InputState, actions: &ActionState) {
+        if actions.held(Action::Look) {
+            self.yaw += input.mouse_delta().x * MOUSE_SENSITIVITY;
+            self.pitch += input.mouse_delta().y * MOUSE_SENSITIVITY;
+
+            self.yaw = self.yaw.rem_euclid(360.0);
+            self.pitch = self.pitch.clamp(-89.0, 89.0);
+        }
+    }
+
+    // Trails `target`, which was `last_target` a frame ago. `smoothing` is the seconds to cover most of a mouse
+    // movement, 0 follows directly. Taking the mouse as moving evenly through the frame rather than jumping at its
+    // start makes the result the same at any frame rate.
+    pub fn follow(&mut self, last_target: LookAngles, target: LookAngles, smoothing: f32, dt: f32) {
+        // How far the view trails after the frame, from how far it trailed before and how far the target moved.
+        let lag = |lag: f32, target_delta: f32| {
+            if smoothing <= 0.0 {
+                return 0.0;
+            }
+
+            let decay = (-dt / smoothing).exp();
+            let ramp = if dt > 0.0 { smoothing * (1.0 - decay) / dt } else { 1.0 };
+
+            target_delta * ramp + lag * decay
+        };
+
+        // The wrap around of yaw takes the short way.
+        let yaw_diff = |a: f32, b: f32| (a - b + 180.0).rem_euclid(360.0) - 180.0;
+        let yaw_lag = lag(
+            yaw_diff(last_target.yaw, self.yaw),
+            yaw_diff(target.yaw, last_target.yaw),
+        );
+        let pitch_lag = lag(last_target.pitch - self.pitch, target.pitch - last_target.pitch);
+
+        self.yaw = (target.yaw - yaw_lag).rem_euclid(360.0);
+        self.pitch = target.pitch - pitch_lag;
+    }
+}
+
+impl Default for LookAngles {
+    fn default() -> Self {
+        Self { yaw: -90.0, pitch: 0.0 }
+    }
+}
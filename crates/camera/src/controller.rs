@@ -0,0 +1,102 @@
+use glam::Vec2;
+use input::{ActionState, InputState};
+
+use crate::{Camera, CameraKeyframe, Easing, FlyCamera, FlythroughCamera, OrbitCamera, WalkCamera};
+
+const ORBIT_DISTANCE: f32 = 100.0;
+const KEYFRAME_INTERVAL: f32 = 3.0;
+const KEYFRAME_TARGET_DISTANCE: f32 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraModeKind {
+    Fly,
+    Orbit,
+    Walk,
+    Flythrough,
+}
+
+#[derive(Clone, Debug)]
+pub enum CameraMode {
+    Fly(FlyCamera),
+    Orbit(OrbitCamera),
+    Walk(WalkCamera),
+    Flythrough(FlythroughCamera),
+}
+
+impl CameraMode {
+    pub fn kind(&self) -> CameraModeKind {
+        match self {
+            CameraMode::Fly(_) => CameraModeKind::Fly,
+            CameraMode::Orbit(_) => CameraModeKind::Orbit,
+            CameraMode::Walk(_) => CameraModeKind::Walk,
+            CameraMode::Flythrough(_) => CameraModeKind::Flythrough,
+        }
+    }
+}
+
+// Drives the camera with one mode at a time and keeps the keyframes authored for flythroughs.
+pub struct CameraController {
+    pub mode: CameraMode,
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraController {
+    pub fn new() -> Self {
+        Self {
+            mode: CameraMode::Fly(FlyCamera::new()),
+            keyframes: Vec::new(),
+        }
+    }
+
+    // Takes over from the current camera without a jump, a flythrough starts at its first keyframe instead. Returns
+    // false for a flythrough with fewer than two keyframes.
+    pub fn set_mode(&mut self, kind: CameraModeKind, camera: &Camera) -> bool {
+        self.mode = match kind {
+            CameraModeKind::Fly => CameraMode::Fly(FlyCamera::from_camera(camera)),
+            CameraModeKind::Orbit => CameraMode::Orbit(OrbitCamera::from_camera(camera, ORBIT_DISTANCE)),
+            CameraModeKind::Walk => CameraMode::Walk(WalkCamera::from_camera(camera)),
+            CameraModeKind::Flythrough if self.keyframes.len() >= 2 => {
+                CameraMode::Flythrough(FlythroughCamera::new(self.keyframes.clone()))
+            }
+            CameraModeKind::Flythrough => return false,
+        };
+
+        true
+    }
+
+    // Appends the current view as a keyframe, a fixed time after the last one. It does not slow down there, easing is
+    // up to the user per keyframe.
+    pub fn add_keyframe(&mut self, camera: &Camera) {
+        let time = self.keyframes.last().map_or(0.0, |k| k.time + KEYFRAME_INTERVAL);
+
+        self.keyframes.push(CameraKeyframe {
+            time,
+            position: *camera.position(),
+            target: *camera.position() + camera.forward() * KEYFRAME_TARGET_DISTANCE,
+            easing: Easing::Linear,
+        });
+    }
+
+    // `ground_height` is only used by the walk mode, see `WalkCamera::control`.
+    pub fn control(
+        &mut self,
+        dt: f32,
+        input: &InputState,
+        actions: &ActionState,
+        ground_height: &dyn Fn(Vec2) -> Option<f32>,
+        camera: &mut Camera,
+    ) {
+        match &mut self.mode {
+            CameraMode::Fly(fly) => fly.control(dt, input, actions, camera),
+            CameraMode::Orbit(orbit) => orbit.control(dt, input, actions, camera),
+            CameraMode::Walk(walk) => walk.control(dt, input, actions, ground_height, camera),
+            CameraMode::Flythrough(flythrough) => flythrough.control(dt, camera),
+        }
+    }
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
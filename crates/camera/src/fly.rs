@@ -0,0 +1,113 @@
+use glam::Vec3;
+use input::{Action, ActionState, InputState};
+
+use crate::{Camera, LookAngles};
+
+const SPEED_MULTIPLIER: f32 = 10.0;
+// Max speed factor per wheel notch.
+const WHEEL_SPEED_STEP: f32 = 1.25;
+const MIN_MAX_SPEED: f32 = 1.0;
+const MAX_MAX_SPEED: f32 = 10000.0;
+
+// Free flight along the view direction. The velocity eases towards the input instead of jumping, which keeps captured
+// footage smooth.
+#[derive(Clone, Debug)]
+pub struct FlyCamera {
+    pub max_speed: f32,
+    // How quickly the velocity follows the input, per second.
+    pub acceleration: f32,
+    // How quickly the camera stops without input, per second.
+    pub drag: f32,
+    // Seconds for the view to cover most of a mouse movement, 0 follows the mouse directly.
+    pub look_smoothing: f32,
+    pub look: LookAngles,
+
+    look_target: LookAngles,
+    velocity: Vec3,
+}
+
+impl FlyCamera {
+    pub fn new() -> Self {
+        Self {
+            max_speed: 50.0,
+            acceleration: 8.0,
+            drag: 5.0,
+            look_smoothing: 0.05,
+            look: LookAngles::default(),
+            look_target: LookAngles::default(),
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    // Continues from wherever the camera is.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let look = LookAngles::from_dir(camera.forward());
+
+        Self {
+            look,
+            look_target: look,
+            ..Self::new()
+        }
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    pub fn control(&mut self, dt: f32, input: &InputState, actions: &ActionState, camera: &mut Camera) {
+        let last_front_dir = self.look.dir();
+        let last_target = self.look_target;
+        self.look_target.control(input, actions);
+        self.look.follow(last_target, self.look_target, self.look_smoothing, dt);
+
+        self.max_speed =
+            (self.max_speed * WHEEL_SPEED_STEP.powf(input.wheel_delta())).clamp(MIN_MAX_SPEED, MAX_MAX_SPEED);
+
+        // Moves along the view halfway through the turn of this frame, so a turn at a low frame rate still bends the path
+        // like it would at a high one.
+        let front_dir = self.look.dir();
+        let move_front_dir = (last_front_dir + front_dir).try_normalize().unwrap_or(front_dir);
+        let right_dir = -move_front_dir.cross(Vec3::Y).normalize();
+
+        let axis = |positive: Action, negative: Action| actions.held(positive) as i32 - actions.held(negative) as i32;
+        let move_dir = (move_front_dir * axis(Action::MoveForward, Action::MoveBackward) as f32
+            + right_dir * axis(Action::MoveRight, Action::MoveLeft) as f32
+            + Vec3::Y * axis(Action::MoveUp, Action::MoveDown) as f32)
+            .normalize_or_zero();
+
+        let mut target_speed = self.max_speed;
+        if actions.held(Action::Boost) {
+            target_speed *= SPEED_MULTIPLIER;
+        }
+
+        let rate = if move_dir == Vec3::ZERO {
+            self.drag
+        } else {
+            self.acceleration
+        };
+        let (velocity, offset) = approach(self.velocity, move_dir * target_speed, rate, dt);
+        self.velocity = velocity;
+
+        camera.look_to(*camera.position() + offset, front_dir);
+    }
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Solves dv/dt = rate * (target - v) exactly over `dt`, returns the new velocity and the distance moved. Unlike a
+// step of `v * dt` the result does not depend on how `dt` is split into frames.
+fn approach(velocity: Vec3, target: Vec3, rate: f32, dt: f32) -> (Vec3, Vec3) {
+    if rate <= 0.0 {
+        return (velocity, velocity * dt);
+    }
+
+    let decay = (-rate * dt).exp();
+    let new_velocity = target + (velocity - target) * decay;
+    let offset = target * dt + (velocity - target) * ((1.0 - decay) / rate);
+
+    (new_velocity, offset)
+}
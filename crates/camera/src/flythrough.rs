@@ -0,0 +1,121 @@
+use glam::Vec3;
+
+use crate::Camera;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    // Slows down towards both keyframes of a segment.
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraKeyframe {
+    // Seconds from the start of the flight.
+    pub time: f32,
+    pub position: Vec3,
+    pub target: Vec3,
+    // Of the segment towards the next keyframe.
+    pub easing: Easing,
+}
+
+// Follows a Catmull-Rom spline through keyframe positions, looking at a second spline through their targets.
+#[derive(Clone, Debug)]
+pub struct FlythroughCamera {
+    pub looping: bool,
+
+    keyframes: Vec<CameraKeyframe>,
+    time: f32,
+}
+
+impl FlythroughCamera {
+    // Keyframes in ascending time.
+    pub fn new(keyframes: Vec<CameraKeyframe>) -> Self {
+        assert!(!keyframes.is_empty());
+        assert!(keyframes.windows(2).all(|k| k[0].time < k[1].time));
+
+        Self {
+            looping: false,
+            time: keyframes[0].time,
+            keyframes,
+        }
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+        &self.keyframes
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.clamp(self.start_time(), self.end_time());
+    }
+
+    pub fn start_time(&self) -> f32 {
+        self.keyframes[0].time
+    }
+
+    pub fn end_time(&self) -> f32 {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.end_time()
+    }
+
+    // Position and target at a point in time, held at the ends.
+    pub fn pose_at(&self, time: f32) -> (Vec3, Vec3) {
+        let last = self.keyframes.len() - 1;
+        if last == 0 {
+            return (self.keyframes[0].position, self.keyframes[0].target);
+        }
+
+        let segment = self.keyframes.partition_point(|k| k.time <= time).clamp(1, last) - 1;
+        let k1 = &self.keyframes[segment];
+        let k2 = &self.keyframes[segment + 1];
+        // The ends repeat their keyframe, so the spline starts and stops there.
+        let k0 = &self.keyframes[segment.saturating_sub(1)];
+        let k3 = &self.keyframes[(segment + 2).min(last)];
+
+        let t = ((time - k1.time) / (k2.time - k1.time)).clamp(0.0, 1.0);
+        let t = k1.easing.apply(t);
+
+        (
+            catmull_rom(k0.position, k1.position, k2.position, k3.position, t),
+            catmull_rom(k0.target, k1.target, k2.target, k3.target, t),
+        )
+    }
+
+    pub fn control(&mut self, dt: f32, camera: &mut Camera) {
+        self.time += dt;
+
+        let duration = self.end_time() - self.start_time();
+        if self.looping && duration > 0.0 {
+            self.time = self.start_time() + (self.time - self.start_time()).rem_euclid(duration);
+        } else {
+            self.time = self.time.min(self.end_time());
+        }
+
+        let (position, target) = self.pose_at(self.time);
+        camera.look_at(position, target);
+    }
+}
+
+// Uniform Catmull-Rom segment from `p1` at 0 to `p2` at 1.
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * (2.0 * p1 + (p2 - p0) * t + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2 + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
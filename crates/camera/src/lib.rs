@@ -0,0 +1,13 @@
+mod camera;
+mod controller;
+mod fly;
+mod flythrough;
+mod orbit;
+mod walk;
+
+pub use camera::*;
+pub use controller::*;
+pub use fly::*;
+pub use flythrough::*;
+pub use orbit::*;
+pub use walk::*;
@@ -0,0 +1,75 @@
+use glam::Vec3;
+use input::{Action, ActionState, InputState};
+
+use crate::{Camera, LookAngles};
+
+const MIN_ORBIT_DISTANCE: f32 = 1.0;
+// Distance factor per wheel notch.
+const ZOOM_STEP: f32 = 0.9;
+// Target movement per second in orbit distances, so panning feels the same close up and far away.
+const PAN_SPEED: f32 = 1.0;
+
+// Circles around a target point, dragging rotates, the wheel zooms and movement pans the target.
+#[derive(Clone, Debug)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    // Direction from the camera to the target.
+    pub look: LookAngles,
+    // See `FlyCamera::look_smoothing`.
+    pub look_smoothing: f32,
+
+    look_target: LookAngles,
+}
+
+impl OrbitCamera {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        let look = LookAngles {
+            yaw: -90.0,
+            pitch: -30.0,
+        };
+
+        Self {
+            target,
+            distance,
+            look,
+            look_smoothing: 0.05,
+            look_target: look,
+        }
+    }
+
+    // Orbits the point `distance` ahead of the camera, without moving it.
+    pub fn from_camera(camera: &Camera, distance: f32) -> Self {
+        let look = LookAngles::from_dir(camera.forward());
+
+        Self {
+            target: *camera.position() + camera.forward() * distance,
+            look,
+            look_target: look,
+            ..Self::new(Vec3::ZERO, distance)
+        }
+    }
+
+    pub fn eye(&self) -> Vec3 {
+        self.target - self.look.dir() * self.distance
+    }
+
+    pub fn control(&mut self, dt: f32, input: &InputState, actions: &ActionState, camera: &mut Camera) {
+        let last_target = self.look_target;
+        self.look_target.control(input, actions);
+        self.look.follow(last_target, self.look_target, self.look_smoothing, dt);
+
+        self.distance = (self.distance * ZOOM_STEP.powf(input.wheel_delta())).max(MIN_ORBIT_DISTANCE);
+
+        let front_dir = self.look.ground_dir();
+        let right_dir = -front_dir.cross(Vec3::Y);
+        let speed = self.distance * PAN_SPEED * dt;
+
+        let axis = |positive: Action, negative: Action| actions.held(positive) as i32 - actions.held(negative) as i32;
+        self.target += front_dir * (axis(Action::MoveForward, Action::MoveBackward) as f32 * speed);
+        self.target += right_dir * (axis(Action::MoveRight, Action::MoveLeft) as f32 * speed);
+        self.target.y += axis(Action::MoveUp, Action::MoveDown) as f32 * speed;
+
+        camera.look_at(self.eye(), self.target);
+    }
+}
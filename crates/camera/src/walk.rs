@@ -0,0 +1,116 @@
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use input::{Action, ActionState, InputState};
+
+use crate::{Camera, LookAngles};
+
+const RUN_MULTIPLIER: f32 = 2.5;
+
+// Walks on the terrain with gravity, jumping on the up action.
+#[derive(Clone, Debug)]
+pub struct WalkCamera {
+    pub speed: f32,
+    pub eye_height: f32,
+    pub gravity: f32,
+    pub jump_speed: f32,
+    pub look: LookAngles,
+    // See `FlyCamera::look_smoothing`.
+    pub look_smoothing: f32,
+
+    look_target: LookAngles,
+    vertical_speed: f32,
+    is_grounded: bool,
+}
+
+impl WalkCamera {
+    pub fn new() -> Self {
+        Self {
+            speed: 10.0,
+            eye_height: 2.0,
+            gravity: 20.0,
+            jump_speed: 8.0,
+            look: LookAngles::default(),
+            look_smoothing: 0.05,
+            look_target: LookAngles::default(),
+            vertical_speed: 0.0,
+            is_grounded: false,
+        }
+    }
+
+    // Starts falling from wherever the camera is.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let look = LookAngles::from_dir(camera.forward());
+
+        Self {
+            look,
+            look_target: look,
+            ..Self::new()
+        }
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.is_grounded
+    }
+
+    // `ground_height` is the terrain height below a world xz position, `None` where the terrain is not loaded yet. The
+    // camera hangs in the air there rather than falling through.
+    pub fn control(
+        &mut self,
+        dt: f32,
+        input: &InputState,
+        actions: &ActionState,
+        ground_height: &dyn Fn(Vec2) -> Option<f32>,
+        camera: &mut Camera,
+    ) {
+        let last_target = self.look_target;
+        self.look_target.control(input, actions);
+        self.look.follow(last_target, self.look_target, self.look_smoothing, dt);
+
+        let front_dir = self.look.ground_dir();
+        let right_dir = -front_dir.cross(Vec3::Y);
+
+        let mut speed = self.speed * dt;
+        if actions.held(Action::Boost) {
+            speed *= RUN_MULTIPLIER;
+        }
+
+        let axis = |positive: Action, negative: Action| actions.held(positive) as i32 - actions.held(negative) as i32;
+        let step = front_dir * axis(Action::MoveForward, Action::MoveBackward) as f32
+            + right_dir * axis(Action::MoveRight, Action::MoveLeft) as f32;
+        let step = step.normalize_or_zero() * speed;
+        let mut position = *camera.position() + step;
+
+        match ground_height(position.xz()) {
+            Some(ground) => {
+                if self.is_grounded && actions.pressed(Action::MoveUp) {
+                    self.vertical_speed = self.jump_speed;
+                }
+
+                // Semi implicit Euler, the speed of this frame moves this frame.
+                self.vertical_speed -= self.gravity * dt;
+                position.y += self.vertical_speed * dt;
+
+                // Walking down slopes up to 45° sticks to the ground instead of falling a bit every frame.
+                let eye_ground = ground + self.eye_height;
+                let max_step_down = if self.vertical_speed < 0.0 { step.length() } else { 0.0 };
+                self.is_grounded =
+                    position.y <= eye_ground || (self.is_grounded && position.y - eye_ground <= max_step_down);
+                if self.is_grounded {
+                    position.y = eye_ground;
+                    self.vertical_speed = 0.0;
+                }
+            }
+            None => {
+                self.vertical_speed = 0.0;
+                self.is_grounded = false;
+            }
+        }
+
+        camera.look_to(position, self.look.dir());
+    }
+}
+
+impl Default for WalkCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
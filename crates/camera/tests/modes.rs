@@ -0,0 +1,267 @@
+use camera::*;
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use input::*;
+
+const VIEWPORT_SIZE: Vec2 = Vec2::new(1920.0, 1080.0);
+const DT: f32 = 1.0 / 60.0;
+
+fn actions_for(input: &InputState) -> ActionState {
+    let mut actions = ActionState::new();
+    actions.update(&ActionMap::new(), input);
+
+    actions
+}
+
+// Where a world position ends up on screen, in normalized device coordinates.
+fn project(camera: &Camera, pos: Vec3) -> Vec3 {
+    camera.world_to_clip().project_point3(pos)
+}
+
+#[test]
+fn look_angles_round_trip() {
+    for (yaw, pitch) in [(0.0, 0.0), (-90.0, 10.0), (135.0, -45.0), (300.0, 80.0)] {
+        let look = LookAngles { yaw, pitch };
+        let back = LookAngles::from_dir(look.dir());
+
+        assert!(
+            (back.yaw - f32::rem_euclid(yaw, 360.0)).abs() < 1e-3,
+            "{look:?} {back:?}"
+        );
+        assert!((back.pitch - pitch).abs() < 1e-3, "{look:?} {back:?}");
+    }
+
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+    camera.look_to(Vec3::ONE, Vec3::new(1.0, -1.0, 2.0));
+    assert!(camera.forward().distance(Vec3::new(1.0, -1.0, 2.0).normalize()) < 1e-6);
+}
+
+#[test]
+fn look_to_survives_degenerate_directions() {
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+    camera.look_to(Vec3::ZERO, Vec3::X);
+
+    // Looking at itself keeps the view.
+    camera.look_at(Vec3::ONE, Vec3::ONE);
+    assert!(camera.world_to_view().is_finite());
+    assert!(camera.forward().distance(Vec3::X) < 1e-6);
+
+    // Straight down, the top of the screen keeps pointing where the camera looked.
+    camera.look_to(Vec3::ONE, Vec3::NEG_Y);
+    assert!(camera.world_to_view().is_finite());
+    assert!(camera.forward().distance(Vec3::NEG_Y) < 1e-6);
+    assert!(project(&camera, Vec3::new(2.0, 0.0, 1.0)).y > 0.5);
+
+    camera.look_to(Vec3::ONE, Vec3::Y);
+    assert!(camera.world_to_view().is_finite());
+    assert!(camera.forward().distance(Vec3::Y) < 1e-6);
+}
+
+#[test]
+fn fly_moves_along_view() {
+    let mut camera = Camera::new(Vec3::new(0.0, 100.0, 0.0), VIEWPORT_SIZE);
+    let mut fly = FlyCamera::from_camera(&camera);
+
+    let mut input = InputState::new();
+    input.press(Key::W);
+    fly.control(1.0, &input, &actions_for(&input), &mut camera);
+
+    assert!(camera.position().xy().distance(Vec2::new(0.0, 100.0)) < 1e-3);
+    // From rest the speed is `max_speed * (1 - e^(-acceleration * t))`, integrated over the second.
+    let distance = fly.max_speed * (1.0 - (1.0 - (-fly.acceleration).exp()) / fly.acceleration);
+    assert!((camera.position().z + distance).abs() < 1e-3);
+    // What is ahead is in the middle of the screen.
+    assert!(project(&camera, Vec3::new(0.0, 100.0, -500.0)).xy().length() < 1e-4);
+}
+
+#[test]
+fn orbit_keeps_target_centered() {
+    let target = Vec3::new(30.0, 5.0, -20.0);
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+    let mut orbit = OrbitCamera::new(target, 50.0);
+
+    let mut input = InputState::new();
+    input.press(MouseButton::Right);
+    input.move_mouse(Vec2::ZERO);
+
+    for i in 0..30 {
+        input.move_mouse(Vec2::new(i as f32 * 7.0, i as f32 * 3.0));
+        if i % 10 == 0 {
+            input.scroll(1.0);
+        }
+
+        orbit.control(DT, &input, &actions_for(&input), &mut camera);
+        input.end_frame();
+
+        assert!(project(&camera, orbit.target).xy().length() < 1e-4);
+        assert!((camera.position().distance(orbit.target) - orbit.distance).abs() < 1e-3);
+    }
+
+    // Three notches in, the rotation does not move the target.
+    assert!((orbit.distance - 50.0 * 0.9_f32.powi(3)).abs() < 1e-3);
+    assert_eq!(orbit.target, target);
+
+    // Panning moves the target on the ground, the view follows.
+    input.release(MouseButton::Right);
+    input.press(Key::D);
+    orbit.control(DT, &input, &actions_for(&input), &mut camera);
+    assert_ne!(orbit.target.xz(), target.xz());
+    assert_eq!(orbit.target.y, target.y);
+    assert!(project(&camera, orbit.target).xy().length() < 1e-4);
+}
+
+#[test]
+fn walk_falls_to_eye_height_and_follows_ground() {
+    // A slope rising towards -z.
+    let ground_height = |pos: Vec2| Some(-pos.y * 0.5);
+
+    let mut camera = Camera::new(Vec3::new(0.0, 20.0, 0.0), VIEWPORT_SIZE);
+    let mut walk = WalkCamera::from_camera(&camera);
+    let mut input = InputState::new();
+
+    // Falls with gravity, never below the ground.
+    let mut last_y = camera.position().y;
+    for _ in 0..120 {
+        walk.control(DT, &input, &actions_for(&input), &ground_height, &mut camera);
+
+        assert!(camera.position().y <= last_y);
+        assert!(camera.position().y >= walk.eye_height - 1e-4);
+        last_y = camera.position().y;
+    }
+    assert!(walk.is_grounded());
+    assert!((camera.position().y - walk.eye_height).abs() < 1e-4);
+
+    // Walking uphill and back down stays on the ground.
+    input.press(Key::W);
+    for frame in 0..240 {
+        if frame == 120 {
+            input.release(Key::W);
+            input.press(Key::S);
+        }
+
+        walk.control(DT, &input, &actions_for(&input), &ground_height, &mut camera);
+        input.end_frame();
+
+        let expected = ground_height(camera.position().xz()).unwrap() + walk.eye_height;
+        assert!((camera.position().y - expected).abs() < 1e-3, "frame {frame}");
+        assert!(walk.is_grounded());
+    }
+
+    // A jump leaves the ground and comes back.
+    input.release(Key::S);
+    input.press(Key::Space);
+    let mut max_height = 0.0_f32;
+    for _ in 0..120 {
+        walk.control(DT, &input, &actions_for(&input), &ground_height, &mut camera);
+        input.end_frame();
+
+        max_height = max_height.max(camera.position().y - ground_height(camera.position().xz()).unwrap());
+    }
+    assert!(max_height > walk.eye_height + 1.0);
+    assert!(walk.is_grounded());
+
+    // Without terrain below, the camera hangs where it is.
+    let position = *camera.position();
+    walk.control(DT, &input, &actions_for(&input), &|_| None, &mut camera);
+    assert_eq!(*camera.position(), position);
+}
+
+fn keyframes() -> Vec<CameraKeyframe> {
+    [
+        (0.0, Vec3::new(0.0, 50.0, 0.0)),
+        (2.0, Vec3::new(100.0, 60.0, 0.0)),
+        (3.0, Vec3::new(100.0, 40.0, 100.0)),
+        (6.0, Vec3::new(0.0, 80.0, 50.0)),
+    ]
+    .into_iter()
+    .map(|(time, position)| CameraKeyframe {
+        time,
+        position,
+        target: Vec3::new(50.0, 0.0, 50.0),
+        easing: Easing::EaseInOut,
+    })
+    .collect()
+}
+
+#[test]
+fn flythrough_passes_keyframes() {
+    let mut flythrough = FlythroughCamera::new(keyframes());
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+
+    let mut keyframe_index = 1;
+    for _ in 0..400 {
+        flythrough.control(DT, &mut camera);
+
+        // Always looking at the target.
+        assert!(project(&camera, Vec3::new(50.0, 0.0, 50.0)).xy().length() < 1e-4);
+
+        let keyframe = flythrough.keyframes().get(keyframe_index).copied();
+        if let Some(keyframe) = keyframe
+            && (flythrough.time() - keyframe.time).abs() < DT / 2.0
+        {
+            assert!(camera.position().distance(keyframe.position) < 0.05);
+            keyframe_index += 1;
+        }
+    }
+
+    assert_eq!(keyframe_index, 4);
+    assert!(flythrough.is_finished());
+    assert_eq!(*camera.position(), Vec3::new(0.0, 80.0, 50.0));
+}
+
+#[test]
+fn easing_slows_down_at_keyframes() {
+    let mut keyframes = keyframes();
+    let eased = FlythroughCamera::new(keyframes.clone());
+    keyframes.iter_mut().for_each(|k| k.easing = Easing::Linear);
+    let linear = FlythroughCamera::new(keyframes);
+
+    let step = |camera: &FlythroughCamera, time: f32| camera.pose_at(time + 0.01).0.distance(camera.pose_at(time).0);
+
+    // Slower right after and before a keyframe, faster in the middle of the segment.
+    assert!(step(&eased, 2.0) < step(&linear, 2.0) * 0.2);
+    assert!(step(&eased, 2.99) < step(&linear, 2.99) * 0.2);
+    assert!(step(&eased, 4.5) > step(&linear, 4.5));
+
+    let mut looping = FlythroughCamera::new(linear.keyframes().to_vec());
+    looping.looping = true;
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+    for _ in 0..400 {
+        looping.control(DT, &mut camera);
+    }
+    assert!(!looping.is_finished());
+    assert!(looping.time() < looping.end_time());
+}
+
+#[test]
+fn switching_modes_keeps_the_view() {
+    let mut camera = Camera::new(Vec3::new(10.0, 100.0, -5.0), VIEWPORT_SIZE);
+    camera.look_to(*camera.position(), Vec3::new(0.3, -0.4, 1.0));
+
+    let mut controller = CameraController::new();
+    let input = InputState::new();
+    let actions = actions_for(&input);
+    let ground_height = |_| Some(0.0);
+
+    for kind in [CameraModeKind::Orbit, CameraModeKind::Fly, CameraModeKind::Walk] {
+        let world_to_view = *camera.world_to_view();
+        assert!(controller.set_mode(kind, &camera));
+        assert_eq!(controller.mode.kind(), kind);
+
+        // Walk mode starts to fall, but only after the first frame of its own.
+        controller.control(0.0, &input, &actions, &ground_height, &mut camera);
+        assert!(camera.world_to_view().abs_diff_eq(world_to_view, 1e-3), "{kind:?}");
+    }
+
+    // Flythroughs need keyframes to follow.
+    assert!(!controller.set_mode(CameraModeKind::Flythrough, &camera));
+    controller.add_keyframe(&camera);
+    camera.look_to(Vec3::new(0.0, 50.0, 0.0), Vec3::X);
+    controller.add_keyframe(&camera);
+    assert!(controller.set_mode(CameraModeKind::Flythrough, &camera));
+    assert_eq!(controller.keyframes[1].time, 3.0);
+    assert!(controller.keyframes.iter().all(|k| k.easing == Easing::Linear));
+
+    controller.control(10.0, &input, &actions, &ground_height, &mut camera);
+    assert_eq!(*camera.position(), Vec3::new(0.0, 50.0, 0.0));
+    assert!(camera.forward().distance(Vec3::X) < 1e-4);
+}
@@ -0,0 +1,138 @@
+use camera::*;
+use glam::{Mat4, Vec2, Vec3};
+use input::*;
+
+const VIEWPORT_SIZE: Vec2 = Vec2::new(1920.0, 1080.0);
+
+// Small xorshift so the flights are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1 << 24) as f32
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct FrameResult {
+    position: Vec3,
+    world_to_view: Mat4,
+}
+
+// Advances the camera by one frame the way the main loop does.
+fn step_frame(
+    dt: f32,
+    input: &InputState,
+    actions: &mut ActionState,
+    controller: &mut CameraController,
+    camera: &mut Camera,
+) -> FrameResult {
+    actions.update(&ActionMap::new(), input);
+    controller.control(dt, input, actions, &|_| None, camera);
+
+    FrameResult {
+        position: *camera.position(),
+        world_to_view: *camera.world_to_view(),
+    }
+}
+
+// Flies around with uneven frame times while recording.
+fn fly_live(frame_count: usize) -> (InputRecording, Vec<FrameResult>) {
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+    let mut input = InputState::new();
+    let mut actions = ActionState::new();
+    let mut controller = CameraController::default();
+    let mut camera = Camera::new(Vec3::new(0.0, 100.0, 0.0), VIEWPORT_SIZE);
+    let mut recording = InputRecording::new();
+    let mut results = Vec::new();
+
+    let buttons = [
+        Button::from(Key::W),
+        Key::A.into(),
+        Key::D.into(),
+        Key::Space.into(),
+        Key::Shift.into(),
+        MouseButton::Right.into(),
+    ];
+    let mut mouse_pos = VIEWPORT_SIZE / 2.0;
+
+    for _ in 0..frame_count {
+        for button in buttons {
+            let roll = rng.next_f32();
+            if roll < 0.1 {
+                input.press(button);
+            } else if roll < 0.15 {
+                input.release(button);
+            }
+        }
+
+        mouse_pos += Vec2::new(rng.next_f32() - 0.5, rng.next_f32() - 0.5) * 40.0;
+        input.move_mouse(mouse_pos);
+
+        let dt = 1.0 / 144.0 + rng.next_f32() * 0.03;
+        recording.push(dt, &input);
+        results.push(step_frame(dt, &input, &mut actions, &mut controller, &mut camera));
+
+        input.end_frame();
+    }
+
+    (recording, results)
+}
+
+const REPLAY_TICK: f32 = 1.0 / 120.0;
+
+fn replay(recording: InputRecording) -> Vec<FrameResult> {
+    let mut replay = InputReplay::new(recording, REPLAY_TICK);
+    let mut actions = ActionState::new();
+    let mut controller = CameraController::default();
+    let mut camera = Camera::new(Vec3::new(0.0, 100.0, 0.0), VIEWPORT_SIZE);
+    let mut results = Vec::new();
+
+    while let Some(steps) = replay.next_frame() {
+        for step in steps {
+            results.push(step_frame(
+                step.dt,
+                &step.input,
+                &mut actions,
+                &mut controller,
+                &mut camera,
+            ));
+        }
+    }
+
+    results
+}
+
+#[test]
+fn replay_repeats_camera_path() {
+    let (recording, live) = fly_live(600);
+
+    let loaded = InputRecording::from_bytes(&recording.to_bytes()).unwrap();
+    assert_eq!(loaded, recording);
+
+    let replayed = replay(loaded);
+    let expected_steps = (recording.duration() / REPLAY_TICK) as usize;
+    assert!(replayed.len().abs_diff(expected_steps) <= 1);
+
+    // Every replay is exactly the same, not just close.
+    let replayed_again = replay(recording.clone());
+    assert_eq!(replayed_again.len(), replayed.len());
+    for (step_index, (again, replayed)) in replayed_again.iter().zip(&replayed).enumerate() {
+        assert_eq!(again, replayed, "step {step_index}");
+    }
+
+    // Fixed steps integrate the flight a little differently than the live frame times, but it ends up in the same place.
+    let live_end = live.last().unwrap().position;
+    let replayed_end = replayed.last().unwrap().position;
+    let flown: f32 = live.windows(2).map(|w| w[0].position.distance(w[1].position)).sum();
+    assert!(live_end.distance(replayed_end) < flown * 0.01);
+
+    // The flight went somewhere and turned, or there would be nothing to compare.
+    let start = live.first().unwrap();
+    let end = live.last().unwrap();
+    assert!(start.position.distance(end.position) > 100.0);
+    assert!(!start.world_to_view.abs_diff_eq(end.world_to_view, 0.1));
+}
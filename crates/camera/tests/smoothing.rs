@@ -0,0 +1,222 @@
+use camera::*;
+use glam::{Vec2, Vec3};
+use input::*;
+
+const VIEWPORT_SIZE: Vec2 = Vec2::new(1920.0, 1080.0);
+// Input changes on a 30 Hz grid, so every tick rate sees the same input over the same time spans.
+const GRID_HZ: u32 = 30;
+const DURATION_S: u32 = 4;
+// Mouse speed while looking around, in pixels per second.
+const MOUSE_VELOCITY: Vec2 = Vec2::new(120.0, -40.0);
+
+// Buttons held from a time on the grid to another, in grid steps.
+const SCRIPT: [(u32, u32, Button); 6] = [
+    (0, 45, Button::Key(Key::W)),
+    (15, 60, Button::Key(Key::D)),
+    (30, 54, Button::Mouse(MouseButton::Right)),
+    (75, 90, Button::Key(Key::Space)),
+    (90, 100, Button::Key(Key::Shift)),
+    (90, 100, Button::Key(Key::S)),
+];
+// Wheel notches at grid steps.
+const WHEEL: [(u32, f32); 2] = [(60, 2.0), (95, -1.0)];
+
+struct Sample {
+    position: Vec3,
+    forward: Vec3,
+    speed: f32,
+}
+
+// Flies the script at a tick rate, sampled at every grid step.
+fn fly(tick_hz: u32, mut fly: FlyCamera, map: &ActionMap) -> Vec<Sample> {
+    assert_eq!(tick_hz % GRID_HZ, 0);
+    let ticks_per_step = tick_hz / GRID_HZ;
+    let dt = 1.0 / tick_hz as f32;
+
+    let mut camera = Camera::new(Vec3::new(0.0, 100.0, 0.0), VIEWPORT_SIZE);
+    let mut input = InputState::new();
+    let mut actions = ActionState::new();
+    let mut samples = Vec::new();
+
+    input.set_mouse_pos(Vec2::ZERO);
+
+    for tick in 0..tick_hz * DURATION_S {
+        let step = tick / ticks_per_step;
+        let is_step_start = tick % ticks_per_step == 0;
+
+        for (start, end, button) in SCRIPT {
+            if step == start && is_step_start {
+                input.press(button);
+            }
+            if step == end && is_step_start {
+                input.release(button);
+            }
+        }
+
+        for (at_step, notches) in WHEEL {
+            if step == at_step && is_step_start {
+                input.scroll(notches);
+            }
+        }
+
+        input.move_mouse(MOUSE_VELOCITY * (tick + 1) as f32 * dt);
+
+        actions.update(map, &input);
+        fly.control(dt, &input, &actions, &mut camera);
+        input.end_frame();
+
+        if (tick + 1) % ticks_per_step == 0 {
+            samples.push(Sample {
+                position: *camera.position(),
+                forward: camera.forward(),
+                speed: fly.velocity().length(),
+            });
+        }
+    }
+
+    samples
+}
+
+fn assert_same_trajectory(fly_camera: FlyCamera, max_position_error: f32, max_forward_error: f32) {
+    let reference = fly(240, fly_camera.clone(), &ActionMap::new());
+
+    for tick_hz in [30, 60] {
+        let samples = fly(tick_hz, fly_camera.clone(), &ActionMap::new());
+        assert_eq!(samples.len(), reference.len());
+
+        for (step, (sample, expected)) in samples.iter().zip(&reference).enumerate() {
+            let position_error = sample.position.distance(expected.position);
+            let forward_error = sample.forward.distance(expected.forward);
+
+            assert!(
+                position_error <= max_position_error,
+                "{tick_hz} Hz step {step}: {position_error}"
+            );
+            assert!(
+                forward_error <= max_forward_error,
+                "{tick_hz} Hz step {step}: {forward_error}"
+            );
+        }
+    }
+
+    // The script went somewhere and turned, or there would be nothing to compare.
+    let end = reference.last().unwrap();
+    assert!(end.position.distance(Vec3::new(0.0, 100.0, 0.0)) > 100.0);
+    assert!(end.forward.distance(Vec3::NEG_Z) > 0.5);
+}
+
+#[test]
+fn same_trajectory_at_any_tick_rate() {
+    // Without turning, the motion itself is exact.
+    let mut no_look = ActionMap::new();
+    no_look.unbind(Action::Look);
+    let [samples_30, samples_60, reference] = [30, 60, 240].map(|tick_hz| fly(tick_hz, FlyCamera::new(), &no_look));
+    for samples in [samples_30, samples_60] {
+        for (sample, expected) in samples.iter().zip(&reference) {
+            assert!(sample.position.distance(expected.position) < 1e-3);
+        }
+    }
+
+    // Turning bends the path within a frame, which leaves a small difference in position but none in the view.
+    assert_same_trajectory(FlyCamera::new(), 0.02, 1e-4);
+
+    let mut unsmoothed = FlyCamera::new();
+    unsmoothed.look_smoothing = 0.0;
+    assert_same_trajectory(unsmoothed, 0.02, 1e-4);
+}
+
+#[test]
+fn accelerates_to_max_speed_and_stops() {
+    let samples = fly(60, FlyCamera::new(), &ActionMap::new());
+    let max_speed = FlyCamera::new().max_speed;
+
+    // Speeds up over a few frames instead of jumping.
+    assert!(samples[0].speed > 0.0 && samples[0].speed < max_speed * 0.5);
+    // Until the wheel raises it.
+    assert!(samples[..60].iter().all(|s| s.speed <= max_speed * 1.0001));
+
+    // Holding forward long enough reaches the max speed.
+    assert!((samples[44].speed - max_speed).abs() < 0.01 * max_speed);
+
+    // With nothing held the camera coasts to a stop, drag takes off the same share of speed every second.
+    let drag = FlyCamera::new().drag;
+    assert!((samples[74].speed / samples[59].speed - (-drag * 0.5).exp()).abs() < 1e-3);
+    let coasted = samples[59].position.distance(samples[74].position);
+    assert!(coasted > 0.0 && coasted < samples[59].speed / drag);
+}
+
+#[test]
+fn wheel_scales_max_speed() {
+    let mut fly_camera = FlyCamera::new();
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+    let mut input = InputState::new();
+    let actions = ActionState::new();
+
+    input.scroll(2.0);
+    fly_camera.control(0.01, &input, &actions, &mut camera);
+    assert!((fly_camera.max_speed - 50.0 * 1.25 * 1.25).abs() < 1e-3);
+
+    input.end_frame();
+    input.scroll(-100.0);
+    fly_camera.control(0.01, &input, &actions, &mut camera);
+    assert_eq!(fly_camera.max_speed, 1.0);
+}
+
+#[test]
+fn look_smoothing_eases_into_mouse_moves() {
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+    let mut smoothed = FlyCamera::new();
+    let mut direct = FlyCamera::new();
+    direct.look_smoothing = 0.0;
+
+    let mut input = InputState::new();
+    input.press(MouseButton::Right);
+    input.move_mouse(Vec2::ZERO);
+    input.move_mouse(Vec2::new(200.0, 0.0));
+    let mut actions = ActionState::new();
+    actions.update(&ActionMap::new(), &input);
+
+    direct.control(1.0 / 60.0, &input, &actions, &mut camera);
+    smoothed.control(1.0 / 60.0, &input, &actions, &mut camera);
+    assert_eq!(direct.look.yaw, 270.0 + 100.0 - 360.0);
+    assert!(smoothed.look.yaw > 270.0 && smoothed.look.yaw < 270.0 + 50.0);
+
+    // Catches up once the mouse stops, across the wrap around.
+    input.end_frame();
+    actions.update(&ActionMap::new(), &input);
+    for _ in 0..60 {
+        smoothed.control(1.0 / 60.0, &input, &actions, &mut camera);
+    }
+    assert!((smoothed.look.yaw - direct.look.yaw).abs() < 1e-3);
+}
+
+#[test]
+fn orbit_and_walk_smooth_the_look_too() {
+    let mut camera = Camera::new(Vec3::ZERO, VIEWPORT_SIZE);
+    let mut orbit = OrbitCamera::new(Vec3::ZERO, 50.0);
+    let mut walk = WalkCamera::new();
+    let ground_height = |_| Some(0.0);
+
+    let mut input = InputState::new();
+    input.press(MouseButton::Right);
+    input.move_mouse(Vec2::ZERO);
+    input.move_mouse(Vec2::new(200.0, 0.0));
+    let mut actions = ActionState::new();
+    actions.update(&ActionMap::new(), &input);
+
+    orbit.control(1.0 / 60.0, &input, &actions, &mut camera);
+    walk.control(1.0 / 60.0, &input, &actions, &ground_height, &mut camera);
+    for look in [orbit.look, walk.look] {
+        assert!(look.yaw > 270.0 && look.yaw < 270.0 + 50.0);
+    }
+
+    input.end_frame();
+    actions.update(&ActionMap::new(), &input);
+    for _ in 0..60 {
+        orbit.control(1.0 / 60.0, &input, &actions, &mut camera);
+        walk.control(1.0 / 60.0, &input, &actions, &ground_height, &mut camera);
+    }
+    for look in [orbit.look, walk.look] {
+        assert!((look.yaw - 10.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::{Button, InputState, Key, MouseButton};
+
+// What controllers react to, so they never see which buttons are bound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Boost,
+    Look,
+    ToggleSolid,
+    ToggleWireframe,
+    ToggleStitching,
+    ToggleMorphing,
+    FreezeCamera,
+    ToggleFrustumCulling,
+    FreezeCulling,
+}
+
+impl Action {
+    pub const ALL: [Action; 15] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Boost,
+        Action::Look,
+        Action::ToggleSolid,
+        Action::ToggleWireframe,
+        Action::ToggleStitching,
+        Action::ToggleMorphing,
+        Action::FreezeCamera,
+        Action::ToggleFrustumCulling,
+        Action::FreezeCulling,
+    ];
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ActionMapError {
+    Json(String),
+    UnknownButton(String),
+}
+
+impl fmt::Display for ActionMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionMapError::Json(message) => write!(f, "{}", message),
+            ActionMapError::UnknownButton(name) => write!(f, "unknown button \"{}\"", name),
+        }
+    }
+}
+
+impl std::error::Error for ActionMapError {}
+
+// Buttons that have to be down together, written as `Control+W`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chord(Vec<Button>);
+
+impl Chord {
+    pub fn new(buttons: impl IntoIterator<Item = Button>) -> Self {
+        let mut chord = Vec::new();
+        for button in buttons {
+            if !chord.contains(&button) {
+                chord.push(button);
+            }
+        }
+
+        Self(chord)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ActionMapError> {
+        text.split('+')
+            .map(|name| Button::from_name(name.trim()).ok_or_else(|| ActionMapError::UnknownButton(name.trim().into())))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self::new)
+    }
+
+    pub fn buttons(&self) -> &[Button] {
+        &self.0
+    }
+
+    // Includes buttons that were tapped within the frame.
+    fn is_down(&self, input: &InputState) -> bool {
+        !self.0.is_empty() && self.0.iter().all(|&b| input.held(b) || input.pressed(b))
+    }
+
+    fn is_held(&self, input: &InputState) -> bool {
+        self.0.iter().all(|&b| input.held(b))
+    }
+
+    fn is_superset_of(&self, other: &Chord) -> bool {
+        self.0.len() > other.0.len() && other.0.iter().all(|b| self.0.contains(b))
+    }
+}
+
+impl From<Button> for Chord {
+    fn from(button: Button) -> Self {
+        Self(vec![button])
+    }
+}
+
+impl From<Key> for Chord {
+    fn from(key: Key) -> Self {
+        Button::Key(key).into()
+    }
+}
+
+impl From<MouseButton> for Chord {
+    fn from(button: MouseButton) -> Self {
+        Button::Mouse(button).into()
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, button) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "+")?;
+            }
+            write!(f, "{}", button)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Any number of chords per action, any of them triggers it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionMap {
+    bindings: HashMap<Action, Vec<Chord>>,
+}
+
+impl ActionMap {
+    // The bindings the fly camera always had, plus function keys for the terrain debug toggles.
+    pub fn new() -> Self {
+        let mut map = Self {
+            bindings: HashMap::new(),
+        };
+
+        map.bind(Action::MoveForward, Key::W);
+        map.bind(Action::MoveBackward, Key::S);
+        map.bind(Action::MoveLeft, Key::A);
+        map.bind(Action::MoveRight, Key::D);
+        map.bind(Action::MoveUp, Key::Space);
+        map.bind(Action::MoveDown, Key::C);
+        map.bind(Action::Boost, Key::Shift);
+        map.bind(Action::Look, MouseButton::Right);
+        map.bind(Action::ToggleSolid, Key::F1);
+        map.bind(Action::ToggleWireframe, Key::F2);
+        map.bind(Action::ToggleStitching, Key::F3);
+        map.bind(Action::ToggleMorphing, Key::F4);
+        map.bind(Action::FreezeCamera, Key::F5);
+        map.bind(Action::FreezeCamera, Chord::new([Key::Control.into(), Key::F.into()]));
+        map.bind(Action::ToggleFrustumCulling, Key::F6);
+        map.bind(Action::FreezeCulling, Key::F7);
+
+        map
+    }
+
+    // Maps action names to lists of chords, like `{ "freeze_camera": ["F5", "Control+F"] }`. Actions that are not
+    // listed keep their default bindings, an empty list unbinds.
+    pub fn from_json(text: &str) -> Result<Self, ActionMapError> {
+        let file: HashMap<Action, Vec<String>> =
+            serde_json::from_str(text).map_err(|e| ActionMapError::Json(e.to_string()))?;
+
+        let mut map = Self::new();
+        for (action, chords) in file {
+            let chords = chords.iter().map(|c| Chord::parse(c)).collect::<Result<Vec<_>, _>>()?;
+            map.bindings.insert(action, chords);
+        }
+
+        Ok(map)
+    }
+
+    pub fn bind(&mut self, action: Action, chord: impl Into<Chord>) {
+        let chord = chord.into();
+        let chords = self.bindings.entry(action).or_default();
+
+        if !chords.contains(&chord) {
+            chords.push(chord);
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Chord] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Actions of one frame, derived from the buttons in `InputState` through an `ActionMap`.
+#[derive(Clone, Debug, Default)]
+pub struct ActionState {
+    held: HashSet<Action>,
+    pressed: HashSet<Action>,
+    released: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A chord is down while all of its buttons are, unless a larger chord containing it is down too. That way
+    // `Control+W` does not also move forward.
+    pub fn update(&mut self, map: &ActionMap, input: &InputState) {
+        let down = map
+            .bindings
+            .iter()
+            .flat_map(|(&action, chords)| chords.iter().map(move |c| (action, c)))
+            .filter(|(_, c)| c.is_down(input))
+            .collect::<Vec<_>>();
+
+        let was_held = std::mem::take(&mut self.held);
+        self.pressed.clear();
+        self.released.clear();
+
+        for &(action, chord) in &down {
+            if down.iter().any(|(_, other)| other.is_superset_of(chord)) {
+                continue;
+            }
+
+            if chord.is_held(input) {
+                self.held.insert(action);
+            }
+
+            // Another chord of an action that is already held is no new press.
+            if !was_held.contains(&action) && chord.buttons().iter().any(|&b| input.pressed(b)) {
+                self.pressed.insert(action);
+            }
+        }
+
+        // Taps within the frame are released right away.
+        self.released.extend(was_held.difference(&self.held));
+        self.released.extend(self.pressed.difference(&self.held));
+    }
+
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    // Started this frame.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    // Stopped this frame.
+    pub fn released(&self, action: Action) -> bool {
+        self.released.contains(&action)
+    }
+}
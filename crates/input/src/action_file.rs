@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::ActionMap;
+
+// An action map read from a JSON file and read again whenever the file changes. A broken file keeps the last good
+// bindings and reports why, a missing one uses the defaults.
+pub struct ActionMapFile {
+    path: PathBuf,
+    // Modification time and size of the last version read, `None` while there is no file.
+    version: Option<(SystemTime, u64)>,
+    map: ActionMap,
+    error: Option<String>,
+}
+
+impl ActionMapFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut file = Self {
+            path: path.into(),
+            version: None,
+            map: ActionMap::new(),
+            error: None,
+        };
+        file.reload();
+
+        file
+    }
+
+    // Cheap enough to call every frame, returns whether the bindings changed.
+    pub fn reload_if_changed(&mut self) -> bool {
+        if self.current_version() == self.version {
+            return false;
+        }
+
+        self.reload()
+    }
+
+    fn reload(&mut self) -> bool {
+        self.version = self.current_version();
+
+        let map = match fs::read_to_string(&self.path) {
+            Ok(text) => ActionMap::from_json(&text).map_err(|e| e.to_string()),
+            Err(_) if self.version.is_none() => Ok(ActionMap::new()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match map {
+            Ok(map) => {
+                let changed = map != self.map;
+                self.map = map;
+                self.error = None;
+
+                changed
+            }
+            Err(e) => {
+                self.error = Some(format!("{}: {}", self.path.display(), e));
+
+                false
+            }
+        }
+    }
+
+    fn current_version(&self) -> Option<(SystemTime, u64)> {
+        let metadata = fs::metadata(&self.path).ok()?;
+
+        Some((metadata.modified().ok()?, metadata.len()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn map(&self) -> &ActionMap {
+        &self.map
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
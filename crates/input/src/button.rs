@@ -0,0 +1,200 @@
+use std::fmt;
+
+const MOUSE_CODE_OFFSET: u8 = 0x80;
+
+// Keys by what they are, independent of keyboard layout codes of any platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Digit0,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    Enter,
+    Escape,
+    Tab,
+    Backspace,
+    Shift,
+    Control,
+    Alt,
+}
+
+impl Key {
+    pub const ALL: [Key; 60] = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+        Key::Digit0,
+        Key::Digit1,
+        Key::Digit2,
+        Key::Digit3,
+        Key::Digit4,
+        Key::Digit5,
+        Key::Digit6,
+        Key::Digit7,
+        Key::Digit8,
+        Key::Digit9,
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::Up,
+        Key::Down,
+        Key::Left,
+        Key::Right,
+        Key::Space,
+        Key::Enter,
+        Key::Escape,
+        Key::Tab,
+        Key::Backspace,
+        Key::Shift,
+        Key::Control,
+        Key::Alt,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    pub const ALL: [MouseButton; 3] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+}
+
+// Anything that is held down, so keys and mouse buttons share the same edge detection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl From<Key> for Button {
+    fn from(key: Key) -> Self {
+        Button::Key(key)
+    }
+}
+
+impl From<MouseButton> for Button {
+    fn from(button: MouseButton) -> Self {
+        Button::Mouse(button)
+    }
+}
+
+// Names are the variant names, mouse buttons are prefixed like `MouseRight`.
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Button::Key(key) => write!(f, "{:?}", key),
+            Button::Mouse(button) => write!(f, "Mouse{:?}", button),
+        }
+    }
+}
+
+impl Button {
+    // Index into `Key::ALL` or `MouseButton::ALL`, those only ever grow at the end so codes stay valid in recordings.
+    pub fn code(self) -> u8 {
+        match self {
+            Button::Key(key) => Key::ALL.iter().position(|&k| k == key).unwrap() as u8,
+            Button::Mouse(button) => {
+                MOUSE_CODE_OFFSET + MouseButton::ALL.iter().position(|&b| b == button).unwrap() as u8
+            }
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code.checked_sub(MOUSE_CODE_OFFSET) {
+            Some(index) => MouseButton::ALL.get(index as usize).copied().map(Button::Mouse),
+            None => Key::ALL.get(code as usize).copied().map(Button::Key),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Key::ALL
+            .into_iter()
+            .map(Button::Key)
+            .chain(MouseButton::ALL.into_iter().map(Button::Mouse))
+            .find(|button| button.to_string().eq_ignore_ascii_case(name))
+    }
+}
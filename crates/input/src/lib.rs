@@ -0,0 +1,11 @@
+mod action;
+mod action_file;
+mod button;
+mod recording;
+mod state;
+
+pub use action::*;
+pub use action_file::*;
+pub use button::*;
+pub use recording::*;
+pub use state::*;
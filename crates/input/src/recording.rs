@@ -0,0 +1,150 @@
+use std::io;
+use std::path::Path;
+
+use crate::InputState;
+
+const RECORDING_MAGIC: [u8; 4] = *b"TINP";
+const RECORDING_VERSION: u32 = 1;
+
+// What controllers saw in one frame and the time step they advanced by.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputFrame {
+    pub dt: f32,
+    pub input: InputState,
+}
+
+// Frames of input in order, stored in a small binary file so flight paths can be repeated exactly.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputRecording {
+    frames: Vec<InputFrame>,
+}
+
+impl InputRecording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Call before `InputState::end_frame`, so the edges of the frame are kept.
+    pub fn push(&mut self, dt: f32, input: &InputState) {
+        self.frames.push(InputFrame {
+            dt,
+            input: input.clone(),
+        });
+    }
+
+    pub fn frames(&self) -> &[InputFrame] {
+        &self.frames
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|f| f.dt).sum()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(RECORDING_MAGIC);
+        bytes.extend(RECORDING_VERSION.to_le_bytes());
+        bytes.extend((self.frames.len() as u32).to_le_bytes());
+
+        for frame in &self.frames {
+            bytes.extend(frame.dt.to_le_bytes());
+            frame.input.write(&mut bytes);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Option<Self> {
+        let header = bytes.split_off(..12)?;
+        let u32_at = |o: usize| u32::from_le_bytes(header[o..o + 4].try_into().unwrap());
+
+        if header[0..4] != RECORDING_MAGIC || u32_at(4) != RECORDING_VERSION {
+            return None;
+        }
+
+        let frames = (0..u32_at(8))
+            .map(|_| {
+                let dt = f32::from_le_bytes(bytes.split_off(..4)?.try_into().unwrap());
+                let input = InputState::read(&mut bytes)?;
+
+                Some(InputFrame { dt, input })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        bytes.is_empty().then_some(Self { frames })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+
+        Self::from_bytes(&bytes).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an input recording"))
+    }
+}
+
+// Hands out a recording in steps of a fixed `tick`, whatever the frame times were while recording. The recorded times
+// add up and are split into ticks, input of frames between two ticks is merged into the next one. Every replay
+// advances the same way on any machine.
+pub struct InputReplay {
+    recording: InputRecording,
+    next_frame_index: usize,
+    tick: f32,
+    time_left: f32,
+    pending: Option<InputState>,
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording, tick: f32) -> Self {
+        assert!(tick > 0.0);
+
+        Self {
+            recording,
+            next_frame_index: 0,
+            tick,
+            time_left: 0.0,
+            pending: None,
+        }
+    }
+
+    // Steps that fit in the next recorded frame, each with `tick` as its `dt`. Can be empty for frames shorter than a
+    // tick, their input comes with the next step.
+    pub fn next_frame(&mut self) -> Option<Vec<InputFrame>> {
+        let frame = self.recording.frames.get(self.next_frame_index)?;
+        self.next_frame_index += 1;
+
+        match &mut self.pending {
+            Some(pending) => pending.merge(&frame.input),
+            None => self.pending = Some(frame.input.clone()),
+        }
+        self.time_left += frame.dt;
+
+        let mut steps = Vec::new();
+        while self.time_left >= self.tick {
+            self.time_left -= self.tick;
+
+            let input = self.pending.as_mut().unwrap();
+            steps.push(InputFrame {
+                dt: self.tick,
+                input: input.clone(),
+            });
+            input.end_frame();
+        }
+
+        Some(steps)
+    }
+
+    pub fn tick(&self) -> f32 {
+        self.tick
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.next_frame_index
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.recording.frames.len()
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use glam::Vec2;
+
+use crate::Button;
+
+// Input of one frame, filled from platform events and read by controllers. Edges and motion accumulate until
+// `end_frame`, so a press and release within the same frame are both seen.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputState {
+    held: HashSet<Button>,
+    pressed: HashSet<Button>,
+    released: HashSet<Button>,
+
+    // Window pixels, `None` until the first mouse event.
+    mouse_pos: Option<Vec2>,
+    mouse_delta: Vec2,
+    // Wheel notches, positive away from the user.
+    wheel_delta: f32,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Repeated presses of a held button, like keyboard auto repeat, are ignored.
+    pub fn press(&mut self, button: impl Into<Button>) {
+        let button = button.into();
+        if self.held.insert(button) {
+            self.pressed.insert(button);
+        }
+    }
+
+    pub fn release(&mut self, button: impl Into<Button>) {
+        let button = button.into();
+        if self.held.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    // For when the window loses focus and would never see the matching releases.
+    pub fn release_all(&mut self) {
+        self.released.extend(self.held.drain());
+    }
+
+    pub fn move_mouse(&mut self, pos: Vec2) {
+        if let Some(old_pos) = self.mouse_pos {
+            self.mouse_delta += pos - old_pos;
+        }
+
+        self.mouse_pos = Some(pos);
+    }
+
+    // Moves the cursor without any motion, for when the UI owns the mouse.
+    pub fn set_mouse_pos(&mut self, pos: Vec2) {
+        self.mouse_pos = Some(pos);
+    }
+
+    pub fn scroll(&mut self, notches: f32) {
+        self.wheel_delta += notches;
+    }
+
+    // Folds a later frame into this one as if both had been a single frame.
+    pub fn merge(&mut self, later: &InputState) {
+        self.held.clone_from(&later.held);
+        self.pressed.extend(&later.pressed);
+        self.released.extend(&later.released);
+        self.mouse_pos = later.mouse_pos.or(self.mouse_pos);
+        self.mouse_delta += later.mouse_delta;
+        self.wheel_delta += later.wheel_delta;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_delta = Vec2::ZERO;
+        self.wheel_delta = 0.0;
+    }
+
+    pub fn held(&self, button: impl Into<Button>) -> bool {
+        self.held.contains(&button.into())
+    }
+
+    // Went down this frame.
+    pub fn pressed(&self, button: impl Into<Button>) -> bool {
+        self.pressed.contains(&button.into())
+    }
+
+    // Went up this frame.
+    pub fn released(&self, button: impl Into<Button>) -> bool {
+        self.released.contains(&button.into())
+    }
+
+    pub fn mouse_pos(&self) -> Vec2 {
+        self.mouse_pos.unwrap_or(Vec2::ZERO)
+    }
+
+    pub fn mouse_delta(&self) -> Vec2 {
+        self.mouse_delta
+    }
+
+    pub fn wheel_delta(&self) -> f32 {
+        self.wheel_delta
+    }
+
+    // Little endian, buttons as `Button::code` in ascending order so equal states encode the same.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.mouse_pos.is_some() as u8);
+        for value in [self.mouse_pos.unwrap_or(Vec2::ZERO), self.mouse_delta] {
+            out.extend(value.x.to_le_bytes());
+            out.extend(value.y.to_le_bytes());
+        }
+        out.extend(self.wheel_delta.to_le_bytes());
+
+        for buttons in [&self.held, &self.pressed, &self.released] {
+            let mut codes = buttons.iter().map(|b| b.code()).collect::<Vec<_>>();
+            codes.sort_unstable();
+
+            out.push(codes.len() as u8);
+            out.extend(codes);
+        }
+    }
+
+    // Reads what `write` wrote from the front of `bytes` and advances past it.
+    pub fn read(bytes: &mut &[u8]) -> Option<Self> {
+        fn take<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+            let (head, rest) = bytes.split_first_chunk::<N>()?;
+            *bytes = rest;
+
+            Some(*head)
+        }
+
+        let f32_at = |bytes: &mut &[u8]| take::<4>(bytes).map(f32::from_le_bytes);
+        let vec2_at = |bytes: &mut &[u8]| Some(Vec2::new(f32_at(bytes)?, f32_at(bytes)?));
+
+        let [has_mouse_pos] = take::<1>(bytes)?;
+        let mouse_pos = vec2_at(bytes)?;
+        let mut state = Self {
+            mouse_pos: (has_mouse_pos != 0).then_some(mouse_pos),
+            mouse_delta: vec2_at(bytes)?,
+            wheel_delta: f32_at(bytes)?,
+            ..Self::default()
+        };
+
+        for buttons in [&mut state.held, &mut state.pressed, &mut state.released] {
+            let [count] = take::<1>(bytes)?;
+            for _ in 0..count {
+                let [code] = take::<1>(bytes)?;
+                buttons.insert(Button::from_code(code)?);
+            }
+        }
+
+        Some(state)
+    }
+}
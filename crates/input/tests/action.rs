@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use input::*;
+
+fn bindings_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("input-bindings-{}-{}.json", name, std::process::id()));
+    _ = std::fs::remove_file(&path);
+
+    path
+}
+
+// Writes the file and moves its modification time, so a reload is seen even on file systems with coarse timestamps.
+fn write_bindings(path: &PathBuf, text: &str, age_s: u64) {
+    std::fs::write(path, text).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(age_s))
+        .unwrap();
+}
+
+#[test]
+fn shipped_bindings_are_the_defaults() {
+    let map = ActionMap::from_json(include_str!("../../../assets/input_bindings.json")).unwrap();
+    assert_eq!(map, ActionMap::new());
+
+    for action in Action::ALL {
+        assert!(!map.bindings(action).is_empty(), "{action:?}");
+    }
+}
+
+#[test]
+fn parses_chords_and_overrides() {
+    let chord = Chord::parse("Control + shift+MouseLeft").unwrap();
+    assert_eq!(
+        chord.buttons(),
+        [Button::from(Key::Control), Key::Shift.into(), MouseButton::Left.into()]
+    );
+    assert_eq!(chord.to_string(), "Control+Shift+MouseLeft");
+    assert_eq!(
+        Chord::parse("Control+Hyper"),
+        Err(ActionMapError::UnknownButton("Hyper".into()))
+    );
+
+    let map = ActionMap::from_json(r#"{ "move_forward": ["Up", "W"], "boost": [] }"#).unwrap();
+    assert_eq!(map.bindings(Action::MoveForward), [Chord::from(Key::Up), Key::W.into()]);
+    assert!(map.bindings(Action::Boost).is_empty());
+    assert_eq!(map.bindings(Action::MoveBackward), [Chord::from(Key::S)]);
+
+    assert!(matches!(
+        ActionMap::from_json(r#"{ "jump": ["Space"] }"#),
+        Err(ActionMapError::Json(_))
+    ));
+}
+
+#[test]
+fn any_binding_triggers_an_action() {
+    let mut map = ActionMap::new();
+    map.bind(Action::MoveForward, Key::Up);
+
+    let mut input = InputState::new();
+    let mut actions = ActionState::new();
+
+    input.press(Key::Up);
+    actions.update(&map, &input);
+    assert!(actions.pressed(Action::MoveForward));
+    assert!(actions.held(Action::MoveForward));
+
+    // The second binding going down is no new press, the action lasts until both are up.
+    input.end_frame();
+    input.press(Key::W);
+    input.release(Key::Up);
+    actions.update(&map, &input);
+    assert!(!actions.pressed(Action::MoveForward));
+    assert!(actions.held(Action::MoveForward));
+
+    input.end_frame();
+    input.release(Key::W);
+    actions.update(&map, &input);
+    assert!(actions.released(Action::MoveForward));
+    assert!(!actions.held(Action::MoveForward));
+
+    // A tap within one frame still toggles.
+    input.end_frame();
+    input.press(Key::F2);
+    input.release(Key::F2);
+    actions.update(&map, &input);
+    assert!(actions.pressed(Action::ToggleWireframe));
+    assert!(actions.released(Action::ToggleWireframe));
+    assert!(!actions.held(Action::ToggleWireframe));
+}
+
+#[test]
+fn chords_shadow_their_parts() {
+    let mut map = ActionMap::new();
+    map.bind(Action::ToggleWireframe, Chord::parse("Control+W").unwrap());
+
+    let mut input = InputState::new();
+    let mut actions = ActionState::new();
+
+    input.press(Key::W);
+    actions.update(&map, &input);
+    assert!(actions.held(Action::MoveForward));
+    assert!(!actions.pressed(Action::ToggleWireframe));
+
+    // Adding the modifier completes the chord, which takes W away from moving.
+    input.end_frame();
+    input.press(Key::Control);
+    actions.update(&map, &input);
+    assert!(actions.pressed(Action::ToggleWireframe));
+    assert!(actions.released(Action::MoveForward));
+    assert!(!actions.held(Action::MoveForward));
+
+    // Holding the chord does not toggle again.
+    input.end_frame();
+    actions.update(&map, &input);
+    assert!(!actions.pressed(Action::ToggleWireframe));
+    assert!(actions.held(Action::ToggleWireframe));
+
+    input.end_frame();
+    input.release(Key::Control);
+    actions.update(&map, &input);
+    assert!(actions.released(Action::ToggleWireframe));
+    assert!(actions.held(Action::MoveForward));
+}
+
+#[test]
+fn file_reloads_when_changed() {
+    let path = bindings_path("reload");
+
+    // Without a file the defaults apply.
+    let mut file = ActionMapFile::new(&path);
+    assert_eq!(file.map(), &ActionMap::new());
+    assert_eq!(file.error(), None);
+    assert!(!file.reload_if_changed());
+
+    write_bindings(&path, r#"{ "move_up": ["E"] }"#, 30);
+    assert!(file.reload_if_changed());
+    assert_eq!(file.map().bindings(Action::MoveUp), [Chord::from(Key::E)]);
+    assert!(!file.reload_if_changed());
+
+    // A broken edit keeps the last good bindings.
+    write_bindings(&path, r#"{ "move_up": ["E" "#, 20);
+    assert!(!file.reload_if_changed());
+    assert!(file.error().is_some());
+    assert_eq!(file.map().bindings(Action::MoveUp), [Chord::from(Key::E)]);
+
+    write_bindings(&path, r#"{ "move_up": ["Q", "Control+E"] }"#, 10);
+    assert!(file.reload_if_changed());
+    assert_eq!(file.error(), None);
+    assert_eq!(file.map().bindings(Action::MoveUp).len(), 2);
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(file.reload_if_changed());
+    assert_eq!(file.map(), &ActionMap::new());
+}
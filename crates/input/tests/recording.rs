@@ -0,0 +1,103 @@
+use glam::Vec2;
+use input::*;
+
+fn sample_recording() -> InputRecording {
+    let mut input = InputState::new();
+    let mut recording = InputRecording::new();
+
+    recording.push(0.016, &input);
+
+    input.press(Key::W);
+    input.press(MouseButton::Right);
+    input.move_mouse(Vec2::new(10.0, 20.0));
+    input.move_mouse(Vec2::new(13.5, 18.0));
+    input.scroll(-1.0);
+    recording.push(0.017, &input);
+    input.end_frame();
+
+    input.press(Key::F12);
+    input.release(Key::F12);
+    input.release(Key::W);
+    recording.push(0.5, &input);
+
+    recording
+}
+
+#[test]
+fn recordings_round_trip() {
+    let recording = sample_recording();
+    let bytes = recording.to_bytes();
+
+    let loaded = InputRecording::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded, recording);
+    assert_eq!(loaded.duration(), 0.016 + 0.017 + 0.5);
+
+    let last = &loaded.frames()[2].input;
+    assert!(last.pressed(Key::F12) && last.released(Key::F12));
+    assert!(last.released(Key::W));
+    assert!(last.held(MouseButton::Right));
+
+    // The encoding does not depend on hash set order.
+    assert_eq!(loaded.to_bytes(), bytes);
+}
+
+#[test]
+fn rejects_damaged_recordings() {
+    let bytes = sample_recording().to_bytes();
+
+    assert!(InputRecording::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+    assert!(InputRecording::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_none());
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'X';
+    assert!(InputRecording::from_bytes(&wrong_magic).is_none());
+}
+
+#[test]
+fn replay_steps_at_a_fixed_tick() {
+    let recording = sample_recording();
+    let mut replay = InputReplay::new(recording.clone(), 0.01);
+    assert_eq!(replay.frame_count(), 3);
+
+    // 16 ms fit one tick, the 6 ms left over carry into the next frame.
+    let steps = replay.next_frame().unwrap();
+    assert_eq!(steps.len(), 1);
+    assert_eq!(steps[0].input, recording.frames()[0].input);
+
+    let steps = replay.next_frame().unwrap();
+    assert_eq!(steps.len(), 2);
+    assert!(steps.iter().all(|step| step.dt == 0.01));
+
+    // Edges and mouse movement come with the first step only, held buttons stay.
+    let (first, second) = (&steps[0].input, &steps[1].input);
+    assert!(first.pressed(Key::W) && first.held(Key::W));
+    assert!(!second.pressed(Key::W) && second.held(Key::W));
+    assert_eq!(first.mouse_delta(), Vec2::new(3.5, -2.0));
+    assert_eq!(second.mouse_delta(), Vec2::ZERO);
+    assert_eq!(first.wheel_delta(), -1.0);
+    assert_eq!(second.wheel_delta(), 0.0);
+
+    let steps = replay.next_frame().unwrap();
+    assert!((49..=51).contains(&steps.len()));
+    assert!(steps[0].input.released(Key::W) && !steps[0].input.held(Key::W));
+
+    assert_eq!(replay.frame_index(), 3);
+    assert_eq!(replay.next_frame(), None);
+}
+
+#[test]
+fn replay_merges_frames_shorter_than_a_tick() {
+    let recording = sample_recording();
+    let mut replay = InputReplay::new(recording, 0.04);
+
+    assert!(replay.next_frame().unwrap().is_empty());
+
+    // The press of the second frame is not lost, it comes with the step ending in the third frame.
+    assert!(replay.next_frame().unwrap().is_empty());
+    let steps = replay.next_frame().unwrap();
+    assert_eq!(steps.len(), 13);
+    assert!(steps[0].input.pressed(Key::W) && steps[0].input.released(Key::W));
+    assert!(steps[0].input.pressed(Key::F12));
+    assert_eq!(steps[0].input.wheel_delta(), -1.0);
+    assert!(steps[1..].iter().all(|step| !step.input.pressed(Key::W)));
+}
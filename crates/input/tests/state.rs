@@ -0,0 +1,91 @@
+use glam::Vec2;
+use input::*;
+
+#[test]
+fn edges_last_one_frame() {
+    let mut input = InputState::new();
+
+    input.press(Key::W);
+    assert!(input.pressed(Key::W));
+    assert!(input.held(Key::W));
+    assert!(!input.released(Key::W));
+
+    input.end_frame();
+    assert!(!input.pressed(Key::W));
+    assert!(input.held(Key::W));
+
+    // Auto repeat of a held key is not another press.
+    input.press(Key::W);
+    assert!(!input.pressed(Key::W));
+
+    input.release(Key::W);
+    assert!(input.released(Key::W));
+    assert!(!input.held(Key::W));
+
+    input.end_frame();
+    assert!(!input.released(Key::W));
+}
+
+#[test]
+fn tap_within_a_frame_is_not_lost() {
+    let mut input = InputState::new();
+
+    input.press(MouseButton::Left);
+    input.release(MouseButton::Left);
+
+    assert!(input.pressed(MouseButton::Left));
+    assert!(input.released(MouseButton::Left));
+    assert!(!input.held(MouseButton::Left));
+
+    // Keys and mouse buttons do not alias.
+    assert!(!input.pressed(Key::A));
+    assert!(!input.pressed(MouseButton::Right));
+}
+
+#[test]
+fn release_all_releases_held_buttons() {
+    let mut input = InputState::new();
+
+    input.press(Key::Shift);
+    input.press(MouseButton::Right);
+    input.end_frame();
+    input.release_all();
+
+    assert!(!input.held(Key::Shift));
+    assert!(!input.held(MouseButton::Right));
+    assert!(input.released(Key::Shift));
+    assert!(input.released(MouseButton::Right));
+
+    // Releasing what is not held has no edge.
+    input.end_frame();
+    input.release(Key::Shift);
+    assert!(!input.released(Key::Shift));
+}
+
+#[test]
+fn mouse_motion_accumulates_per_frame() {
+    let mut input = InputState::new();
+
+    // The first position has nothing to be relative to.
+    input.move_mouse(Vec2::new(100.0, 100.0));
+    assert_eq!(input.mouse_delta(), Vec2::ZERO);
+
+    input.move_mouse(Vec2::new(110.0, 95.0));
+    input.move_mouse(Vec2::new(115.0, 90.0));
+    input.scroll(1.0);
+    input.scroll(0.5);
+
+    assert_eq!(input.mouse_pos(), Vec2::new(115.0, 90.0));
+    assert_eq!(input.mouse_delta(), Vec2::new(15.0, -10.0));
+    assert_eq!(input.wheel_delta(), 1.5);
+
+    input.end_frame();
+    assert_eq!(input.mouse_pos(), Vec2::new(115.0, 90.0));
+    assert_eq!(input.mouse_delta(), Vec2::ZERO);
+    assert_eq!(input.wheel_delta(), 0.0);
+
+    // Positions set while the UI owns the mouse do not count as motion.
+    input.set_mouse_pos(Vec2::new(300.0, 300.0));
+    input.move_mouse(Vec2::new(302.0, 300.0));
+    assert_eq!(input.mouse_delta(), Vec2::new(2.0, 0.0));
+}
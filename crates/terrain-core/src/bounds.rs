@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec3};
+
+use crate::{PatchKey, TerrainScale, height_map_pixel_size};
+
+// Quadtree roots can be coarser than the coarsest generated LOD, ranges are aggregated up to this LOD.
+const BOUNDS_MAX_LOD_INDEX: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeightRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl HeightRange {
+    // Generated heights are normalized, used for patches whose data is not known yet.
+    pub const FULL: HeightRange = HeightRange { min: 0.0, max: 1.0 };
+
+    pub fn from_heights(heights: &[f32]) -> Self {
+        heights.iter().fold(
+            HeightRange {
+                min: f32::INFINITY,
+                max: f32::NEG_INFINITY,
+            },
+            |range, &h| HeightRange {
+                min: range.min.min(h),
+                max: range.max.max(h),
+            },
+        )
+    }
+
+    pub fn union(self, other: HeightRange) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+// Min/max heights of the quads of one patch, level 0 has one cell per quad and every following level halves the
+// resolution down to a single cell covering the whole patch.
+pub struct HeightRangePyramid {
+    pixel_size: u32,
+    levels: Vec<Vec<HeightRange>>,
+}
+
+impl HeightRangePyramid {
+    pub fn new(heights: &[f32]) -> Self {
+        let pixel_size = height_map_pixel_size(heights);
+        let h = |x: u32, y: u32| heights[(y * (pixel_size + 1) + x) as usize];
+
+        let quads = (0..pixel_size * pixel_size)
+            .map(|i| {
+                let (x, y) = (i % pixel_size, i / pixel_size);
+                let corners = [h(x, y), h(x + 1, y), h(x, y + 1), h(x + 1, y + 1)];
+
+                HeightRange::from_heights(&corners)
+            })
+            .collect::<Vec<_>>();
+
+        let mut levels = vec![quads];
+
+        while levels.last().unwrap().len() > 1 {
+            let prev = levels.last().unwrap();
+            let prev_size = prev.len().isqrt();
+            let size = prev_size / 2;
+
+            let level = (0..size * size)
+                .map(|i| {
+                    let (x, y) = (i % size * 2, i / size * 2);
+
+                    prev[y * prev_size + x]
+                        .union(prev[y * prev_size + x + 1])
+                        .union(prev[(y + 1) * prev_size + x])
+                        .union(prev[(y + 1) * prev_size + x + 1])
+                })
+                .collect();
+
+            levels.push(level);
+        }
+
+        Self { pixel_size, levels }
+    }
+
+    pub fn level_count(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    pub fn level_size(&self, level: u32) -> u32 {
+        self.pixel_size >> level
+    }
+
+    pub fn cell(&self, level: u32, cell: IVec2) -> HeightRange {
+        let size = self.level_size(level) as i32;
+        self.levels[level as usize][(cell.y * size + cell.x) as usize]
+    }
+
+    pub fn range(&self) -> HeightRange {
+        self.levels.last().unwrap()[0]
+    }
+}
+
+// Largest difference between a patch and the same area sampled at half the resolution, which is what its parent
+// patch shows there. Normalized height units.
+pub fn patch_decimation_error(heights: &[f32]) -> f32 {
+    let pixel_size = height_map_pixel_size(heights);
+    let h = |x: u32, y: u32| heights[(y * (pixel_size + 1) + x) as usize];
+
+    let mut max_error = 0.0_f32;
+    for y in 0..=pixel_size {
+        for x in 0..=pixel_size {
+            let (x0, y0) = (x & !1, y & !1);
+            let (x1, y1) = ((x0 + 2).min(pixel_size), (y0 + 2).min(pixel_size));
+            let (tx, ty) = ((x - x0) as f32 * 0.5, (y - y0) as f32 * 0.5);
+
+            let top = h(x0, y0) * (1.0 - tx) + h(x1, y0) * tx;
+            let bottom = h(x0, y1) * (1.0 - tx) + h(x1, y1) * tx;
+            let coarse = top * (1.0 - ty) + bottom * ty;
+
+            max_error = max_error.max((h(x, y) - coarse).abs());
+        }
+    }
+
+    max_error
+}
+
+// Height ranges of patches and their quadtree ancestors. A node's range is the union of its own patch and every known
+// patch below it, so it bounds whatever is drawn for the node. It is only reported once the node is covered, by its
+// own patch or by four covered children.
+pub struct PatchBoundsTree {
+    patch_ranges: HashMap<PatchKey, HeightRange>,
+    ranges: HashMap<PatchKey, NodeRange>,
+    decimation_errors: HashMap<PatchKey, f32>,
+    revision: u64,
+    // Revision of the last change to a node or anything below it. Entries outlive their patches so that removals are
+    // seen as changes too.
+    subtree_revisions: HashMap<PatchKey, u64>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct NodeRange {
+    range: HeightRange,
+    covered: bool,
+}
+
+impl PatchBoundsTree {
+    pub fn new() -> Self {
+        Self {
+            patch_ranges: HashMap::new(),
+            ranges: HashMap::new(),
+            decimation_errors: HashMap::new(),
+            revision: 0,
+            subtree_revisions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.patch_ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patch_ranges.is_empty()
+    }
+
+    // Changes with every insert and remove that changes anything, lets users cache results derived from the tree.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Last `revision` that changed `key` or a node below it, 0 if none ever did. Results derived from a subtree stay
+    // valid as long as this is not newer than the revision they were derived at.
+    pub fn subtree_revision(&self, key: &PatchKey) -> u64 {
+        self.subtree_revisions.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn insert(&mut self, key: PatchKey, range: HeightRange) {
+        if self.patch_ranges.insert(key, range) != Some(range) {
+            self.update_ancestors(key);
+            self.mark_changed(key);
+        }
+    }
+
+    // Forgets a patch that is no longer kept, ancestors shrink back to the patches that are left.
+    pub fn remove(&mut self, key: &PatchKey) {
+        let had_error = self.decimation_errors.remove(key).is_some();
+        let had_range = self.patch_ranges.remove(key).is_some();
+
+        if had_range {
+            self.update_ancestors(*key);
+        }
+        if had_error || had_range {
+            self.mark_changed(*key);
+        }
+    }
+
+    pub fn range(&self, key: &PatchKey) -> Option<HeightRange> {
+        self.ranges.get(key).filter(|r| r.covered).map(|r| r.range)
+    }
+
+    pub fn insert_decimation_error(&mut self, key: PatchKey, error: f32) {
+        if self.decimation_errors.insert(key, error) != Some(error) {
+            self.mark_changed(key);
+        }
+    }
+
+    // Height error of drawing `key` instead of its children, measured on the children that are known. Without any
+    // children the error is estimated from the node's own data, assuming detail halves with every finer LOD.
+    pub fn geometric_error(&self, key: &PatchKey) -> Option<f32> {
+        if key.lod_index == 0 {
+            return Some(0.0);
+        }
+
+        let offset = 1 << (key.lod_index - 1);
+        let children_error = [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]
+            .into_iter()
+            .filter_map(|o| {
+                self.decimation_errors.get(&PatchKey {
+                    world_index: key.world_index + o * offset,
+                    lod_index: key.lod_index - 1,
+                })
+            })
+            .copied()
+            .reduce(f32::max);
+
+        children_error.or_else(|| self.decimation_errors.get(key).map(|e| e * 0.5))
+    }
+
+    // Rendered world space bounds of a node, unknown heights span the full normalized range.
+    pub fn aabb(&self, key: &PatchKey, scale: &TerrainScale) -> (Vec3, Vec3) {
+        let range = self.range(key).unwrap_or(HeightRange::FULL);
+        let pos = key.world_pos().as_vec2() * scale.world_scale;
+        let size = key.world_size() as f32 * scale.world_scale;
+
+        (
+            Vec3::new(pos.x, range.min * scale.height_scale, pos.y),
+            Vec3::new(pos.x + size, range.max * scale.height_scale, pos.y + size),
+        )
+    }
+
+    fn mark_changed(&mut self, mut key: PatchKey) {
+        self.revision += 1;
+
+        loop {
+            self.subtree_revisions.insert(key, self.revision);
+
+            if key.lod_index >= BOUNDS_MAX_LOD_INDEX {
+                break;
+            }
+
+            key = PatchKey {
+                world_index: (key.world_index >> (key.lod_index + 1)) << (key.lod_index + 1),
+                lod_index: key.lod_index + 1,
+            };
+        }
+    }
+
+    // Recomputes `key` and its ancestors until a node comes out unchanged.
+    fn update_ancestors(&mut self, mut key: PatchKey) {
+        loop {
+            let changed = match self.node_range(&key) {
+                Some(range) => self.ranges.insert(key, range) != Some(range),
+                None => self.ranges.remove(&key).is_some(),
+            };
+
+            if !changed || key.lod_index >= BOUNDS_MAX_LOD_INDEX {
+                break;
+            }
+
+            key = PatchKey {
+                world_index: (key.world_index >> (key.lod_index + 1)) << (key.lod_index + 1),
+                lod_index: key.lod_index + 1,
+            };
+        }
+    }
+
+    fn node_range(&self, key: &PatchKey) -> Option<NodeRange> {
+        let own = self.patch_ranges.get(key).copied();
+        let children = if key.lod_index == 0 {
+            [None; 4]
+        } else {
+            let offset = 1 << (key.lod_index - 1);
+            [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE].map(|o| {
+                self.ranges
+                    .get(&PatchKey {
+                        world_index: key.world_index + o * offset,
+                        lod_index: key.lod_index - 1,
+                    })
+                    .copied()
+            })
+        };
+
+        let range = own
+            .into_iter()
+            .chain(children.iter().flatten().map(|c| c.range))
+            .reduce(HeightRange::union)?;
+        let covered = own.is_some() || children.iter().all(|c| c.is_some_and(|c| c.covered));
+
+        Some(NodeRange { range, covered })
+    }
+}
+
+impl Default for PatchBoundsTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,300 @@
+use std::fmt;
+
+use glam::{IVec2, UVec2};
+
+use crate::{
+    ATLAS_PATCH_COUNT, INDIRECTION_SLOT_COUNT, LodPolicy, PATCH_LOD_COUNT, PATCH_PIXEL_SIZE, PATCH_WORLD_SIZE,
+    PatchKey, TerrainScale,
+};
+
+// Size of the morph range array in the shader constants, mirrored in terrain.hlsl.
+pub const MAX_PATCH_LOD_COUNT: u32 = 8;
+
+// D3D12 limit for the width and height of 2D textures.
+pub const MAX_TEXTURE_SIZE: u32 = 16384;
+
+// Largest indirection table, 2048² slots of 8 bytes take 32 MB per copy and there is one per frame in flight.
+pub const MAX_INDIRECTION_SLOT_COUNT: u32 = 2048;
+
+// Sizes of everything the terrain allocates, shared by the CPU side and the shader constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TerrainConfig {
+    pub lod_count: u32,
+    // Quads per patch side, height maps store one more row and column for the overlap.
+    pub patch_pixel_size: u32,
+    // Patches per atlas side.
+    pub atlas_patch_count: u32,
+    // Slots per indirection side of the finest LOD, every coarser LOD has half as many.
+    pub indirection_slot_count: u32,
+}
+
+// What the GPU side needs to draw every leaf at a render distance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TerrainCoverage {
+    pub render_distance: u32,
+    // Slots per indirection side of the finest LOD, never fewer than configured.
+    pub indirection_slot_count: u32,
+    // Upper bound of the leaf count for any camera position.
+    pub max_leaf_count: u32,
+    // Upper bound of the patches drawn per frame, only resident leafs are drawn so the atlas caps it as well.
+    pub patch_capacity: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainConfigError {
+    LodCount(u32),
+    PatchPixelSize(u32),
+    AtlasSize(u32),
+    IndirectionSlotCount(u32),
+    RenderDistance {
+        render_distance: u32,
+        min_render_distance: u32,
+        max_render_distance: u32,
+    },
+}
+
+impl fmt::Display for TerrainConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TerrainConfigError::LodCount(count) => {
+                write!(f, "LOD count {} is not in 1..={}", count, MAX_PATCH_LOD_COUNT)
+            }
+            TerrainConfigError::PatchPixelSize(size) => {
+                write!(f, "patch pixel size {} is not a power of two of at least 2", size)
+            }
+            TerrainConfigError::AtlasSize(size) => {
+                write!(f, "atlas size {} is not in 1..={}", size, MAX_TEXTURE_SIZE)
+            }
+            TerrainConfigError::IndirectionSlotCount(count) => write!(
+                f,
+                "indirection slot count {} is not a power of two leaving at least 2 slots for the coarsest LOD",
+                count
+            ),
+            TerrainConfigError::RenderDistance {
+                render_distance,
+                min_render_distance,
+                max_render_distance,
+            } => write!(
+                f,
+                "render distance {} is not in the indirection coverage of {}..={}",
+                render_distance, min_render_distance, max_render_distance
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TerrainConfigError {}
+
+impl TerrainConfig {
+    pub fn new() -> Self {
+        Self {
+            lod_count: PATCH_LOD_COUNT,
+            patch_pixel_size: PATCH_PIXEL_SIZE,
+            atlas_patch_count: ATLAS_PATCH_COUNT,
+            indirection_slot_count: INDIRECTION_SLOT_COUNT,
+        }
+    }
+
+    pub fn atlas_patch_pixel_size(&self) -> u32 {
+        self.patch_pixel_size + 1 // for pixel overlap
+    }
+
+    pub fn atlas_size(&self) -> u32 {
+        self.atlas_patch_pixel_size() * self.atlas_patch_count
+    }
+
+    pub fn height_map_len(&self) -> usize {
+        self.atlas_patch_pixel_size().pow(2) as usize
+    }
+
+    pub fn patch_index_count(&self) -> u32 {
+        self.patch_pixel_size.pow(2) * 6
+    }
+
+    pub fn coarsest_lod_index(&self) -> u32 {
+        self.lod_count - 1
+    }
+
+    pub fn coarsest_patch_world_size(&self) -> u32 {
+        PATCH_WORLD_SIZE << self.coarsest_lod_index()
+    }
+
+    pub fn indirection_slot_count(&self, lod_index: u32) -> u32 {
+        self.indirection_slot_count >> lod_index
+    }
+
+    // Slot of `key` in its LOD's indirection table if it is in the window centered on `cam_world_index`.
+    pub fn indirection_slot(&self, key: &PatchKey, cam_world_index: IVec2) -> Option<UVec2> {
+        let lod_index = key.lod_index;
+        let slot_count = self.indirection_slot_count(lod_index);
+
+        let relative_index = (key.world_index >> lod_index) - (cam_world_index >> lod_index);
+        let window_index = relative_index + slot_count as i32 / 2;
+
+        let range = 0..slot_count as i32;
+        (range.contains(&window_index.x) && range.contains(&window_index.y)).then(|| self.toroidal_slot(key))
+    }
+
+    // Slots wrap around by world index, so a window that moves keeps the slots of the patches it still covers.
+    // Mirrored by `ProcessVertex` in terrain.hlsl.
+    pub fn toroidal_slot(&self, key: &PatchKey) -> UVec2 {
+        let slot_count = self.indirection_slot_count(key.lod_index) as i32;
+
+        (key.world_index >> key.lod_index)
+            .rem_euclid(IVec2::splat(slot_count))
+            .as_uvec2()
+    }
+
+    // The quadtree root has to hold at least one patch of the finest LOD.
+    pub fn min_render_distance(&self) -> u32 {
+        PATCH_WORLD_SIZE / 2
+    }
+
+    // Farthest render distance whose leafs all fall into the indirection window. The quadtree root snaps to the
+    // coarsest patch size and the window to the finest, which costs up to one patch of each.
+    pub fn max_render_distance(&self) -> u32 {
+        self.max_render_distance_for(self.indirection_slot_count)
+    }
+
+    fn max_render_distance_for(&self, indirection_slot_count: u32) -> u32 {
+        (indirection_slot_count / 2 * PATCH_WORLD_SIZE)
+            .saturating_sub(self.coarsest_patch_world_size() + PATCH_WORLD_SIZE)
+    }
+
+    // Smallest power of two slot count whose `max_render_distance` reaches `render_distance`, but no fewer than
+    // configured. Not limited to `MAX_INDIRECTION_SLOT_COUNT`, `coverage` rejects the render distance instead.
+    pub fn indirection_slot_count_for(&self, render_distance: u32) -> u32 {
+        let half_slot_count =
+            (render_distance + self.coarsest_patch_world_size() + PATCH_WORLD_SIZE).div_ceil(PATCH_WORLD_SIZE);
+
+        (half_slot_count * 2)
+            .next_power_of_two()
+            .max(self.indirection_slot_count)
+    }
+
+    // Upper bound of the leaf count for any camera position. A quadtree with `n` split nodes has `3n + 1` leafs. Nodes
+    // above the coarsest LOD are always split, the rest only within their split distance of the camera or when
+    // touching a split node one LOD finer, which balancing needs. The screen space error rule has no bound on its
+    // split distance, so all of its nodes count as split.
+    pub fn max_leaf_count(
+        &self,
+        render_distance: u32,
+        lod_policy: &LodPolicy,
+        hysteresis: f32,
+        scale: &TerrainScale,
+    ) -> u32 {
+        let root_lod_index = (render_distance * 2 / PATCH_WORLD_SIZE).max(1).ilog2();
+
+        let mut split_node_count = 0_u64;
+        // Farthest horizontal distance of a split node from the camera, in unscaled world units.
+        let mut split_radius: Option<f32> = None;
+
+        for lod_index in 1..=root_lod_index {
+            let node_size = (PATCH_WORLD_SIZE << lod_index) as f32;
+            let side_node_count = 1_u64 << (root_lod_index - lod_index);
+
+            let split_distance = match *lod_policy {
+                LodPolicy::Distance { lod_factor } => {
+                    Some(node_size * 0.5 * lod_factor * (1.0 + hysteresis) / scale.world_scale)
+                }
+                LodPolicy::ScreenSpaceError { .. } => None,
+            };
+            let balance_distance = split_radius.map(|r| r + node_size * 0.5 * std::f32::consts::SQRT_2);
+
+            split_radius = match (split_distance, balance_distance) {
+                (Some(d), Some(b)) => Some(d.max(b)),
+                (Some(d), None) => Some(d),
+                (None, _) => None,
+            }
+            .filter(|r| *r > 0.0);
+
+            let side_split_count = match split_radius {
+                _ if lod_index > self.coarsest_lod_index() => side_node_count,
+                Some(radius) => ((radius * 2.0 / node_size).ceil() as u64 + 1).min(side_node_count),
+                None if split_distance.is_none() => side_node_count,
+                None => 0,
+            };
+
+            split_node_count += side_split_count.pow(2);
+        }
+
+        (split_node_count * 3 + 1).min(u32::MAX as u64) as u32
+    }
+
+    // Checks `render_distance` against the indirection tables the config can grow to and sizes what it needs.
+    pub fn coverage(
+        &self,
+        render_distance: u32,
+        lod_policy: &LodPolicy,
+        hysteresis: f32,
+        scale: &TerrainScale,
+    ) -> Result<TerrainCoverage, TerrainConfigError> {
+        let min_render_distance = self.min_render_distance();
+        let max_render_distance = self.max_render_distance_for(MAX_INDIRECTION_SLOT_COUNT);
+
+        if !(min_render_distance..=max_render_distance).contains(&render_distance) {
+            return Err(TerrainConfigError::RenderDistance {
+                render_distance,
+                min_render_distance,
+                max_render_distance,
+            });
+        }
+
+        let max_leaf_count = self.max_leaf_count(render_distance, lod_policy, hysteresis, scale);
+
+        Ok(TerrainCoverage {
+            render_distance,
+            indirection_slot_count: self.indirection_slot_count_for(render_distance),
+            max_leaf_count,
+            patch_capacity: max_leaf_count.min(self.atlas_patch_count.pow(2)),
+        })
+    }
+
+    // Closest render distance `coverage` accepts.
+    pub fn clamp_render_distance(&self, render_distance: u32) -> u32 {
+        render_distance.clamp(
+            self.min_render_distance(),
+            self.max_render_distance_for(MAX_INDIRECTION_SLOT_COUNT),
+        )
+    }
+
+    pub fn validate(&self, render_distance: u32) -> Result<(), TerrainConfigError> {
+        if !(1..=MAX_PATCH_LOD_COUNT).contains(&self.lod_count) {
+            return Err(TerrainConfigError::LodCount(self.lod_count));
+        }
+
+        // Stitching and morphing snap odd vertices onto even ones, the bounds pyramid halves down to one cell.
+        if self.patch_pixel_size < 2 || !self.patch_pixel_size.is_power_of_two() {
+            return Err(TerrainConfigError::PatchPixelSize(self.patch_pixel_size));
+        }
+
+        if self.atlas_patch_count == 0 || self.atlas_size() > MAX_TEXTURE_SIZE {
+            return Err(TerrainConfigError::AtlasSize(self.atlas_size()));
+        }
+
+        if !self.indirection_slot_count.is_power_of_two()
+            || self.indirection_slot_count > MAX_INDIRECTION_SLOT_COUNT
+            || self.indirection_slot_count(self.coarsest_lod_index()) < 2
+        {
+            return Err(TerrainConfigError::IndirectionSlotCount(self.indirection_slot_count));
+        }
+
+        let min_render_distance = self.min_render_distance();
+        let max_render_distance = self.max_render_distance();
+        if !(min_render_distance..=max_render_distance).contains(&render_distance) {
+            return Err(TerrainConfigError::RenderDistance {
+                render_distance,
+                min_render_distance,
+                max_render_distance,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
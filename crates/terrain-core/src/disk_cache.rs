@@ -0,0 +1,315 @@
+use std::hash::Hasher;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use crate::PatchKey;
+
+const DISK_CACHE_MAGIC: [u8; 4] = *b"TPCH";
+const DISK_CACHE_VERSION: u32 = 1;
+const DISK_CACHE_HEADER_SIZE: usize = 40;
+const DISK_CACHE_EXTENSION: &str = "patch";
+
+// FNV-1a, unlike `DefaultHasher` the result is stable across builds which matters for keys that end up on disk.
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+struct DiskPatchHeader {
+    source_hash: u64,
+    key: PatchKey,
+    pixel_count: u32,
+    checksum: u32,
+    compressed_size: u32,
+}
+
+impl DiskPatchHeader {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend(DISK_CACHE_MAGIC);
+        out.extend(DISK_CACHE_VERSION.to_le_bytes());
+        out.extend(self.source_hash.to_le_bytes());
+        out.extend(self.key.world_index.x.to_le_bytes());
+        out.extend(self.key.world_index.y.to_le_bytes());
+        out.extend(self.key.lod_index.to_le_bytes());
+        out.extend(self.pixel_count.to_le_bytes());
+        out.extend(self.checksum.to_le_bytes());
+        out.extend(self.compressed_size.to_le_bytes());
+    }
+
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let header = bytes.get(..DISK_CACHE_HEADER_SIZE)?;
+
+        let u32_at = |o: usize| u32::from_le_bytes(header[o..o + 4].try_into().unwrap());
+        let i32_at = |o: usize| i32::from_le_bytes(header[o..o + 4].try_into().unwrap());
+
+        if header[0..4] != DISK_CACHE_MAGIC || u32_at(4) != DISK_CACHE_VERSION {
+            return None;
+        }
+
+        Some(Self {
+            source_hash: u64::from_le_bytes(header[8..16].try_into().unwrap()),
+            key: PatchKey {
+                world_index: glam::IVec2::new(i32_at(16), i32_at(20)),
+                lod_index: u32_at(24),
+            },
+            pixel_count: u32_at(28),
+            checksum: u32_at(32),
+            compressed_size: u32_at(36),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct DiskCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub writes: u64,
+    pub corrupted: u64,
+    pub evicted: u64,
+}
+
+pub struct DiskPatchCache {
+    dir: PathBuf,
+    source_hash: u64,
+    max_bytes: u64,
+    total_bytes: Mutex<u64>,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+    writes: AtomicU64,
+    corrupted: AtomicU64,
+    evicted: AtomicU64,
+}
+
+impl DiskPatchCache {
+    // `source_hash` identifies the height source and its parameters, see `HeightSource::cache_key`.
+    pub fn new(dir: impl AsRef<Path>, source_hash: u64, max_bytes: u64) -> std::io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        let total_bytes = Self::cache_files(&dir)?.iter().map(|(_, len, _)| len).sum();
+
+        Ok(Self {
+            dir,
+            source_hash,
+            max_bytes,
+            total_bytes: Mutex::new(total_bytes),
+
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            corrupted: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> DiskCacheStats {
+        DiskCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            corrupted: self.corrupted.load(Ordering::Relaxed),
+            evicted: self.evicted.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        *self.total_bytes.lock().unwrap()
+    }
+
+    fn patch_path(&self, key: &PatchKey) -> PathBuf {
+        self.dir.join(format!(
+            "{:016x}_{}_{}_{}.{}",
+            self.source_hash, key.lod_index, key.world_index.x, key.world_index.y, DISK_CACHE_EXTENSION
+        ))
+    }
+
+    fn cache_files(dir: &Path) -> std::io::Result<Vec<(PathBuf, u64, std::time::SystemTime)>> {
+        let mut files = Vec::new();
+
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != DISK_CACHE_EXTENSION) {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            files.push((path, metadata.len(), metadata.modified()?));
+        }
+
+        Ok(files)
+    }
+
+    // Missing entries are misses, entries of another `pixel_size` count as corrupted and are deleted. Only failing to
+    // read an existing file is an error.
+    pub fn load(&self, key: &PatchKey, pixel_size: u32) -> std::io::Result<Option<Vec<f32>>> {
+        let path = self.patch_path(key);
+
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return match err.kind() {
+                    std::io::ErrorKind::NotFound => Ok(None),
+                    _ => Err(err),
+                };
+            }
+        };
+
+        match self.decode(key, pixel_size, &bytes) {
+            Some(height_map) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(height_map))
+            }
+            None => {
+                self.corrupted.fetch_add(1, Ordering::Relaxed);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+
+                if std::fs::remove_file(&path).is_ok() {
+                    let mut total_bytes = self.total_bytes.lock().unwrap();
+                    *total_bytes = total_bytes.saturating_sub(bytes.len() as u64);
+                }
+
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode(&self, key: &PatchKey, pixel_size: u32, bytes: &[u8]) -> Option<Vec<f32>> {
+        let header = DiskPatchHeader::read(bytes)?;
+
+        let pixel_count = (pixel_size + 1) * (pixel_size + 1);
+        if header.source_hash != self.source_hash
+            || header.key != *key
+            || header.pixel_count != pixel_count
+            || header.compressed_size as usize != bytes.len() - DISK_CACHE_HEADER_SIZE
+        {
+            return None;
+        }
+
+        // One byte more than expected is enough to tell oversized data apart, without inflating all of it.
+        let expected_len = pixel_count as usize * 4;
+        let mut shuffled = Vec::with_capacity(expected_len + 1);
+        ZlibDecoder::new(&bytes[DISK_CACHE_HEADER_SIZE..])
+            .take(expected_len as u64 + 1)
+            .read_to_end(&mut shuffled)
+            .ok()?;
+
+        if shuffled.len() != expected_len || crc32fast::hash(&shuffled) != header.checksum {
+            return None;
+        }
+
+        // Bytes are stored in planes (all first bytes, then all second bytes, ...), which compresses much better.
+        let plane_size = pixel_count as usize;
+        let height_map = (0..plane_size)
+            .map(|i| {
+                f32::from_le_bytes([
+                    shuffled[i],
+                    shuffled[plane_size + i],
+                    shuffled[plane_size * 2 + i],
+                    shuffled[plane_size * 3 + i],
+                ])
+            })
+            .collect();
+
+        Some(height_map)
+    }
+
+    pub fn store(&self, key: &PatchKey, height_map: &[f32]) -> std::io::Result<()> {
+        let plane_size = height_map.len();
+        let mut shuffled = vec![0; plane_size * 4];
+        for (i, h) in height_map.iter().enumerate() {
+            for (plane, b) in h.to_le_bytes().into_iter().enumerate() {
+                shuffled[plane * plane_size + i] = b;
+            }
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&shuffled)?;
+        let compressed = encoder.finish()?;
+
+        let mut bytes = Vec::with_capacity(DISK_CACHE_HEADER_SIZE + compressed.len());
+        DiskPatchHeader {
+            source_hash: self.source_hash,
+            key: *key,
+            pixel_count: height_map.len() as u32,
+            checksum: crc32fast::hash(&shuffled),
+            compressed_size: compressed.len() as u32,
+        }
+        .write(&mut bytes);
+        bytes.extend(compressed);
+
+        // Write to a temporary file first so readers never see half written patches.
+        let path = self.patch_path(key);
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, &bytes)?;
+        std::fs::rename(&temp_path, &path)?;
+
+        self.writes.fetch_add(1, Ordering::Relaxed);
+
+        let mut total_bytes = self.total_bytes.lock().unwrap();
+        *total_bytes += bytes.len() as u64;
+
+        if *total_bytes > self.max_bytes {
+            *total_bytes = self.trim(&path)?;
+        }
+
+        Ok(())
+    }
+
+    // Deletes the oldest files except `keep_path` until the cache is back at 90% of its size limit, returns the new
+    // size.
+    fn trim(&self, keep_path: &Path) -> std::io::Result<u64> {
+        let mut files = Self::cache_files(&self.dir)?;
+        files.sort_unstable_by_key(|(_, _, modified)| *modified);
+
+        let target_bytes = self.max_bytes / 10 * 9;
+        let mut total_bytes = files.iter().map(|(_, len, _)| len).sum::<u64>();
+
+        for (path, len, _) in files {
+            if total_bytes <= target_bytes {
+                break;
+            }
+
+            if path == keep_path {
+                continue;
+            }
+
+            if std::fs::remove_file(path).is_ok() {
+                total_bytes -= len;
+                self.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        Ok(total_bytes)
+    }
+}
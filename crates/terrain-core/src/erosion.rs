@@ -0,0 +1,378 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use glam::{IVec2, UVec2, Vec2};
+
+use crate::{HeightSource, PatchKey, StableHasher};
+
+#[derive(Clone, Debug)]
+pub struct ErosionParams {
+    pub seed: u64,
+
+    // Hydraulic erosion, every iteration spawns `rain` droplets per pixel on average.
+    pub iterations: u32,
+    pub rain: f32,
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erode_speed: f32,
+    pub deposit_speed: f32,
+    pub evaporation: f32,
+    pub inertia: f32,
+    pub gravity: f32,
+    pub droplet_lifetime: u32,
+
+    // Thermal erosion, material above `talus_slope` (height per world unit) slumps to lower neighbours.
+    pub thermal_iterations: u32,
+    pub talus_slope: f32,
+    pub thermal_rate: f32,
+}
+
+impl Default for ErosionParams {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+
+            iterations: 4,
+            rain: 0.25,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporation: 0.01,
+            inertia: 0.05,
+            gravity: 4.0,
+            droplet_lifetime: 24,
+
+            thermal_iterations: 16,
+            talus_slope: 0.004,
+            thermal_rate: 0.5,
+        }
+    }
+}
+
+impl ErosionParams {
+    // How far (in pixels) changes can travel, regions closer than this to the edge of an eroded window differ from
+    // eroding the whole map at once.
+    pub fn influence_radius(&self) -> u32 {
+        self.iterations * (self.droplet_lifetime + 1) + self.thermal_iterations
+    }
+
+    fn hash_into(&self, hasher: &mut impl Hasher) {
+        self.seed.hash(hasher);
+        self.iterations.hash(hasher);
+        self.droplet_lifetime.hash(hasher);
+        self.thermal_iterations.hash(hasher);
+
+        for v in [
+            self.rain,
+            self.sediment_capacity,
+            self.min_sediment_capacity,
+            self.erode_speed,
+            self.deposit_speed,
+            self.evaporation,
+            self.inertia,
+            self.gravity,
+            self.talus_slope,
+            self.thermal_rate,
+        ] {
+            v.to_bits().hash(hasher);
+        }
+    }
+}
+
+// SplitMix64 finalizer, used to derive droplets from their world grid position so overlapping regions spawn the same
+// droplets.
+fn hash_cell(seed: u64, iteration: u32, cell: IVec2, salt: u32) -> u64 {
+    let mut z = seed
+        ^ (iteration as u64).wrapping_mul(0x9e3779b97f4a7c15)
+        ^ (cell.x as u32 as u64).wrapping_mul(0xc2b2ae3d27d4eb4f)
+        ^ ((cell.y as u32 as u64) << 32)
+        ^ (salt as u64).wrapping_mul(0x165667b19e3779f9);
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn unit_float(hash: u64) -> f32 {
+    (hash >> 40) as f32 / (1 << 24) as f32
+}
+
+struct HeightGrid<'a> {
+    heights: &'a mut [f32],
+    deltas: Vec<f32>,
+    size: UVec2,
+}
+
+// Every iteration reads the heights of the previous one and accumulates its changes separately. That way a pixel only
+// depends on its surroundings (`ErosionParams::influence_radius`), no matter in which order or window it is eroded.
+impl HeightGrid<'_> {
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.y as u32 * self.size.x + cell.x as u32) as usize
+    }
+
+    // Droplets track their position relative to the pixel they spawned in, which keeps the float math identical in
+    // every window that contains them.
+    fn locate(&self, spawn: IVec2, pos: Vec2) -> Option<(IVec2, Vec2)> {
+        let floor = pos.floor();
+        let cell = spawn + floor.as_ivec2();
+
+        let in_bounds = cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size.as_ivec2() - 1).all();
+        in_bounds.then_some((cell, pos - floor))
+    }
+
+    fn corners(&self, cell: IVec2) -> [usize; 4] {
+        [
+            self.index(cell),
+            self.index(cell + IVec2::X),
+            self.index(cell + IVec2::Y),
+            self.index(cell + IVec2::ONE),
+        ]
+    }
+
+    fn height_and_gradient(&self, cell: IVec2, t: Vec2) -> (f32, Vec2) {
+        let [h00, h10, h01, h11] = self.corners(cell).map(|i| self.heights[i]);
+
+        let gradient = Vec2::new(
+            (h10 - h00) * (1.0 - t.y) + (h11 - h01) * t.y,
+            (h01 - h00) * (1.0 - t.x) + (h11 - h10) * t.x,
+        );
+        let height = (h00 * (1.0 - t.x) + h10 * t.x) * (1.0 - t.y) + (h01 * (1.0 - t.x) + h11 * t.x) * t.y;
+
+        (height, gradient)
+    }
+
+    // Adds `amount` to the four pixels around the position, weighted bilinearly.
+    fn splat(&mut self, cell: IVec2, t: Vec2, amount: f32) {
+        let [i00, i10, i01, i11] = self.corners(cell);
+
+        self.deltas[i00] += amount * (1.0 - t.x) * (1.0 - t.y);
+        self.deltas[i10] += amount * t.x * (1.0 - t.y);
+        self.deltas[i01] += amount * (1.0 - t.x) * t.y;
+        self.deltas[i11] += amount * t.x * t.y;
+    }
+
+    fn apply_deltas(&mut self) {
+        for (h, d) in self.heights.iter_mut().zip(&mut self.deltas) {
+            *h += *d;
+            *d = 0.0;
+        }
+    }
+
+    fn simulate_droplet(&mut self, spawn: IVec2, mut pos: Vec2, params: &ErosionParams) {
+        let mut dir = Vec2::ZERO;
+        let mut speed = 1.0_f32;
+        let mut water = 1.0_f32;
+        let mut sediment = 0.0_f32;
+
+        let Some((mut cell, mut t)) = self.locate(spawn, pos) else {
+            return;
+        };
+
+        for _ in 0..params.droplet_lifetime {
+            let (height, gradient) = self.height_and_gradient(cell, t);
+
+            dir = (dir * params.inertia - gradient * (1.0 - params.inertia)).normalize_or_zero();
+            if dir == Vec2::ZERO {
+                break;
+            }
+
+            let new_pos = pos + dir;
+            let Some((new_cell, new_t)) = self.locate(spawn, new_pos) else {
+                break;
+            };
+
+            let delta_height = self.height_and_gradient(new_cell, new_t).0 - height;
+            let capacity = (-delta_height * speed * water * params.sediment_capacity).max(params.min_sediment_capacity);
+
+            if sediment > capacity || delta_height > 0.0 {
+                // Fill up the pit we are climbing out of, or drop what we can't carry anymore.
+                let deposit = if delta_height > 0.0 {
+                    delta_height.min(sediment)
+                } else {
+                    (sediment - capacity) * params.deposit_speed
+                };
+
+                sediment -= deposit;
+                self.splat(cell, t, deposit);
+            } else {
+                let erode = ((capacity - sediment) * params.erode_speed).min(-delta_height);
+
+                sediment += erode;
+                self.splat(cell, t, -erode);
+            }
+
+            speed = (speed * speed - delta_height * params.gravity).max(0.0).sqrt();
+            water *= 1.0 - params.evaporation;
+
+            pos = new_pos;
+            (cell, t) = (new_cell, new_t);
+        }
+    }
+
+    fn hydraulic(&mut self, grid_origin: IVec2, params: &ErosionParams) {
+        for iteration in 0..params.iterations {
+            for y in 0..self.size.y as i32 - 1 {
+                for x in 0..self.size.x as i32 - 1 {
+                    let spawn = IVec2::new(x, y);
+                    let cell = grid_origin + spawn;
+                    if unit_float(hash_cell(params.seed, iteration, cell, 0)) >= params.rain {
+                        continue;
+                    }
+
+                    let offset = Vec2::new(
+                        unit_float(hash_cell(params.seed, iteration, cell, 1)),
+                        unit_float(hash_cell(params.seed, iteration, cell, 2)),
+                    );
+
+                    self.simulate_droplet(spawn, offset, params);
+                }
+            }
+
+            self.apply_deltas();
+        }
+    }
+
+    fn thermal(&mut self, pixel_world_size: f32, params: &ErosionParams) {
+        let talus = params.talus_slope * pixel_world_size;
+
+        for _ in 0..params.thermal_iterations {
+            for y in 0..self.size.y as i32 {
+                for x in 0..self.size.x as i32 {
+                    let cell = IVec2::new(x, y);
+                    let i = self.index(cell);
+
+                    for neighbor in [cell + IVec2::X, cell + IVec2::Y] {
+                        if neighbor.cmpge(self.size.as_ivec2()).any() {
+                            continue;
+                        }
+
+                        let n = self.index(neighbor);
+                        let diff = self.heights[i] - self.heights[n];
+                        if diff.abs() <= talus {
+                            continue;
+                        }
+
+                        // Each pixel exchanges with up to four neighbours, a quarter of the excess per pair keeps it
+                        // from overshooting.
+                        let amount = (diff - talus * diff.signum()) * 0.5 * params.thermal_rate * 0.25;
+                        self.deltas[i] -= amount;
+                        self.deltas[n] += amount;
+                    }
+                }
+            }
+
+            self.apply_deltas();
+        }
+    }
+}
+
+// Erodes a row major height map in place. `grid_origin` is the position of the first pixel on a global pixel grid,
+// droplets are derived from it so overlapping regions erode the same way.
+pub fn erode_height_map(
+    heights: &mut [f32],
+    size: UVec2,
+    grid_origin: IVec2,
+    pixel_world_size: f32,
+    params: &ErosionParams,
+) {
+    assert_eq!(heights.len(), (size.x * size.y) as usize);
+
+    if size.x < 2 || size.y < 2 {
+        return;
+    }
+
+    let mut grid = HeightGrid {
+        deltas: vec![0.0; heights.len()],
+        heights,
+        size,
+    };
+    grid.hydraulic(grid_origin, params);
+    grid.thermal(pixel_world_size, params);
+}
+
+// Erodes patches of another height source. Each patch is eroded together with a guard border of
+// `ErosionParams::influence_radius` pixels taken from its neighbours, so neighbouring patches match exactly.
+pub struct ErosionHeightSource {
+    source: Arc<dyn HeightSource>,
+    pub params: ErosionParams,
+}
+
+impl ErosionHeightSource {
+    pub fn new(source: Arc<dyn HeightSource>, params: ErosionParams) -> Self {
+        Self { source, params }
+    }
+}
+
+impl HeightSource for ErosionHeightSource {
+    fn sample_patch(&self, key: &PatchKey, pixel_size: u32) -> Vec<f32> {
+        let border = self.params.influence_radius();
+        // Neighbours reaching into the border on each side, more than one when the border is wider than a patch.
+        let patch_radius = border.div_ceil(pixel_size) as i32;
+
+        let atlas_pixel_size = pixel_size + 1;
+        let guard_size = pixel_size + 2 * border + 1;
+        let mut heights = vec![0.0; (guard_size * guard_size) as usize];
+
+        let lod_step = 1 << key.lod_index;
+
+        for patch_y in -patch_radius..=patch_radius {
+            for patch_x in -patch_radius..=patch_radius {
+                let neighbor = PatchKey {
+                    world_index: key.world_index + IVec2::new(patch_x, patch_y) * lod_step,
+                    lod_index: key.lod_index,
+                };
+                let patch = self.source.sample_patch(&neighbor, pixel_size);
+                let origin = IVec2::new(patch_x, patch_y) * pixel_size as i32 + border as i32;
+
+                // Every patch also covers the first row/column of its right/bottom neighbour.
+                for y in 0..atlas_pixel_size {
+                    for x in 0..atlas_pixel_size {
+                        let guard = origin + IVec2::new(x as i32, y as i32);
+                        if guard.cmplt(IVec2::ZERO).any() || guard.cmpge(IVec2::splat(guard_size as i32)).any() {
+                            continue;
+                        }
+
+                        heights[(guard.y as u32 * guard_size + guard.x as u32) as usize] =
+                            patch[(y * atlas_pixel_size + x) as usize];
+                    }
+                }
+            }
+        }
+
+        let grid_origin = key.world_index / lod_step * pixel_size as i32 - border as i32;
+        let pixel_world_size = key.world_size() as f32 / pixel_size as f32;
+
+        // Droplets are salted with the LOD so every level gets its own, independent rain.
+        let params = ErosionParams {
+            seed: self.params.seed ^ (key.lod_index as u64) << 56,
+            ..self.params.clone()
+        };
+
+        erode_height_map(
+            &mut heights,
+            UVec2::splat(guard_size),
+            grid_origin,
+            pixel_world_size,
+            &params,
+        );
+
+        (0..atlas_pixel_size * atlas_pixel_size)
+            .map(|i| {
+                let x = i % atlas_pixel_size + border;
+                let y = i / atlas_pixel_size + border;
+
+                heights[(y * guard_size + x) as usize].clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+
+    fn cache_key(&self) -> Option<u64> {
+        let mut hasher = StableHasher::new();
+        "erosion".hash(&mut hasher);
+        self.source.cache_key()?.hash(&mut hasher);
+        self.params.hash_into(&mut hasher);
+
+        Some(hasher.finish())
+    }
+}
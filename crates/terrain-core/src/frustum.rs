@@ -0,0 +1,63 @@
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+// Planes point inwards, a point is inside when `dot(plane.xyz, p) + plane.w >= 0` for all of them.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+    plane_count: usize,
+}
+
+impl Frustum {
+    // Expects D3D clip space (0 <= z <= w). With a reversed infinite projection the far plane ends up with a zero normal
+    // and is dropped, so such frusta only have five planes.
+    pub fn from_world_to_clip(world_to_clip: &Mat4) -> Self {
+        let rows = [
+            world_to_clip.row(0),
+            world_to_clip.row(1),
+            world_to_clip.row(2),
+            world_to_clip.row(3),
+        ];
+
+        let candidates = [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            rows[2],
+            rows[3] - rows[2],
+        ];
+
+        let mut planes = [Vec4::ZERO; 6];
+        let mut plane_count = 0;
+
+        for plane in candidates {
+            let normal_length = plane.xyz().length();
+            if normal_length < 1e-6 {
+                continue;
+            }
+
+            planes[plane_count] = plane / normal_length;
+            plane_count += 1;
+        }
+
+        Self { planes, plane_count }
+    }
+
+    pub fn planes(&self) -> &[Vec4] {
+        &self.planes[..self.plane_count]
+    }
+
+    pub fn contains_point(&self, p: Vec3) -> bool {
+        self.planes().iter().all(|plane| plane.xyz().dot(p) + plane.w >= 0.0)
+    }
+
+    // Conservative: boxes near frustum corners may be reported as visible.
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes().iter().all(|plane| {
+            let normal = plane.xyz();
+            let positive_vertex = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+
+            normal.dot(positive_vertex) + plane.w >= 0.0
+        })
+    }
+}
@@ -0,0 +1,403 @@
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use glam::{IVec2, UVec2, Vec3};
+use noise::utils::{NoiseMapBuilder, PlaneMapBuilder};
+use noise::{Fbm, MultiFractal, Perlin};
+
+use crate::{
+    DiskPatchCache, ErosionParams, HeightRangePyramid, HeightSource, PatchKey, erode_height_map, patch_decimation_error,
+};
+
+const PATCH_GEN_WORKER_COUNT: usize = 16;
+
+pub type PatchGenRequest = PatchKey;
+
+pub struct PatchGenResult {
+    pub request: PatchGenRequest,
+    pub height_map: Vec<f32>,
+    pub height_ranges: HeightRangePyramid,
+    pub decimation_error: f32,
+    // Loading from or storing to the disk cache failed, the patch was generated anyway.
+    pub cache_error: Option<std::io::Error>,
+}
+
+struct QueuedPatchGenRequest {
+    request: PatchGenRequest,
+    distance: f32,
+}
+
+impl QueuedPatchGenRequest {
+    // Distance in patch sizes, a coarse patch takes up as much of the screen as a finer one that is closer.
+    fn priority(&self) -> f32 {
+        self.distance / self.request.world_size() as f32
+    }
+}
+
+#[derive(Default)]
+struct PatchGenQueue {
+    // Sorted so that the most important request is at the end, see `sort`.
+    requests: Vec<QueuedPatchGenRequest>,
+    sorted: bool,
+    cancelled_count: u64,
+    generated_count: u64,
+    generation_time: Duration,
+    shutdown: bool,
+}
+
+impl PatchGenQueue {
+    // Lower priority values are processed first, coarser patches win ties so the terrain is covered sooner.
+    fn sort(&mut self) {
+        if self.sorted {
+            return;
+        }
+
+        self.requests.sort_unstable_by(|a, b| {
+            b.priority()
+                .total_cmp(&a.priority())
+                .then(a.request.lod_index.cmp(&b.request.lod_index))
+        });
+        self.sorted = true;
+    }
+
+    fn pop(&mut self) -> Option<PatchGenRequest> {
+        self.sort();
+        self.requests.pop().map(|r| r.request)
+    }
+}
+
+#[derive(Default)]
+struct PatchGenShared {
+    queue: Mutex<PatchGenQueue>,
+    request_available: Condvar,
+}
+
+pub struct PatchGenPool {
+    workers: Vec<std::thread::JoinHandle<()>>,
+    disk_cache: Option<Arc<DiskPatchCache>>,
+    shared: Arc<PatchGenShared>,
+    result_receiver: Receiver<PatchGenResult>,
+}
+
+impl Drop for PatchGenPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.request_available.notify_all();
+
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+impl PatchGenPool {
+    // Patches are generated with `pixel_size` quads per side.
+    pub fn new(height_source: Arc<dyn HeightSource>, disk_cache: Option<Arc<DiskPatchCache>>, pixel_size: u32) -> Self {
+        Self::with_worker_count(height_source, disk_cache, pixel_size, PATCH_GEN_WORKER_COUNT)
+    }
+
+    pub fn with_worker_count(
+        height_source: Arc<dyn HeightSource>,
+        disk_cache: Option<Arc<DiskPatchCache>>,
+        pixel_size: u32,
+        worker_count: usize,
+    ) -> Self {
+        let (result_sender, result_receiver) = std::sync::mpsc::channel::<PatchGenResult>();
+
+        let shared = Arc::new(PatchGenShared::default());
+
+        let workers = (0..worker_count)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                let result_sender = result_sender.clone();
+                let height_source = Arc::clone(&height_source);
+                let disk_cache = disk_cache.clone();
+
+                std::thread::Builder::new()
+                    .name(format!("tile-generator-{}", i))
+                    .spawn(move || {
+                        loop {
+                            let request = {
+                                let mut queue = shared.queue.lock().unwrap();
+                                loop {
+                                    if queue.shutdown {
+                                        break None;
+                                    }
+
+                                    if let Some(request) = queue.pop() {
+                                        break Some(request);
+                                    }
+
+                                    queue = shared.request_available.wait(queue).unwrap();
+                                }
+                            };
+
+                            let Some(request) = request else {
+                                break;
+                            };
+
+                            let (cached_height_map, mut cache_error) =
+                                match disk_cache.as_ref().map(|c| c.load(&request, pixel_size)).transpose() {
+                                    Ok(height_map) => (height_map.flatten(), None),
+                                    Err(err) => (None, Some(err)),
+                                };
+
+                            if let Some(height_map) = cached_height_map {
+                                let height_ranges = HeightRangePyramid::new(&height_map);
+                                let decimation_error = patch_decimation_error(&height_map);
+                                result_sender
+                                    .send(PatchGenResult {
+                                        request,
+                                        height_map,
+                                        height_ranges,
+                                        decimation_error,
+                                        cache_error,
+                                    })
+                                    .unwrap();
+                                continue;
+                            }
+
+                            let instant = Instant::now();
+
+                            let height_map = height_source.sample_patch(&request, pixel_size);
+                            let height_ranges = HeightRangePyramid::new(&height_map);
+                            let decimation_error = patch_decimation_error(&height_map);
+
+                            {
+                                let mut queue = shared.queue.lock().unwrap();
+                                queue.generated_count += 1;
+                                queue.generation_time += instant.elapsed();
+                            }
+
+                            if let Some(disk_cache) = &disk_cache
+                                && let Err(err) = disk_cache.store(&request, &height_map)
+                            {
+                                cache_error = Some(err);
+                            }
+
+                            result_sender
+                                .send(PatchGenResult {
+                                    request,
+                                    height_map,
+                                    height_ranges,
+                                    decimation_error,
+                                    cache_error,
+                                })
+                                .unwrap();
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        Self {
+            workers,
+            disk_cache,
+            shared,
+            result_receiver,
+        }
+    }
+
+    // `distance` is the distance from the camera to the patch in unscaled world units, see
+    // `QueuedPatchGenRequest::priority`.
+    pub fn requst_patch_generation(&self, request: PatchGenRequest, distance: f32) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.requests.push(QueuedPatchGenRequest { request, distance });
+        queue.sorted = false;
+
+        self.shared.request_available.notify_one();
+    }
+
+    // Updates the camera distance of all queued requests, returning `None` from `distance` cancels the request. Requests
+    // already picked up by a worker are not affected and will still show up in `drain_results`.
+    pub fn reprioritize_requests<F>(&self, mut distance: F) -> Vec<PatchGenRequest>
+    where
+        F: FnMut(&PatchGenRequest) -> Option<f32>,
+    {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let mut cancelled = Vec::new();
+
+        queue.requests.retain_mut(|r| match distance(&r.request) {
+            Some(d) => {
+                r.distance = d;
+                true
+            }
+            None => {
+                cancelled.push(r.request);
+                false
+            }
+        });
+
+        queue.sorted = false;
+        queue.cancelled_count += cancelled.len() as u64;
+
+        cancelled
+    }
+
+    pub fn disk_cache(&self) -> Option<&DiskPatchCache> {
+        self.disk_cache.as_deref()
+    }
+
+    pub fn queued_count(&self) -> usize {
+        self.shared.queue.lock().unwrap().requests.len()
+    }
+
+    pub fn cancelled_count(&self) -> u64 {
+        self.shared.queue.lock().unwrap().cancelled_count
+    }
+
+    // Patches generated from the height source, without the ones loaded from the disk cache.
+    pub fn generated_count(&self) -> u64 {
+        self.shared.queue.lock().unwrap().generated_count
+    }
+
+    // Summed over all workers.
+    pub fn generation_time(&self) -> Duration {
+        self.shared.queue.lock().unwrap().generation_time
+    }
+
+    pub fn drain_results(&self) -> impl Iterator<Item = PatchGenResult> + '_ {
+        self.result_receiver.try_iter()
+    }
+}
+
+pub struct MapData {
+    pub height_mips: Vec<Vec<f32>>,
+    pub normal_mips: Vec<Vec<Vec3>>,
+}
+
+pub struct MapGeneratorParams {
+    pub size: usize,
+    pub scale: f32,
+    pub octaves: usize,
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+    pub seed: u32,
+    pub erosion: Option<ErosionParams>,
+}
+
+impl MapGeneratorParams {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            scale: 7.0,
+            octaves: 6,
+            frequency: 1.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            seed: 123,
+            erosion: None,
+        }
+    }
+
+    pub fn generate(&self, terrain_size: usize) -> MapData {
+        let fbm = Fbm::<Perlin>::new(self.seed)
+            .set_octaves(self.octaves)
+            .set_frequency(self.frequency)
+            .set_lacunarity(self.lacunarity)
+            .set_persistence(self.persistence);
+
+        let height_map = PlaneMapBuilder::new(fbm)
+            .set_size(self.size, self.size)
+            .set_x_bounds(0.0, self.scale as f64)
+            .set_y_bounds(0.0, self.scale as f64)
+            .build()
+            .into_iter()
+            .map(|n| n as f32)
+            .collect::<Vec<_>>();
+
+        let min = height_map.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = height_map.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+        let mut height_map = height_map.iter().map(|n| (n - min) / (max - min)).collect::<Vec<_>>();
+
+        if let Some(erosion) = &self.erosion {
+            erode_height_map(
+                &mut height_map,
+                UVec2::splat(self.size as u32),
+                IVec2::ZERO,
+                terrain_size as f32 / self.size as f32,
+                erosion,
+            );
+        }
+
+        let normal_map = self.generate_normals(height_map.as_slice(), terrain_size);
+
+        MapData {
+            height_mips: self.generate_mips(height_map, |s0, s1, s2, s3| (s0 + s1 + s2 + s3) * 0.25),
+            normal_mips: self.generate_mips(normal_map, |s0, s1, s2, s3| {
+                let n = (s0 + s1 + s2 + s3) * 0.25;
+
+                if n.length_squared() > 1e-8 {
+                    n.normalize()
+                } else {
+                    Vec3::Y
+                }
+            }),
+        }
+    }
+
+    fn generate_mips<T, F>(&self, data: Vec<T>, mut downsample: F) -> Vec<Vec<T>>
+    where
+        T: Copy,
+        F: FnMut(T, T, T, T) -> T,
+    {
+        let mut mips = vec![data];
+        let mut current_size = self.size;
+
+        while current_size > 1 {
+            current_size /= 2;
+
+            let prev = mips.last().unwrap();
+            let mip = (0..current_size * current_size)
+                .map(|i| {
+                    let x = (i % current_size) * 2;
+                    let y = (i / current_size) * 2;
+                    let prev_size = current_size * 2;
+
+                    let s00 = prev[y * prev_size + x];
+                    let s10 = prev[y * prev_size + x + 1];
+                    let s01 = prev[(y + 1) * prev_size + x];
+                    let s11 = prev[(y + 1) * prev_size + x + 1];
+
+                    downsample(s00, s10, s01, s11)
+                })
+                .collect();
+
+            mips.push(mip);
+        }
+
+        mips
+    }
+
+    fn generate_normals(&self, height_map: &[f32], terrain_size: usize) -> Vec<Vec3> {
+        let world_scale = terrain_size as f32 / self.size as f32;
+        let mut normals = vec![Vec3::ZERO; self.size * self.size];
+
+        for z in 0..self.size {
+            for x in 0..self.size {
+                let sample = |sx: i32, sz: i32| -> f32 {
+                    let cx = sx.clamp(0, self.size as i32 - 1) as usize;
+                    let cz = sz.clamp(0, self.size as i32 - 1) as usize;
+
+                    height_map[cz * self.size + cx]
+                };
+
+                let hl = sample(x as i32 - 1, z as i32);
+                let hr = sample(x as i32 + 1, z as i32);
+                let hb = sample(x as i32, z as i32 - 1);
+                let ht = sample(x as i32, z as i32 + 1);
+
+                let height_scale = 10.0;
+                let dx = (hl - hr) * height_scale;
+                let dz = (hb - ht) * height_scale;
+
+                normals[z * self.size + x] = glam::Vec3::new(dx, 2.0 * world_scale, dz).normalize();
+            }
+        }
+
+        normals
+    }
+}
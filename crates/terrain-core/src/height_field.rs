@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use glam::{UVec2, Vec2, Vec3};
+
+use crate::{HeightRangePyramid, PATCH_LOD_COUNT, PATCH_WORLD_SIZE, PatchKey, height_map_pixel_size};
+
+// Mirrors the scaling applied by `ProcessVertex` in terrain.hlsl.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainScale {
+    pub world_scale: f32,
+    pub height_scale: f32,
+}
+
+impl TerrainScale {
+    pub fn new() -> Self {
+        Self {
+            world_scale: 1.0,
+            height_scale: 100.0,
+        }
+    }
+}
+
+impl Default for TerrainScale {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainSample<T> {
+    pub value: T,
+    pub lod_index: u32,
+    // Set when the patch of the rendered leaf is not available and a coarser patch answered instead.
+    pub is_fallback: bool,
+}
+
+struct HeightFieldPatch {
+    heights: Arc<[f32]>,
+    height_ranges: HeightRangePyramid,
+}
+
+// CPU copies of the height maps that were sent to the atlas, used for gameplay queries.
+pub struct TerrainHeightField {
+    lod_count: u32,
+    patches: HashMap<PatchKey, HeightFieldPatch>,
+    leaf_patches: HashSet<PatchKey>,
+    // Leafs and all their ancestors up to the coarsest LOD.
+    tree_nodes: HashSet<PatchKey>,
+}
+
+impl TerrainHeightField {
+    pub fn new(lod_count: u32) -> Self {
+        Self {
+            lod_count,
+            patches: HashMap::new(),
+            leaf_patches: HashSet::new(),
+            tree_nodes: HashSet::new(),
+        }
+    }
+
+    pub fn lod_count(&self) -> u32 {
+        self.lod_count
+    }
+
+    pub fn patch_count(&self) -> usize {
+        self.patches.len()
+    }
+
+    pub fn insert(&mut self, key: PatchKey, height_map: Arc<[f32]>, height_ranges: HeightRangePyramid) {
+        self.patches.insert(
+            key,
+            HeightFieldPatch {
+                heights: height_map,
+                height_ranges,
+            },
+        );
+    }
+
+    pub fn patch(&self, key: &PatchKey) -> Option<&[f32]> {
+        self.patches.get(key).map(|p| &*p.heights)
+    }
+
+    pub fn patch_height_ranges(&self, key: &PatchKey) -> Option<&HeightRangePyramid> {
+        self.patches.get(key).map(|p| &p.height_ranges)
+    }
+
+    pub fn remove(&mut self, key: &PatchKey) {
+        self.patches.remove(key);
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&PatchKey) -> bool) {
+        self.patches.retain(|key, _| f(key));
+    }
+
+    pub fn set_leaf_patches(&mut self, leaf_patches: &[PatchKey]) {
+        self.leaf_patches = leaf_patches.iter().copied().collect();
+
+        self.tree_nodes.clear();
+        for leaf in leaf_patches {
+            for lod_index in leaf.lod_index..self.lod_count {
+                self.tree_nodes.insert(PatchKey {
+                    world_index: (leaf.world_index >> lod_index) << lod_index,
+                    lod_index,
+                });
+            }
+        }
+    }
+
+    pub fn is_leaf(&self, key: &PatchKey) -> bool {
+        self.leaf_patches.contains(key)
+    }
+
+    pub fn is_tree_node(&self, key: &PatchKey) -> bool {
+        self.tree_nodes.contains(key)
+    }
+
+    pub fn root_nodes(&self) -> impl Iterator<Item = &PatchKey> {
+        self.tree_nodes.iter().filter(|key| key.lod_index == self.lod_count - 1)
+    }
+
+    // Finest patch with height data covering `pos`, in unscaled world units.
+    fn find_patch(&self, pos: Vec2) -> Option<(PatchKey, &[f32])> {
+        let world_index = (pos / PATCH_WORLD_SIZE as f32).floor().as_ivec2();
+
+        (0..self.lod_count).find_map(|lod_index| {
+            let key = PatchKey {
+                world_index: (world_index >> lod_index) << lod_index,
+                lod_index,
+            };
+
+            self.patches.get(&key).map(|p| (key, &*p.heights))
+        })
+    }
+
+    fn is_fallback(&self, pos: Vec2, lod_index: u32) -> bool {
+        let world_index = (pos / PATCH_WORLD_SIZE as f32).floor().as_ivec2();
+
+        let leaf_lod_index = (0..self.lod_count).find(|&lod_index| {
+            self.leaf_patches.contains(&PatchKey {
+                world_index: (world_index >> lod_index) << lod_index,
+                lod_index,
+            })
+        });
+
+        leaf_lod_index.is_none_or(|leaf_lod_index| lod_index > leaf_lod_index)
+    }
+
+    // Bilinear sample of one patch, `pixel` is clamped to the patch.
+    fn sample_patch(heights: &[f32], pixel: Vec2) -> f32 {
+        let pixel_size = height_map_pixel_size(heights);
+        let pixel = pixel.clamp(Vec2::ZERO, Vec2::splat(pixel_size as f32));
+
+        let p0 = pixel.floor().as_uvec2().min(UVec2::splat(pixel_size - 1));
+        let t = pixel - p0.as_vec2();
+
+        let h = |x: u32, y: u32| heights[(y * (pixel_size + 1) + x) as usize];
+
+        let top = h(p0.x, p0.y) * (1.0 - t.x) + h(p0.x + 1, p0.y) * t.x;
+        let bottom = h(p0.x, p0.y + 1) * (1.0 - t.x) + h(p0.x + 1, p0.y + 1) * t.x;
+
+        top * (1.0 - t.y) + bottom * t.y
+    }
+
+    fn pixel_world_size(key: &PatchKey, heights: &[f32]) -> f32 {
+        key.world_size() as f32 / height_map_pixel_size(heights) as f32
+    }
+
+    pub fn height_at(&self, pos: Vec2, scale: &TerrainScale) -> Option<TerrainSample<f32>> {
+        let pos = pos / scale.world_scale;
+        let (key, heights) = self.find_patch(pos)?;
+
+        let pixel = (pos - key.world_pos().as_vec2()) / Self::pixel_world_size(&key, heights);
+
+        Some(TerrainSample {
+            value: Self::sample_patch(heights, pixel) * scale.height_scale,
+            lod_index: key.lod_index,
+            is_fallback: self.is_fallback(pos, key.lod_index),
+        })
+    }
+
+    // Central differences one texel apart on the answering patch, one sided on its borders.
+    pub fn normal_at(&self, pos: Vec2, scale: &TerrainScale) -> Option<TerrainSample<Vec3>> {
+        let pos = pos / scale.world_scale;
+        let (key, heights) = self.find_patch(pos)?;
+
+        let pixel_world_size = Self::pixel_world_size(&key, heights);
+        let pixel = (pos - key.world_pos().as_vec2()) / pixel_world_size;
+        let max_pixel = height_map_pixel_size(heights) as f32;
+
+        let gradient = |axis: Vec2| {
+            let p0 = (pixel - axis).clamp(Vec2::ZERO, Vec2::splat(max_pixel));
+            let p1 = (pixel + axis).clamp(Vec2::ZERO, Vec2::splat(max_pixel));
+
+            let dh = (Self::sample_patch(heights, p1) - Self::sample_patch(heights, p0)) * scale.height_scale;
+            let dx = (p1 - p0).dot(axis) * pixel_world_size * scale.world_scale;
+
+            dh / dx
+        };
+
+        let dhdx = gradient(Vec2::X);
+        let dhdz = gradient(Vec2::Y);
+
+        Some(TerrainSample {
+            value: Vec3::new(-dhdx, 1.0, -dhdz).normalize(),
+            lod_index: key.lod_index,
+            is_fallback: self.is_fallback(pos, key.lod_index),
+        })
+    }
+
+    // Angle between the surface and the horizontal plane, in radians.
+    pub fn slope_at(&self, pos: Vec2, scale: &TerrainScale) -> Option<TerrainSample<f32>> {
+        let normal = self.normal_at(pos, scale)?;
+
+        Some(TerrainSample {
+            value: normal.value.y.clamp(-1.0, 1.0).acos(),
+            lod_index: normal.lod_index,
+            is_fallback: normal.is_fallback,
+        })
+    }
+}
+
+impl Default for TerrainHeightField {
+    fn default() -> Self {
+        Self::new(PATCH_LOD_COUNT)
+    }
+}
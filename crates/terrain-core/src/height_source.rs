@@ -0,0 +1,220 @@
+use std::hash::{Hash, Hasher};
+
+use noise::utils::{NoiseMapBuilder, PlaneMapBuilder};
+use noise::{
+    Add, Billow, Fbm, MultiFractal, Multiply, NoiseFn, Perlin, RidgedMulti, ScaleBias, Seedable, Terrace, Turbulence,
+};
+
+use crate::{PatchKey, StableHasher};
+
+// Returns `(pixel_size + 1)`² normalized heights covering the patch, including the one pixel overlap to the right and
+// bottom neighbours.
+pub trait HeightSource: Send + Sync {
+    fn sample_patch(&self, key: &PatchKey, pixel_size: u32) -> Vec<f32>;
+
+    // Stable hash of everything that affects the output, sources without one are never cached on disk.
+    fn cache_key(&self) -> Option<u64> {
+        None
+    }
+}
+
+fn sample_noise_patch<N: NoiseFn<f64, 3>>(
+    noise: &N,
+    key: &PatchKey,
+    pixel_size: u32,
+    noise_scale: f64,
+    world_scale: f64,
+) -> Vec<f32> {
+    let fbm_pos = key.world_pos().as_dvec2() / world_scale * noise_scale;
+    let fbm_size = key.world_size() as f64 / world_scale * noise_scale;
+    let fbm_pixel_size = key.world_size() as f64 / pixel_size as f64 / world_scale * noise_scale;
+
+    PlaneMapBuilder::new(noise)
+        .set_size(pixel_size as usize + 1, pixel_size as usize + 1)
+        .set_x_bounds(fbm_pos.x, fbm_pos.x + fbm_size + fbm_pixel_size) // pixel overlap
+        .set_y_bounds(fbm_pos.y, fbm_pos.y + fbm_size + fbm_pixel_size) // pixel overlap
+        .build()
+        .into_iter()
+        .map(|n| (n as f32).clamp(0.0, 1.0))
+        .collect()
+}
+
+pub struct FbmHeightSource {
+    seed: u32,
+    fbm: ScaleBias<f64, Fbm<Perlin>, 3>,
+    pub noise_scale: f64,
+    pub world_scale: f64,
+}
+
+impl FbmHeightSource {
+    pub fn new(seed: u32) -> Self {
+        let fbm = Fbm::<Perlin>::new(seed)
+            .set_octaves(8)
+            .set_frequency(1.0)
+            .set_lacunarity(2.0)
+            .set_persistence(0.5);
+
+        Self {
+            seed,
+            fbm: ScaleBias::new(fbm).set_scale(1.5).set_bias(0.3),
+            noise_scale: 4.0,
+            world_scale: 2048.0,
+        }
+    }
+}
+
+impl Default for FbmHeightSource {
+    fn default() -> Self {
+        Self::new(123)
+    }
+}
+
+impl HeightSource for FbmHeightSource {
+    fn sample_patch(&self, key: &PatchKey, pixel_size: u32) -> Vec<f32> {
+        sample_noise_patch(&self.fbm, key, pixel_size, self.noise_scale, self.world_scale)
+    }
+
+    fn cache_key(&self) -> Option<u64> {
+        let mut hasher = StableHasher::new();
+        "fbm".hash(&mut hasher);
+        self.seed.hash(&mut hasher);
+        self.noise_scale.to_bits().hash(&mut hasher);
+        self.world_scale.to_bits().hash(&mut hasher);
+
+        Some(hasher.finish())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FractalParams {
+    pub seed: u32,
+    pub octaves: usize,
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+}
+
+impl Default for FractalParams {
+    fn default() -> Self {
+        Self {
+            seed: 123,
+            octaves: 8,
+            frequency: 1.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum NoiseNode {
+    Fbm(FractalParams),
+    RidgedMulti(FractalParams),
+    Billow(FractalParams),
+    DomainWarp {
+        source: Box<NoiseNode>,
+        seed: u32,
+        frequency: f64,
+        strength: f64,
+    },
+    Terraces {
+        source: Box<NoiseNode>,
+        control_points: Vec<f64>,
+    },
+    ScaleBias {
+        source: Box<NoiseNode>,
+        scale: f64,
+        bias: f64,
+    },
+    Add(Box<NoiseNode>, Box<NoiseNode>),
+    Multiply(Box<NoiseNode>, Box<NoiseNode>),
+}
+
+type BoxedNoiseFn = Box<dyn NoiseFn<f64, 3> + Send + Sync>;
+
+impl NoiseNode {
+    fn build(&self) -> BoxedNoiseFn {
+        match self {
+            NoiseNode::Fbm(p) => Box::new(
+                Fbm::<Perlin>::new(p.seed)
+                    .set_octaves(p.octaves)
+                    .set_frequency(p.frequency)
+                    .set_lacunarity(p.lacunarity)
+                    .set_persistence(p.persistence),
+            ),
+            NoiseNode::RidgedMulti(p) => Box::new(
+                RidgedMulti::<Perlin>::new(p.seed)
+                    .set_octaves(p.octaves)
+                    .set_frequency(p.frequency)
+                    .set_lacunarity(p.lacunarity)
+                    .set_persistence(p.persistence),
+            ),
+            NoiseNode::Billow(p) => Box::new(
+                Billow::<Perlin>::new(p.seed)
+                    .set_octaves(p.octaves)
+                    .set_frequency(p.frequency)
+                    .set_lacunarity(p.lacunarity)
+                    .set_persistence(p.persistence),
+            ),
+            NoiseNode::DomainWarp {
+                source,
+                seed,
+                frequency,
+                strength,
+            } => Box::new(
+                Turbulence::<_, Perlin>::new(source.build())
+                    .set_seed(*seed)
+                    .set_frequency(*frequency)
+                    .set_power(*strength),
+            ),
+            NoiseNode::Terraces { source, control_points } => Box::new(
+                control_points
+                    .iter()
+                    .fold(Terrace::new(source.build()), |terrace, &p| terrace.add_control_point(p)),
+            ),
+            NoiseNode::ScaleBias { source, scale, bias } => {
+                Box::new(ScaleBias::new(source.build()).set_scale(*scale).set_bias(*bias))
+            }
+            NoiseNode::Add(a, b) => Box::new(Add::new(a.build(), b.build())),
+            NoiseNode::Multiply(a, b) => Box::new(Multiply::new(a.build(), b.build())),
+        }
+    }
+}
+
+pub struct NoiseGraphHeightSource {
+    noise: BoxedNoiseFn,
+    graph_hash: u64,
+    pub noise_scale: f64,
+    pub world_scale: f64,
+}
+
+impl NoiseGraphHeightSource {
+    pub fn new(graph: &NoiseNode) -> Self {
+        Self {
+            noise: graph.build(),
+            graph_hash: {
+                let mut hasher = StableHasher::new();
+                format!("{:?}", graph).hash(&mut hasher);
+                hasher.finish()
+            },
+            noise_scale: 4.0,
+            world_scale: 2048.0,
+        }
+    }
+}
+
+impl HeightSource for NoiseGraphHeightSource {
+    fn sample_patch(&self, key: &PatchKey, pixel_size: u32) -> Vec<f32> {
+        sample_noise_patch(&self.noise, key, pixel_size, self.noise_scale, self.world_scale)
+    }
+
+    fn cache_key(&self) -> Option<u64> {
+        let mut hasher = StableHasher::new();
+        "noise_graph".hash(&mut hasher);
+        self.graph_hash.hash(&mut hasher);
+        self.noise_scale.to_bits().hash(&mut hasher);
+        self.world_scale.to_bits().hash(&mut hasher);
+
+        Some(hasher.finish())
+    }
+}
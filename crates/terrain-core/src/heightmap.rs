@@ -0,0 +1,500 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use glam::{DVec2, UVec2, Vec2};
+
+use crate::{HeightSource, PATCH_PIXEL_SIZE, PATCH_WORLD_SIZE, PatchKey};
+
+fn invalid_data(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+// Number of samples of a `size` heightmap, sizes from files can be empty or too large to index.
+fn sample_count(size: UVec2) -> Result<usize> {
+    if size.min_element() == 0 {
+        return Err(invalid_data(format!("empty {}x{} heightmap", size.x, size.y)));
+    }
+
+    (size.x as usize)
+        .checked_mul(size.y as usize)
+        .ok_or_else(|| invalid_data(format!("{}x{} heightmap is too large", size.x, size.y)))
+}
+
+struct HeightmapMip {
+    size: UVec2,
+    heights: Vec<f32>,
+}
+
+impl HeightmapMip {
+    fn texel(&self, x: i32, y: i32) -> f32 {
+        let x = x.clamp(0, self.size.x as i32 - 1) as usize;
+        let y = y.clamp(0, self.size.y as i32 - 1) as usize;
+
+        self.heights[y * self.size.x as usize + x]
+    }
+
+    fn downsample(&self) -> Self {
+        let size = (self.size + 1) / 2;
+
+        let heights = (0..size.x as usize * size.y as usize)
+            .map(|i| {
+                let x = (i % size.x as usize) as i32 * 2;
+                let y = (i / size.x as usize) as i32 * 2;
+
+                (self.texel(x, y) + self.texel(x + 1, y) + self.texel(x, y + 1) + self.texel(x + 1, y + 1)) * 0.25
+            })
+            .collect();
+
+        Self { size, heights }
+    }
+
+    fn sample(&self, pixel_pos: Vec2) -> f32 {
+        let base = pixel_pos.floor();
+        let t = pixel_pos - base;
+        let (x, y) = (base.x as i32, base.y as i32);
+
+        let top = self.texel(x, y) * (1.0 - t.x) + self.texel(x + 1, y) * t.x;
+        let bottom = self.texel(x, y + 1) * (1.0 - t.x) + self.texel(x + 1, y + 1) * t.x;
+
+        top * (1.0 - t.y) + bottom * t.y
+    }
+}
+
+pub struct HeightmapSource {
+    mips: Vec<HeightmapMip>,
+    pub world_origin: Vec2,
+    pub world_pixel_size: f32,
+}
+
+impl HeightmapSource {
+    // The heightmap is centered on the world origin and uses the same pixel density as the finest LOD of the default
+    // `TerrainConfig`.
+    pub fn new(size: UVec2, heights: Vec<f32>) -> Self {
+        assert!(size.min_element() > 0, "empty heightmap");
+        assert_eq!(heights.len(), size.x as usize * size.y as usize);
+
+        let mut mips = vec![HeightmapMip { size, heights }];
+        while mips.last().unwrap().size.max_element() > 1 {
+            let mip = mips.last().unwrap().downsample();
+            mips.push(mip);
+        }
+
+        let world_pixel_size = PATCH_WORLD_SIZE as f32 / PATCH_PIXEL_SIZE as f32;
+
+        Self {
+            mips,
+            world_origin: -size.as_vec2() * world_pixel_size * 0.5,
+            world_pixel_size,
+        }
+    }
+
+    // Picks the loader from the file extension, RAW files need a sidecar, see `raw_sidecar_size`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "png" => Self::from_png_file(path),
+            "raw" | "r16" => Self::from_raw_file(path, raw_sidecar_size(path)?),
+            "tif" | "tiff" => Self::from_geotiff_file(path),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("unknown heightmap format: {}", path.display()),
+            )),
+        }
+    }
+
+    // Little endian 16-bit samples, row by row.
+    pub fn from_raw_file(path: impl AsRef<Path>, size: UVec2) -> Result<Self> {
+        let expected_len = sample_count(size)?
+            .checked_mul(2)
+            .ok_or_else(|| invalid_data(format!("{}x{} heightmap is too large", size.x, size.y)))?;
+
+        let bytes = std::fs::read(path)?;
+        if bytes.len() != expected_len {
+            return Err(invalid_data(format!(
+                "expected {} bytes for {}x{} heightmap, got {}",
+                expected_len,
+                size.x,
+                size.y,
+                bytes.len()
+            )));
+        }
+
+        let heights = bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as f32 / u16::MAX as f32)
+            .collect();
+
+        Ok(Self::new(size, heights))
+    }
+
+    pub fn from_png_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(png::Transformations::IDENTITY);
+
+        let mut reader = decoder.read_info().map_err(Error::other)?;
+        let mut buffer = vec![
+            0;
+            reader
+                .output_buffer_size()
+                .ok_or_else(|| invalid_data("png too large"))?
+        ];
+        let info = reader.next_frame(&mut buffer).map_err(Error::other)?;
+
+        if info.color_type != png::ColorType::Grayscale {
+            return Err(invalid_data(format!(
+                "expected grayscale png, got {:?}",
+                info.color_type
+            )));
+        }
+
+        let size = UVec2::new(info.width, info.height);
+        sample_count(size)?;
+
+        let heights = match info.bit_depth {
+            png::BitDepth::Sixteen => buffer
+                .chunks_exact(info.line_size)
+                .take(info.height as usize)
+                .flat_map(|line| line[..info.width as usize * 2].chunks_exact(2))
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / u16::MAX as f32)
+                .collect(),
+            png::BitDepth::Eight => buffer
+                .chunks_exact(info.line_size)
+                .take(info.height as usize)
+                .flat_map(|line| &line[..info.width as usize])
+                .map(|&b| b as f32 / u8::MAX as f32)
+                .collect(),
+            bit_depth => return Err(invalid_data(format!("unsupported png bit depth: {:?}", bit_depth))),
+        };
+
+        Ok(Self::new(size, heights))
+    }
+
+    // Single band, uncompressed, stripped or tiled GeoTIFF. Signed and float elevations are normalized to their
+    // min/max range, the geo referencing tags are ignored.
+    pub fn from_geotiff_file(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let (size, heights) = decode_geotiff(&bytes)?;
+
+        Ok(Self::new(size, heights))
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.mips[0].size
+    }
+
+    pub fn mip_count(&self) -> usize {
+        self.mips.len()
+    }
+
+    // Samples `mip_index` with texel centers aligned to the 2x2 footprint they were averaged from.
+    pub fn sample(&self, world_pos: DVec2, mip_index: usize) -> f32 {
+        let mip_index = mip_index.min(self.mips.len() - 1);
+        let mip_scale = (1 << mip_index) as f32;
+
+        let pixel_pos = (world_pos.as_vec2() - self.world_origin) / self.world_pixel_size;
+        let mip_pixel_pos = (pixel_pos + 0.5) / mip_scale - 0.5;
+
+        self.mips[mip_index].sample(mip_pixel_pos)
+    }
+}
+
+impl HeightSource for HeightmapSource {
+    fn sample_patch(&self, key: &PatchKey, pixel_size: u32) -> Vec<f32> {
+        let world_pos = key.world_pos().as_dvec2();
+        let world_pixel_size = key.world_size() as f64 / pixel_size as f64;
+        let atlas_pixel_size = pixel_size + 1;
+
+        let mip_index = (world_pixel_size as f32 / self.world_pixel_size)
+            .log2()
+            .floor()
+            .max(0.0) as usize;
+
+        (0..atlas_pixel_size * atlas_pixel_size)
+            .map(|i| {
+                let pixel = DVec2::new((i % atlas_pixel_size) as f64, (i / atlas_pixel_size) as f64);
+                self.sample(world_pos + pixel * world_pixel_size, mip_index)
+            })
+            .collect()
+    }
+}
+
+// `<file>.size` next to the RAW file containing "<width> <height>" (or "<width>x<height>"). Without a sidecar the
+// heightmap is assumed to be square.
+pub fn raw_sidecar_size(path: &Path) -> Result<UVec2> {
+    let mut sidecar_path = PathBuf::from(path);
+    sidecar_path.as_mut_os_string().push(".size");
+
+    let Ok(sidecar) = std::fs::read_to_string(&sidecar_path) else {
+        let sample_count = std::fs::metadata(path)?.len() / 2;
+        let side = (sample_count as f64).sqrt() as u32;
+
+        if side as u64 * side as u64 != sample_count {
+            return Err(invalid_data(format!(
+                "{} is not square and has no sidecar size file",
+                path.display()
+            )));
+        }
+
+        return Ok(UVec2::splat(side));
+    };
+
+    let dims = sidecar
+        .split(|c: char| c.is_whitespace() || c == 'x' || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<u32>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| invalid_data(format!("{}: {}", sidecar_path.display(), e)))?;
+
+    let [width, height] = dims[..] else {
+        return Err(invalid_data(format!(
+            "{}: expected width and height",
+            sidecar_path.display()
+        )));
+    };
+
+    Ok(UVec2::new(width, height))
+}
+
+const TIFF_TAG_IMAGE_WIDTH: u16 = 256;
+const TIFF_TAG_IMAGE_LENGTH: u16 = 257;
+const TIFF_TAG_BITS_PER_SAMPLE: u16 = 258;
+const TIFF_TAG_COMPRESSION: u16 = 259;
+const TIFF_TAG_STRIP_OFFSETS: u16 = 273;
+const TIFF_TAG_SAMPLES_PER_PIXEL: u16 = 277;
+const TIFF_TAG_ROWS_PER_STRIP: u16 = 278;
+const TIFF_TAG_TILE_WIDTH: u16 = 322;
+const TIFF_TAG_TILE_LENGTH: u16 = 323;
+const TIFF_TAG_TILE_OFFSETS: u16 = 324;
+const TIFF_TAG_SAMPLE_FORMAT: u16 = 339;
+const TIFF_TAG_GDAL_NODATA: u16 = 42113;
+
+struct TiffReader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl TiffReader<'_> {
+    fn slice(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        self.bytes
+            .get(offset..offset + len)
+            .ok_or_else(|| invalid_data("tiff offset out of bounds"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let b = self.slice(offset, 2)?.try_into().unwrap();
+        Ok(if self.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let b = self.slice(offset, 4)?.try_into().unwrap();
+        Ok(if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn sample(&self, offset: usize, bits: u32, format: u16) -> Result<f32> {
+        Ok(match (format, bits) {
+            (1, 8) => self.slice(offset, 1)?[0] as f32,
+            (2, 8) => self.slice(offset, 1)?[0] as i8 as f32,
+            (1, 16) => self.u16(offset)? as f32,
+            (2, 16) => self.u16(offset)? as i16 as f32,
+            (1, 32) => self.u32(offset)? as f32,
+            (2, 32) => self.u32(offset)? as i32 as f32,
+            (3, 32) => f32::from_bits(self.u32(offset)?),
+            _ => {
+                return Err(invalid_data(format!(
+                    "unsupported tiff sample: format={} bits={}",
+                    format, bits
+                )));
+            }
+        })
+    }
+}
+
+struct TiffEntry {
+    field_type: u16,
+    count: u32,
+    value_offset: usize,
+}
+
+impl TiffEntry {
+    fn values(&self, reader: &TiffReader) -> Result<Vec<u32>> {
+        let value_size = match self.field_type {
+            1 => 1,
+            3 => 2,
+            4 => 4,
+            t => return Err(invalid_data(format!("unsupported tiff field type {}", t))),
+        };
+
+        let offset = if value_size * self.count as usize <= 4 {
+            self.value_offset
+        } else {
+            reader.u32(self.value_offset)? as usize
+        };
+
+        (0..self.count as usize)
+            .map(|i| {
+                let value_offset = offset + i * value_size;
+                match value_size {
+                    1 => Ok(reader.slice(value_offset, 1)?[0] as u32),
+                    2 => reader.u16(value_offset).map(u32::from),
+                    _ => reader.u32(value_offset),
+                }
+            })
+            .collect()
+    }
+
+    fn ascii(&self, reader: &TiffReader) -> Result<String> {
+        let offset = if self.count <= 4 {
+            self.value_offset
+        } else {
+            reader.u32(self.value_offset)? as usize
+        };
+
+        let bytes = reader.slice(offset, self.count as usize)?;
+        Ok(String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string())
+    }
+}
+
+fn decode_geotiff(bytes: &[u8]) -> Result<(UVec2, Vec<f32>)> {
+    let little_endian = match bytes.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return Err(invalid_data("not a tiff file")),
+    };
+
+    let reader = TiffReader { bytes, little_endian };
+    if reader.u16(2)? != 42 {
+        return Err(invalid_data("unsupported tiff version (BigTIFF?)"));
+    }
+
+    let ifd_offset = reader.u32(4)? as usize;
+    let entry_count = reader.u16(ifd_offset)? as usize;
+
+    let mut entries = std::collections::HashMap::new();
+    for i in 0..entry_count {
+        let entry_offset = ifd_offset + 2 + i * 12;
+        entries.insert(
+            reader.u16(entry_offset)?,
+            TiffEntry {
+                field_type: reader.u16(entry_offset + 2)?,
+                count: reader.u32(entry_offset + 4)?,
+                value_offset: entry_offset + 8,
+            },
+        );
+    }
+
+    let values = |tag: u16| -> Result<Option<Vec<u32>>> { entries.get(&tag).map(|e| e.values(&reader)).transpose() };
+    let value = |tag: u16| -> Result<Option<u32>> { Ok(values(tag)?.and_then(|v| v.first().copied())) };
+    let required =
+        |tag: u16| -> Result<u32> { value(tag)?.ok_or_else(|| invalid_data(format!("missing tiff tag {}", tag))) };
+
+    let width = required(TIFF_TAG_IMAGE_WIDTH)?;
+    let height = required(TIFF_TAG_IMAGE_LENGTH)?;
+    let bits = value(TIFF_TAG_BITS_PER_SAMPLE)?.unwrap_or(1);
+    let format = value(TIFF_TAG_SAMPLE_FORMAT)?.unwrap_or(1) as u16;
+
+    if value(TIFF_TAG_COMPRESSION)?.unwrap_or(1) != 1 {
+        return Err(invalid_data("compressed tiff files are not supported"));
+    }
+    if value(TIFF_TAG_SAMPLES_PER_PIXEL)?.unwrap_or(1) != 1 {
+        return Err(invalid_data("only single band tiff files are supported"));
+    }
+
+    if !matches!(bits, 8 | 16 | 32) {
+        return Err(invalid_data(format!("unsupported tiff bits per sample: {}", bits)));
+    }
+
+    // Uncompressed files hold every sample, which also bounds the allocation below by the file size.
+    let sample_size = bits as usize / 8;
+    let sample_count = sample_count(UVec2::new(width, height))?;
+    if sample_count.saturating_mul(sample_size) > bytes.len() {
+        return Err(invalid_data(format!(
+            "tiff is too small for a {}x{} image",
+            width, height
+        )));
+    }
+
+    let mut samples = vec![0.0_f32; sample_count];
+
+    // Copies a block of `block_size` samples stored row by row at `offset` to `pos`, clipped to the image.
+    let mut copy_block = |offset: usize, pos: UVec2, block_size: UVec2| -> Result<()> {
+        for y in 0..block_size.y.min(height.saturating_sub(pos.y)) {
+            for x in 0..block_size.x.min(width.saturating_sub(pos.x)) {
+                let sample_offset = offset + (y as usize * block_size.x as usize + x as usize) * sample_size;
+                let sample_index = (pos.y + y) as usize * width as usize + (pos.x + x) as usize;
+                samples[sample_index] = reader.sample(sample_offset, bits, format)?;
+            }
+        }
+
+        Ok(())
+    };
+
+    if let Some(tile_offsets) = values(TIFF_TAG_TILE_OFFSETS)? {
+        let tile_size = UVec2::new(required(TIFF_TAG_TILE_WIDTH)?, required(TIFF_TAG_TILE_LENGTH)?);
+        if tile_size.min_element() == 0 {
+            return Err(invalid_data("empty tiff tiles"));
+        }
+        let tiles_across = width.div_ceil(tile_size.x);
+
+        for (i, &offset) in tile_offsets.iter().enumerate() {
+            let tile_index = UVec2::new(i as u32 % tiles_across, i as u32 / tiles_across);
+            copy_block(offset as usize, tile_index.saturating_mul(tile_size), tile_size)?;
+        }
+    } else {
+        let strip_offsets =
+            values(TIFF_TAG_STRIP_OFFSETS)?.ok_or_else(|| invalid_data("missing tiff strip offsets"))?;
+        let rows_per_strip = value(TIFF_TAG_ROWS_PER_STRIP)?.unwrap_or(height).min(height);
+
+        for (i, &offset) in strip_offsets.iter().enumerate() {
+            let pos = UVec2::new(0, (i as u32).saturating_mul(rows_per_strip));
+            copy_block(offset as usize, pos, UVec2::new(width, rows_per_strip))?;
+        }
+    }
+
+    let no_data = match entries.get(&TIFF_TAG_GDAL_NODATA) {
+        Some(entry) => entry.ascii(&reader)?.parse::<f32>().ok(),
+        None => None,
+    };
+
+    let is_valid = |h: &f32| h.is_finite() && Some(*h) != no_data;
+
+    // Unsigned integer samples use their full range, everything else is normalized to the valid height range.
+    let (min, max) = if format == 1 {
+        (0.0, ((1_u64 << bits) - 1) as f32)
+    } else {
+        samples
+            .iter()
+            .filter(|h| is_valid(h))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &h| {
+                (min.min(h), max.max(h))
+            })
+    };
+
+    let range = (max - min).max(f32::EPSILON);
+    let heights = samples
+        .iter()
+        .map(|h| {
+            if is_valid(h) {
+                ((h - min) / range).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    Ok((UVec2::new(width, height), heights))
+}
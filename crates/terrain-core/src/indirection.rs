@@ -0,0 +1,185 @@
+use std::ops::Range;
+
+use glam::{IVec2, UVec2};
+
+use crate::{PatchKey, TerrainConfig};
+
+// Outside of any atlas.
+pub const EMPTY_ATLAS_INDEX: UVec2 = UVec2::MAX;
+
+// Slots of one indirection row that changed since the last `take_dirty_rows`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndirectionRowUpdate {
+    pub lod_index: u32,
+    pub row: u32,
+    pub columns: Range<u32>,
+}
+
+struct IndirectionLod {
+    slots: Vec<UVec2>,
+    dirty_rows: Vec<Option<Range<u32>>>,
+}
+
+// Atlas slot of every resident patch around the camera, one table per LOD. Slots are addressed toroidally by world
+// index, see `TerrainConfig::toroidal_slot`, so a moving window only rewrites the rows and columns that scrolled in
+// and residency changes only the slot of their patch.
+pub struct IndirectionTables {
+    config: TerrainConfig,
+    cam_world_index: Option<IVec2>,
+    lods: Vec<IndirectionLod>,
+}
+
+impl IndirectionTables {
+    // Every slot starts out empty and dirty, the GPU copy has undefined contents until the first upload.
+    pub fn new(config: TerrainConfig) -> Self {
+        let lods = (0..config.lod_count)
+            .map(|lod_index| {
+                let slot_count = config.indirection_slot_count(lod_index);
+
+                IndirectionLod {
+                    slots: vec![EMPTY_ATLAS_INDEX; slot_count.pow(2) as usize],
+                    dirty_rows: vec![Some(0..slot_count); slot_count as usize],
+                }
+            })
+            .collect();
+
+        Self {
+            config,
+            cam_world_index: None,
+            lods,
+        }
+    }
+
+    pub fn config(&self) -> &TerrainConfig {
+        &self.config
+    }
+
+    pub fn lod_count(&self) -> u32 {
+        self.lods.len() as u32
+    }
+
+    pub fn slot_count(&self, lod_index: u32) -> u32 {
+        self.config.indirection_slot_count(lod_index)
+    }
+
+    pub fn lod(&self, lod_index: u32) -> &[UVec2] {
+        &self.lods[lod_index as usize].slots
+    }
+
+    // `None` outside of the window or for patches that are not resident.
+    pub fn atlas_index(&self, key: &PatchKey) -> Option<UVec2> {
+        let slot = self.slot(key)?;
+        let atlas_index = self.lod(key.lod_index)[self.slot_offset(key.lod_index, slot)];
+
+        (atlas_index != EMPTY_ATLAS_INDEX).then_some(atlas_index)
+    }
+
+    // Moves the window of every LOD to `cam_world_index` and fills the slots that scrolled in from `atlas_index`. A
+    // window that moved by its full size or more is filled again completely.
+    pub fn recenter(&mut self, cam_world_index: IVec2, atlas_index: impl Fn(&PatchKey) -> Option<UVec2>) {
+        let old_cam_world_index = self.cam_world_index.replace(cam_world_index);
+
+        for lod_index in 0..self.lod_count() {
+            let slot_count = self.slot_count(lod_index) as i32;
+            let origin = |cam_world_index: IVec2| (cam_world_index >> lod_index) - slot_count / 2;
+            let new_origin = origin(cam_world_index);
+            let old_origin = old_cam_world_index.map(origin);
+
+            let window = |axis: usize| new_origin[axis]..new_origin[axis] + slot_count;
+            let mut refill = |xs: Range<i32>, ys: Range<i32>| {
+                for y in ys {
+                    for x in xs.clone() {
+                        let key = PatchKey {
+                            world_index: IVec2::new(x, y) << lod_index,
+                            lod_index,
+                        };
+                        self.write(&key, atlas_index(&key).unwrap_or(EMPTY_ATLAS_INDEX));
+                    }
+                }
+            };
+
+            let delta = match old_origin {
+                Some(old_origin) if (new_origin - old_origin).abs().max_element() < slot_count => {
+                    new_origin - old_origin
+                }
+                _ => {
+                    refill(window(0), window(1));
+                    continue;
+                }
+            };
+
+            // Bands of columns and rows that entered the window, the corner they share is written twice.
+            let entered = |axis: usize| {
+                let window = window(axis);
+                if delta[axis] > 0 {
+                    window.end - delta[axis]..window.end
+                } else {
+                    window.start..window.start - delta[axis]
+                }
+            };
+
+            refill(entered(0), window(1));
+            refill(window(0), entered(1));
+        }
+    }
+
+    // Residency changes of patches outside the window are picked up by `recenter` once they scroll in.
+    pub fn insert(&mut self, key: &PatchKey, atlas_index: UVec2) {
+        if self.slot(key).is_some() {
+            self.write(key, atlas_index);
+        }
+    }
+
+    pub fn remove(&mut self, key: &PatchKey) {
+        if self.slot(key).is_some() {
+            self.write(key, EMPTY_ATLAS_INDEX);
+        }
+    }
+
+    pub fn take_dirty_rows(&mut self) -> Vec<IndirectionRowUpdate> {
+        let mut updates = Vec::new();
+
+        for (lod_index, lod) in self.lods.iter_mut().enumerate() {
+            for (row, columns) in lod.dirty_rows.iter_mut().enumerate() {
+                if let Some(columns) = columns.take() {
+                    updates.push(IndirectionRowUpdate {
+                        lod_index: lod_index as u32,
+                        row: row as u32,
+                        columns,
+                    });
+                }
+            }
+        }
+
+        updates
+    }
+
+    fn slot(&self, key: &PatchKey) -> Option<UVec2> {
+        if key.lod_index >= self.lod_count() {
+            return None;
+        }
+
+        self.config.indirection_slot(key, self.cam_world_index?)
+    }
+
+    fn slot_offset(&self, lod_index: u32, slot: UVec2) -> usize {
+        (slot.y * self.slot_count(lod_index) + slot.x) as usize
+    }
+
+    // Only marks the slot dirty if its atlas index actually changes.
+    fn write(&mut self, key: &PatchKey, atlas_index: UVec2) {
+        let slot = self.config.toroidal_slot(key);
+        let offset = self.slot_offset(key.lod_index, slot);
+        let lod = &mut self.lods[key.lod_index as usize];
+
+        if lod.slots[offset] == atlas_index {
+            return;
+        }
+
+        lod.slots[offset] = atlas_index;
+
+        let columns = lod.dirty_rows[slot.y as usize].get_or_insert(slot.x..slot.x + 1);
+        columns.start = columns.start.min(slot.x);
+        columns.end = columns.end.max(slot.x + 1);
+    }
+}
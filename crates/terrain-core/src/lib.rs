@@ -0,0 +1,47 @@
+mod bounds;
+mod config;
+mod disk_cache;
+mod erosion;
+mod frustum;
+mod generator;
+mod height_field;
+mod height_source;
+mod heightmap;
+mod indirection;
+mod morph;
+mod patch;
+mod planner;
+mod quadtree;
+mod raycast;
+mod residency;
+mod seams;
+
+pub use bounds::*;
+pub use config::*;
+pub use disk_cache::*;
+pub use erosion::*;
+pub use frustum::*;
+pub use generator::*;
+pub use height_field::*;
+pub use height_source::*;
+pub use heightmap::*;
+pub use indirection::*;
+pub use morph::*;
+pub use patch::*;
+pub use planner::*;
+pub use quadtree::*;
+pub use raycast::*;
+pub use residency::*;
+pub use seams::*;
+
+// Defaults of `TerrainConfig`.
+pub const PATCH_LOD_COUNT: u32 = 5;
+pub const PATCH_PIXEL_SIZE: u32 = 128;
+
+pub const ATLAS_PATCH_PIXEL_SIZE: u32 = PATCH_PIXEL_SIZE + 1; // for pixel overlap
+pub const ATLAS_PATCH_COUNT: u32 = 32;
+pub const ATLAS_SIZE: u32 = ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_COUNT;
+pub const INDIRECTION_SLOT_COUNT: u32 = 128;
+
+// World units covered by a patch of the finest LOD, independent of its pixel size.
+pub const PATCH_WORLD_SIZE: u32 = 64;
@@ -0,0 +1,101 @@
+use glam::{UVec2, Vec2};
+
+use crate::{LodPolicy, PATCH_WORLD_SIZE, StitchMask, height_map_pixel_size};
+
+// Camera distances over which the vertices of one LOD blend into their parent's mesh. Mirrored by `morph_ranges` in
+// terrain.hlsl, stored as x = start and y = end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MorphRange {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl MorphRange {
+    // Never reached, vertices keep their own positions.
+    pub const NONE: MorphRange = MorphRange {
+        start: f32::MAX,
+        end: f32::MAX,
+    };
+}
+
+// Vertices are fully morphed at the distance their parent splits at and only start morphing outside their own LOD's
+// split band, so neither splits nor merges pop. Only the distance policy has per LOD switch distances, the screen space error policy
+// switches per node and does not morph.
+pub fn lod_morph_ranges(lod_policy: &LodPolicy, hysteresis: f32, lod_count: u32) -> Vec<MorphRange> {
+    let LodPolicy::Distance { lod_factor } = *lod_policy else {
+        return vec![MorphRange::NONE; lod_count as usize];
+    };
+
+    let split_distance = |lod_index: u32| (PATCH_WORLD_SIZE << lod_index) as f32 * 0.5 * lod_factor;
+
+    (0..lod_count)
+        .map(|lod_index| {
+            if lod_index == lod_count - 1 {
+                return MorphRange::NONE;
+            }
+
+            let end = split_distance(lod_index + 1) * (1.0 - hysteresis);
+            let start = (split_distance(lod_index) * (1.0 + hysteresis) + end) * 0.5;
+
+            MorphRange { start, end }
+        })
+        .collect()
+}
+
+pub fn morph_factor(distance: f32, range: &MorphRange) -> f32 {
+    ((distance - range.start) / (range.end - range.start).max(1e-6)).clamp(0.0, 1.0)
+}
+
+// Odd vertices slide onto an even neighbour, fully morphed the patch flattens into exactly the parent's triangles.
+// Vertices on parent edges move towards the patch origin like stitched ones, vertices in the middle of a parent quad
+// move onto a corner on that quad's diagonal, which alternates like in `patch_quad_triangles`.
+pub fn morph_vertex(vertex: UVec2, morph: f32) -> Vec2 {
+    let odd = vertex % 2;
+    let parent_quad = vertex / 2;
+
+    let x_dir = if odd == UVec2::ONE && !(parent_quad.x + parent_quad.y).is_multiple_of(2) {
+        1.0
+    } else {
+        -1.0
+    };
+
+    vertex.as_vec2() + Vec2::new(x_dir, -1.0) * odd.as_vec2() * morph
+}
+
+// Grid position the vertex shader draws `vertex` of a patch with `pixel_size` quads at. Stitched edges snap onto the
+// coarser neighbour's vertices, and since those have to stay put, edges towards a finer neighbour are not morphed.
+pub fn place_vertex(vertex: UVec2, pixel_size: u32, stitch_mask: StitchMask, morph: f32) -> Vec2 {
+    let (top, bottom) = (vertex.y == 0, vertex.y == pixel_size);
+    let (left, right) = (vertex.x == 0, vertex.x == pixel_size);
+    let has = |flag: StitchMask| stitch_mask.contains(flag);
+
+    let mut stitched = vertex;
+    if (top && has(StitchMask::TOP)) || (bottom && has(StitchMask::BOTTOM)) {
+        stitched.x = vertex.x / 2 * 2;
+    }
+    if (left && has(StitchMask::LEFT)) || (right && has(StitchMask::RIGHT)) {
+        stitched.y = vertex.y / 2 * 2;
+    }
+
+    let locked = (top && has(StitchMask::FINER_TOP))
+        || (bottom && has(StitchMask::FINER_BOTTOM))
+        || (left && has(StitchMask::FINER_LEFT))
+        || (right && has(StitchMask::FINER_RIGHT));
+
+    morph_vertex(stitched, if locked { 0.0 } else { morph })
+}
+
+// Bilinear height at a possibly morphed grid position, matching the height the vertex shader reads.
+pub fn morphed_height(heights: &[f32], grid_pos: Vec2) -> f32 {
+    let pixel_size = height_map_pixel_size(heights);
+    let grid_pos = grid_pos.clamp(Vec2::ZERO, Vec2::splat(pixel_size as f32));
+    let p0 = grid_pos.floor().as_uvec2().min(UVec2::splat(pixel_size - 1));
+    let t = grid_pos - p0.as_vec2();
+
+    let h = |x: u32, y: u32| heights[(y * (pixel_size + 1) + x) as usize];
+
+    let top = h(p0.x, p0.y) * (1.0 - t.x) + h(p0.x + 1, p0.y) * t.x;
+    let bottom = h(p0.x, p0.y + 1) * (1.0 - t.x) + h(p0.x + 1, p0.y + 1) * t.x;
+
+    top * (1.0 - t.y) + bottom * t.y
+}
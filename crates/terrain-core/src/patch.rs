@@ -0,0 +1,87 @@
+use bitflags::bitflags;
+use glam::{IVec2, UVec2};
+
+use crate::{HeightRangePyramid, PATCH_WORLD_SIZE};
+
+bitflags! {
+    #[repr(transparent)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct StitchMask: u32 {
+        const TOP = 1 << 0;
+        const BOTTOM = 1 << 1;
+        const LEFT = 1 << 2;
+        const RIGHT = 1 << 3;
+        // Edges towards a finer neighbour. They are not morphed, the neighbour stitches onto their vertices.
+        const FINER_TOP = 1 << 4;
+        const FINER_BOTTOM = 1 << 5;
+        const FINER_LEFT = 1 << 6;
+        const FINER_RIGHT = 1 << 7;
+    }
+}
+
+pub enum PatchState {
+    Requested,
+    Generated(Vec<f32>, HeightRangePyramid),
+    Uploading(UVec2, u64),
+    Resident(UVec2),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PatchKey {
+    pub world_index: IVec2,
+    pub lod_index: u32,
+}
+
+impl PatchKey {
+    pub fn world_pos(&self) -> IVec2 {
+        self.world_index * PATCH_WORLD_SIZE as i32
+    }
+
+    pub fn world_size(&self) -> u32 {
+        PATCH_WORLD_SIZE * 2_u32.pow(self.lod_index)
+    }
+
+    pub fn world_center(&self) -> IVec2 {
+        self.world_pos() + self.world_size() as i32 / 2
+    }
+}
+
+// Vertex grid coordinates of the two triangles of quad (x, z). The diagonal alternates in a checkerboard so the
+// triangulation has no directional bias, the raycast relies on this being the exact same split the GPU draws.
+pub fn patch_quad_triangles(x: u32, z: u32) -> [[UVec2; 3]; 2] {
+    let top_left = UVec2::new(x, z);
+    let top_right = UVec2::new(x + 1, z);
+    let bottom_left = UVec2::new(x, z + 1);
+    let bottom_right = UVec2::new(x + 1, z + 1);
+
+    if (x + z).is_multiple_of(2) {
+        [
+            [top_left, bottom_left, bottom_right],
+            [top_left, bottom_right, top_right],
+        ]
+    } else {
+        [
+            [top_left, bottom_left, top_right],
+            [top_right, bottom_left, bottom_right],
+        ]
+    }
+}
+
+// Quads per side of a height map, which has one more row and column for the overlap.
+pub fn height_map_pixel_size(heights: &[f32]) -> u32 {
+    heights.len().isqrt() as u32 - 1
+}
+
+pub fn patch_indices(pixel_size: u32) -> Vec<u32> {
+    let mut indices = Vec::with_capacity((pixel_size * pixel_size * 6) as usize);
+
+    for z in 0..pixel_size {
+        for x in 0..pixel_size {
+            for triangle in patch_quad_triangles(x, z) {
+                indices.extend(triangle.map(|v| v.y * (pixel_size + 1) + v.x));
+            }
+        }
+    }
+
+    indices
+}
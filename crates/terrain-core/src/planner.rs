@@ -0,0 +1,355 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use glam::{IVec2, UVec2, Vec3, Vec3Swizzles};
+
+use crate::{
+    AtlasResidency, DiskPatchCache, Frustum, HeightSource, IndirectionRowUpdate, IndirectionTables, LodPolicy,
+    MorphRange, PATCH_WORLD_SIZE, PatchBoundsTree, PatchGenPool, PatchKey, PatchLeafDiff, PatchLeafIndex,
+    PatchQuadTree, PatchState, StitchMask, TerrainConfig, TerrainConfigError, TerrainCoverage, TerrainHeightField,
+    TerrainScale, lod_morph_ranges,
+};
+
+// Fraction of the split distance that leafs have to come closer before splitting, and that split nodes have to move
+// farther away before merging.
+pub const LOD_HYSTERESIS: f32 = 0.1;
+
+pub struct PlannedPatch {
+    pub key: PatchKey,
+    // Edges to stitch and edges to keep unmorphed for finer neighbours.
+    pub stitch_mask: StitchMask,
+}
+
+pub struct AtlasUpload {
+    pub key: PatchKey,
+    pub atlas_index: UVec2,
+    pub height_map: Arc<[f32]>,
+}
+
+pub struct FramePlan {
+    pub atlas_uploads: Vec<AtlasUpload>,
+    pub patches: Vec<PlannedPatch>,
+    pub indirection_updates: Vec<IndirectionRowUpdate>,
+}
+
+pub struct TerrainPlanner {
+    config: TerrainConfig,
+    pub render_distance: u32,
+    pub lod_policy: LodPolicy,
+    pub scale: TerrainScale,
+
+    cam_world_index: IVec2,
+    quad_tree: PatchQuadTree,
+    leaf_index: PatchLeafIndex,
+    leaf_diff: PatchLeafDiff,
+    culled_leaf_count: usize,
+
+    patch_cache: HashMap<PatchKey, PatchState>,
+    patch_gen_pool: PatchGenPool,
+    residency: AtlasResidency,
+    indirection: IndirectionTables,
+    height_field: TerrainHeightField,
+    bounds: PatchBoundsTree,
+    cache_error: Option<std::io::Error>,
+}
+
+impl TerrainPlanner {
+    // `config` is expected to pass `TerrainConfig::validate` for `render_distance`.
+    pub fn new(
+        config: TerrainConfig,
+        render_distance: u32,
+        lod_policy: LodPolicy,
+        height_source: Arc<dyn HeightSource>,
+        disk_cache: Option<Arc<DiskPatchCache>>,
+    ) -> Self {
+        Self {
+            config,
+            render_distance,
+            lod_policy,
+            scale: TerrainScale::default(),
+
+            cam_world_index: IVec2::ZERO,
+            quad_tree: PatchQuadTree::with_hysteresis(LOD_HYSTERESIS).with_lod_count(config.lod_count),
+            leaf_index: PatchLeafIndex::new(&[]),
+            leaf_diff: PatchLeafDiff::default(),
+            culled_leaf_count: 0,
+
+            patch_cache: HashMap::new(),
+            patch_gen_pool: PatchGenPool::new(height_source, disk_cache, config.patch_pixel_size),
+            residency: AtlasResidency::new(config.atlas_patch_count),
+            indirection: IndirectionTables::new(TerrainConfig {
+                indirection_slot_count: config.indirection_slot_count_for(render_distance),
+                ..config
+            }),
+            height_field: TerrainHeightField::new(config.lod_count),
+            bounds: PatchBoundsTree::new(),
+            cache_error: None,
+        }
+    }
+
+    pub fn config(&self) -> &TerrainConfig {
+        &self.config
+    }
+
+    pub fn cam_world_index(&self) -> IVec2 {
+        self.cam_world_index
+    }
+
+    pub fn leaf_patches(&self) -> &[PatchKey] {
+        self.quad_tree.leafs()
+    }
+
+    // Leafs added and removed by the last `collect_leaf_patches`.
+    pub fn leaf_diff(&self) -> &PatchLeafDiff {
+        &self.leaf_diff
+    }
+
+    pub fn quad_tree(&self) -> &PatchQuadTree {
+        &self.quad_tree
+    }
+
+    // Sizes the GPU side needs for the current render distance, LOD policy and scale.
+    pub fn coverage(&self) -> Result<TerrainCoverage, TerrainConfigError> {
+        self.config
+            .coverage(self.render_distance, &self.lod_policy, LOD_HYSTERESIS, &self.scale)
+    }
+
+    pub fn morph_ranges(&self) -> Vec<MorphRange> {
+        lod_morph_ranges(&self.lod_policy, LOD_HYSTERESIS, self.config.lod_count)
+    }
+
+    // Leafs outside the frustum passed to the last `collect_leaf_patches`.
+    pub fn culled_leaf_count(&self) -> usize {
+        self.culled_leaf_count
+    }
+
+    pub fn patch_cache(&self) -> &HashMap<PatchKey, PatchState> {
+        &self.patch_cache
+    }
+
+    pub fn residency(&self) -> &AtlasResidency {
+        &self.residency
+    }
+
+    pub fn indirection(&self) -> &IndirectionTables {
+        &self.indirection
+    }
+
+    // Rows changed since the last call, for uploading only those to the GPU copy of `indirection`.
+    pub fn take_indirection_updates(&mut self) -> Vec<IndirectionRowUpdate> {
+        self.indirection.take_dirty_rows()
+    }
+
+    pub fn height_field(&self) -> &TerrainHeightField {
+        &self.height_field
+    }
+
+    pub fn bounds(&self) -> &PatchBoundsTree {
+        &self.bounds
+    }
+
+    pub fn patch_gen_pool(&self) -> &PatchGenPool {
+        &self.patch_gen_pool
+    }
+
+    // Last disk cache failure reported by patch generation.
+    pub fn cache_error(&self) -> Option<&std::io::Error> {
+        self.cache_error.as_ref()
+    }
+
+    pub fn plan_frame(
+        &mut self,
+        camera_pos: &Vec3,
+        frustum: Option<&Frustum>,
+        cpu_frame_index: u64,
+        gpu_frame_index: u64,
+    ) -> FramePlan {
+        let atlas_uploads = self.collect_atlas_uploads(cpu_frame_index, gpu_frame_index);
+        let patches = self.collect_leaf_patches(camera_pos, frustum, cpu_frame_index);
+        let indirection_updates = self.take_indirection_updates();
+
+        FramePlan {
+            atlas_uploads,
+            patches,
+            indirection_updates,
+        }
+    }
+
+    pub fn collect_atlas_uploads(&mut self, cpu_frame_index: u64, gpu_frame_index: u64) -> Vec<AtlasUpload> {
+        let mut generated_patches = Vec::new();
+
+        for (&key, state) in self.patch_cache.iter_mut() {
+            match *state {
+                PatchState::Uploading(atlas_index, frame_index) if frame_index <= gpu_frame_index => {
+                    *state = PatchState::Resident(atlas_index);
+                    self.indirection.insert(&key, atlas_index);
+                }
+                PatchState::Generated(..) => generated_patches.push(key),
+                _ => {}
+            }
+        }
+
+        // Patches that are still part of the leaf set get the slots first, the rest is dropped if the atlas is full.
+        generated_patches.sort_unstable_by_key(|key| !self.leaf_index.contains(key));
+
+        let evicted_patches = self.residency.evict(
+            &mut self.patch_cache,
+            self.leaf_index.leafs(),
+            gpu_frame_index,
+            generated_patches.len(),
+        );
+        for key in &evicted_patches {
+            self.indirection.remove(key);
+            self.bounds.remove(key);
+        }
+        self.height_field.retain(|key| self.patch_cache.contains_key(key));
+
+        let mut uploads = Vec::new();
+
+        for key in generated_patches {
+            let Some(atlas_index) = self.residency.allocate() else {
+                if !self.leaf_index.contains(&key) {
+                    self.patch_cache.remove(&key);
+                    self.bounds.remove(&key);
+                }
+
+                continue;
+            };
+
+            let state = self.patch_cache.get_mut(&key).unwrap();
+
+            let PatchState::Generated(height_map, height_ranges) =
+                std::mem::replace(state, PatchState::Uploading(atlas_index, cpu_frame_index))
+            else {
+                unreachable!();
+            };
+
+            let height_map = Arc::<[f32]>::from(height_map);
+            self.height_field.insert(key, height_map.clone(), height_ranges);
+
+            uploads.push(AtlasUpload {
+                key,
+                atlas_index,
+                height_map,
+            });
+        }
+
+        uploads
+    }
+
+    // All leafs are generated and kept resident, but only the ones touching `frustum` are returned for drawing.
+    pub fn collect_leaf_patches(
+        &mut self,
+        camera_pos: &Vec3,
+        frustum: Option<&Frustum>,
+        cpu_frame_index: u64,
+    ) -> Vec<PlannedPatch> {
+        self.leaf_diff = self.quad_tree.update(
+            camera_pos,
+            self.render_distance,
+            &self.lod_policy,
+            &self.bounds,
+            &self.scale,
+        );
+        self.leaf_index.apply(&self.leaf_diff);
+
+        let leaf_count = self.quad_tree.leafs().len();
+        let visible_leafs = frustum.map(|f| {
+            self.quad_tree
+                .collect_visible_leafs(f, &self.bounds, &self.scale)
+                .into_iter()
+                .collect::<HashSet<_>>()
+        });
+        self.culled_leaf_count = visible_leafs.as_ref().map_or(0, |v| leaf_count - v.len());
+        if !self.leaf_diff.is_empty() {
+            self.height_field.set_leaf_patches(self.quad_tree.leafs());
+        }
+        self.cam_world_index = (camera_pos.xz() / self.scale.world_scale).as_ivec2() / PATCH_WORLD_SIZE as i32;
+        self.update_indirection();
+
+        // In unscaled world units like the patch sizes the queue divides by.
+        let cam_xz = camera_pos.xz() / self.scale.world_scale;
+        let patch_distance = |key: &PatchKey| cam_xz.distance(key.world_center().as_vec2());
+
+        let leaf_index = &self.leaf_index;
+        let cancelled_patches = self
+            .patch_gen_pool
+            .reprioritize_requests(|key| leaf_index.contains(key).then(|| patch_distance(key)));
+
+        for key in cancelled_patches {
+            self.patch_cache.remove(&key);
+        }
+
+        for result in self.patch_gen_pool.drain_results() {
+            if let Some(error) = result.cache_error {
+                self.cache_error = Some(error);
+            }
+
+            self.bounds.insert(result.request, result.height_ranges.range());
+            self.bounds
+                .insert_decimation_error(result.request, result.decimation_error);
+            self.patch_cache.insert(
+                result.request,
+                PatchState::Generated(result.height_map, result.height_ranges),
+            );
+        }
+
+        // Leafs stay cached until they stop being leafs, so only new ones can be missing.
+        for &key in &self.leaf_diff.added {
+            if !self.patch_cache.contains_key(&key) {
+                self.patch_gen_pool.requst_patch_generation(key, patch_distance(&key));
+                self.patch_cache.insert(key, PatchState::Requested);
+            }
+        }
+
+        let resident_leafs = self
+            .quad_tree
+            .leafs()
+            .iter()
+            .filter(|l| {
+                self.patch_cache
+                    .get(l)
+                    .is_some_and(|s| matches!(s, PatchState::Resident(_)))
+            })
+            .collect::<Vec<_>>();
+
+        let patches = resident_leafs
+            .iter()
+            .filter(|l| visible_leafs.as_ref().is_none_or(|v| v.contains(l)))
+            .map(|l| PlannedPatch {
+                key: **l,
+                stitch_mask: self.leaf_index.stitch_mask(l) | self.leaf_index.finer_edge_mask(l),
+            })
+            .collect();
+
+        for &&key in &resident_leafs {
+            debug_assert!(
+                self.indirection.atlas_index(&key).is_some(),
+                "leaf {:?} is outside of the indirection",
+                key
+            );
+            self.residency.touch(key, cpu_frame_index);
+        }
+
+        patches
+    }
+
+    // Tables grow with the render distance, see `TerrainConfig::indirection_slot_count_for`. Grown tables start over
+    // and are uploaded completely.
+    fn update_indirection(&mut self) {
+        let slot_count = self.config.indirection_slot_count_for(self.render_distance);
+        if slot_count != self.indirection.config().indirection_slot_count {
+            self.indirection = IndirectionTables::new(TerrainConfig {
+                indirection_slot_count: slot_count,
+                ..self.config
+            });
+        }
+
+        let patch_cache = &self.patch_cache;
+        self.indirection
+            .recenter(self.cam_world_index, |key| match patch_cache.get(key) {
+                Some(PatchState::Resident(atlas_index)) => Some(*atlas_index),
+                _ => None,
+            });
+    }
+}
@@ -0,0 +1,485 @@
+use std::collections::HashSet;
+
+use glam::{IVec2, Mat4, Vec2, Vec3, Vec3Swizzles};
+
+use crate::{
+    Frustum, PATCH_LOD_COUNT, PATCH_WORLD_SIZE, PatchBoundsTree, PatchEdge, PatchKey, StitchMask, TerrainScale,
+    find_leaf_at, finer_edge_mask, neighbor_across, stitch_mask,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LodPolicy {
+    // Splits while the camera is closer than `lod_factor` half node sizes.
+    Distance {
+        lod_factor: f32,
+    },
+    // Splits while the height error of a node against its children projects to more than `max_pixel_error` pixels.
+    // `projection_scale` converts world size at unit distance into pixels, nodes without any known error use the
+    // distance rule with `fallback_lod_factor`.
+    ScreenSpaceError {
+        max_pixel_error: f32,
+        projection_scale: f32,
+        fallback_lod_factor: f32,
+    },
+}
+
+impl LodPolicy {
+    pub fn screen_space_error(
+        max_pixel_error: f32,
+        view_to_clip: &Mat4,
+        viewport_height: u32,
+        fallback_lod_factor: f32,
+    ) -> Self {
+        LodPolicy::ScreenSpaceError {
+            max_pixel_error,
+            projection_scale: projection_scale(view_to_clip, viewport_height),
+            fallback_lod_factor,
+        }
+    }
+
+    // Camera distance below which `key` is split.
+    fn split_distance(&self, key: &PatchKey, bounds: &PatchBoundsTree, scale: &TerrainScale) -> f32 {
+        let distance_rule = |lod_factor: f32| key.world_size() as f32 * 0.5 * lod_factor;
+
+        match *self {
+            LodPolicy::Distance { lod_factor } => distance_rule(lod_factor),
+            LodPolicy::ScreenSpaceError {
+                max_pixel_error,
+                projection_scale,
+                fallback_lod_factor,
+            } => match bounds.geometric_error(key) {
+                Some(error) => error * scale.height_scale * projection_scale / max_pixel_error,
+                None => distance_rule(fallback_lod_factor),
+            },
+        }
+    }
+}
+
+// Pixels covered by one world unit at unit distance, from the vertical focal length of the projection.
+pub fn projection_scale(view_to_clip: &Mat4, viewport_height: u32) -> f32 {
+    view_to_clip.y_axis.y.abs() * viewport_height as f32 * 0.5
+}
+
+#[derive(Clone)]
+pub struct PatchQuadNode {
+    key: PatchKey,
+    children: Option<Box<[PatchQuadNode; 4]>>,
+    // Split only to keep the tree balanced, such splits are undone before every update.
+    balanced: bool,
+    // Camera position of the last evaluation and how far the camera can move from there before any split decision in
+    // the subtree can change.
+    eval_pos: Vec3,
+    slack: f32,
+    // `PatchBoundsTree::revision` at the last evaluation.
+    bounds_revision: u64,
+}
+
+impl PatchQuadNode {
+    fn root(cam_pos: &Vec3, render_distance: u32, lod_count: u32, scale: &TerrainScale) -> Self {
+        let snap_size = PATCH_WORLD_SIZE * 2_u32.pow(lod_count - 1);
+        let snapped_cam_pos =
+            (cam_pos.xz() / scale.world_scale / snap_size as f32).round().as_ivec2() * snap_size as i32;
+
+        Self::new(
+            (snapped_cam_pos / PATCH_WORLD_SIZE as i32) - (render_distance / PATCH_WORLD_SIZE) as i32,
+            (render_distance * 2 / PATCH_WORLD_SIZE).ilog2(),
+        )
+    }
+
+    fn new(world_index: IVec2, lod_index: u32) -> Self {
+        Self {
+            key: PatchKey { world_index, lod_index },
+            children: None,
+            balanced: false,
+            eval_pos: Vec3::ZERO,
+            slack: 0.0,
+            bounds_revision: 0,
+        }
+    }
+}
+
+// Leafs hashed by key. Neighbour queries probe one key per LOD, so they cost the same for any number of leafs.
+pub struct PatchLeafIndex {
+    leafs: HashSet<PatchKey>,
+}
+
+impl PatchLeafIndex {
+    pub fn new(leafs: &[PatchKey]) -> Self {
+        Self {
+            leafs: leafs.iter().copied().collect(),
+        }
+    }
+
+    pub fn apply(&mut self, diff: &PatchLeafDiff) {
+        for key in &diff.removed {
+            self.leafs.remove(key);
+        }
+        self.leafs.extend(diff.added.iter().copied());
+    }
+
+    pub fn leafs(&self) -> &HashSet<PatchKey> {
+        &self.leafs
+    }
+
+    pub fn len(&self) -> usize {
+        self.leafs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leafs.is_empty()
+    }
+
+    pub fn contains(&self, key: &PatchKey) -> bool {
+        self.leafs.contains(key)
+    }
+
+    // `world_pos` in unscaled world units.
+    pub fn leaf_at(&self, world_pos: Vec2) -> Option<PatchKey> {
+        find_leaf_at(&self.leafs, world_pos)
+    }
+
+    pub fn neighbor(&self, key: &PatchKey, edge: PatchEdge) -> Option<PatchKey> {
+        neighbor_across(&self.leafs, key, edge)
+    }
+
+    pub fn stitch_mask(&self, key: &PatchKey) -> StitchMask {
+        stitch_mask(&self.leafs, key)
+    }
+
+    pub fn finer_edge_mask(&self, key: &PatchKey) -> StitchMask {
+        finer_edge_mask(&self.leafs, key)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatchLeafDiff {
+    pub added: Vec<PatchKey>,
+    pub removed: Vec<PatchKey>,
+}
+
+impl PatchLeafDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct PatchQuadTreeInputs {
+    render_distance: u32,
+    lod_policy: LodPolicy,
+    scale: TerrainScale,
+}
+
+#[derive(Clone, Copy)]
+struct NodeUpdate<'a> {
+    cam_pos: &'a Vec3,
+    full_update: bool,
+    lod_policy: &'a LodPolicy,
+    bounds: &'a PatchBoundsTree,
+    scale: &'a TerrainScale,
+    hysteresis: f32,
+    lod_count: u32,
+}
+
+// Persistent LOD quadtree. Updates only revisit subtrees the camera has moved far enough for or whose bounds changed,
+// unless the policy, scale or render distance changed, in which case every node is evaluated again.
+pub struct PatchQuadTree {
+    root: Option<PatchQuadNode>,
+    // Leafs split when the camera is closer than `1 - hysteresis` times the split distance and merge again when it is
+    // farther than `1 + hysteresis` times, so hovering around the threshold does not flip LODs every frame.
+    hysteresis: f32,
+    lod_count: u32,
+    inputs: Option<PatchQuadTreeInputs>,
+    leafs: Vec<PatchKey>,
+    evaluated_node_count: usize,
+}
+
+impl PatchQuadTree {
+    // `bounds` gives every node a height range so LOD selection uses the 3D distance to the node's bounding box.
+    pub fn new(
+        cam_pos: &Vec3,
+        render_distance: u32,
+        lod_policy: &LodPolicy,
+        bounds: &PatchBoundsTree,
+        scale: &TerrainScale,
+    ) -> Self {
+        let mut tree = Self::with_hysteresis(0.0);
+        tree.update(cam_pos, render_distance, lod_policy, bounds, scale);
+
+        tree
+    }
+
+    pub fn with_hysteresis(hysteresis: f32) -> Self {
+        Self {
+            root: None,
+            hysteresis,
+            lod_count: PATCH_LOD_COUNT,
+            inputs: None,
+            leafs: Vec::new(),
+            evaluated_node_count: 0,
+        }
+    }
+
+    // Leafs are at most `lod_count - 1`, coarser nodes are always split.
+    pub fn with_lod_count(mut self, lod_count: u32) -> Self {
+        self.lod_count = lod_count;
+        self
+    }
+
+    pub fn lod_count(&self) -> u32 {
+        self.lod_count
+    }
+
+    pub fn update(
+        &mut self,
+        cam_pos: &Vec3,
+        render_distance: u32,
+        lod_policy: &LodPolicy,
+        bounds: &PatchBoundsTree,
+        scale: &TerrainScale,
+    ) -> PatchLeafDiff {
+        let inputs = PatchQuadTreeInputs {
+            render_distance,
+            lod_policy: *lod_policy,
+            scale: *scale,
+        };
+        let full_update = self.inputs != Some(inputs);
+        self.inputs = Some(inputs);
+
+        let root = PatchQuadNode::root(cam_pos, render_distance, self.lod_count, scale);
+        if self.root.as_ref().is_none_or(|r| r.key != root.key) {
+            self.root = Some(root);
+        }
+
+        let root = self.root.as_mut().unwrap();
+        Self::unbalance(root);
+
+        self.evaluated_node_count = 0;
+        let update = NodeUpdate {
+            cam_pos,
+            full_update,
+            lod_policy,
+            bounds,
+            scale,
+            hysteresis: self.hysteresis,
+            lod_count: self.lod_count,
+        };
+        Self::update_node(root, &update, &mut self.evaluated_node_count);
+        Self::balance(root);
+
+        let leafs = self.collect_leafs();
+        let old_leafs = self.leafs.iter().copied().collect::<HashSet<_>>();
+        let new_leafs = leafs.iter().copied().collect::<HashSet<_>>();
+
+        let diff = PatchLeafDiff {
+            added: leafs.iter().copied().filter(|l| !old_leafs.contains(l)).collect(),
+            removed: self.leafs.iter().copied().filter(|l| !new_leafs.contains(l)).collect(),
+        };
+        self.leafs = leafs;
+
+        diff
+    }
+
+    pub fn leafs(&self) -> &[PatchKey] {
+        &self.leafs
+    }
+
+    // Nodes whose split decision was evaluated by the last update.
+    pub fn evaluated_node_count(&self) -> usize {
+        self.evaluated_node_count
+    }
+
+    pub fn collect_leafs(&self) -> Vec<PatchKey> {
+        let mut leafs = Vec::new();
+        if let Some(root) = &self.root {
+            Self::traverse_node(root, &mut leafs);
+        }
+
+        leafs
+    }
+
+    pub fn leaf_index(&self) -> PatchLeafIndex {
+        PatchLeafIndex::new(&self.collect_leafs())
+    }
+
+    // Leafs whose bounding box touches `frustum`, whole subtrees are skipped once their node is outside. That relies on
+    // `PatchBoundsTree` ranges containing every patch below a node.
+    pub fn collect_visible_leafs(
+        &self,
+        frustum: &Frustum,
+        bounds: &PatchBoundsTree,
+        scale: &TerrainScale,
+    ) -> Vec<PatchKey> {
+        let mut leafs = Vec::new();
+        if let Some(root) = &self.root {
+            Self::traverse_visible_node(root, frustum, bounds, scale, &mut leafs);
+        }
+
+        leafs
+    }
+
+    fn update_node(node: &mut PatchQuadNode, update: &NodeUpdate, evaluated_node_count: &mut usize) {
+        let NodeUpdate {
+            cam_pos,
+            full_update,
+            lod_policy,
+            bounds,
+            scale,
+            hysteresis,
+            lod_count,
+        } = *update;
+
+        // Distances to the boxes change at most as much as the camera moved.
+        if !full_update
+            && cam_pos.distance(node.eval_pos) < node.slack
+            && bounds.subtree_revision(&node.key) <= node.bounds_revision
+        {
+            return;
+        }
+
+        *evaluated_node_count += 1;
+
+        let (aabb_min, aabb_max) = bounds.aabb(&node.key, scale);
+        let distance = (cam_pos.clamp(aabb_min, aabb_max) - cam_pos).length();
+        let split_distance = lod_policy.split_distance(&node.key, bounds, scale);
+
+        let threshold = |is_split: bool| split_distance * if is_split { 1.0 + hysteresis } else { 1.0 - hysteresis };
+        let is_forced = node.key.lod_index > lod_count - 1;
+        let should_split = is_forced || distance < threshold(node.children.is_some());
+
+        let margin = if is_forced {
+            f32::INFINITY
+        } else {
+            (distance - threshold(should_split)).abs()
+        };
+
+        node.eval_pos = *cam_pos;
+        node.slack = margin;
+        node.bounds_revision = bounds.revision();
+
+        if !should_split {
+            node.children = None;
+            return;
+        }
+
+        if node.children.is_none() {
+            Self::split_node(node);
+        }
+
+        if node.key.lod_index == 1 {
+            return;
+        }
+
+        for child in node.children.as_mut().unwrap().iter_mut() {
+            Self::update_node(child, update, evaluated_node_count);
+            node.slack = node.slack.min(child.slack - cam_pos.distance(child.eval_pos));
+        }
+    }
+
+    fn split_node(node: &mut PatchQuadNode) {
+        let next_lod_index = node.key.lod_index - 1;
+        let next_offset = 2_u32.pow(next_lod_index) as i32;
+
+        node.children = Some(Box::new([
+            PatchQuadNode::new(node.key.world_index + IVec2::ZERO * next_offset, next_lod_index),
+            PatchQuadNode::new(node.key.world_index + IVec2::X * next_offset, next_lod_index),
+            PatchQuadNode::new(node.key.world_index + IVec2::Y * next_offset, next_lod_index),
+            PatchQuadNode::new(node.key.world_index + IVec2::ONE * next_offset, next_lod_index),
+        ]));
+    }
+
+    // Stitching only handles neighbours one LOD coarser, so leafs more than one LOD coarser than a neighbour are split
+    // until no such pair is left. Splits can unbalance further leafs, hence the repeated passes.
+    fn balance(root: &mut PatchQuadNode) {
+        loop {
+            let mut leafs = Vec::new();
+            Self::traverse_node(root, &mut leafs);
+            let leaf_set = leafs.iter().copied().collect::<HashSet<_>>();
+
+            let unbalanced = leafs
+                .iter()
+                .flat_map(|leaf| {
+                    PatchEdge::ALL
+                        .into_iter()
+                        .filter_map(|edge| neighbor_across(&leaf_set, leaf, edge))
+                        .filter(|neighbor| neighbor.lod_index > leaf.lod_index + 1)
+                })
+                .collect::<HashSet<_>>();
+
+            if unbalanced.is_empty() {
+                return;
+            }
+
+            for key in &unbalanced {
+                if let Some(node) = Self::find_node(root, key) {
+                    Self::split_node(node);
+                    node.balanced = true;
+                }
+            }
+        }
+    }
+
+    fn unbalance(node: &mut PatchQuadNode) {
+        if node.balanced {
+            node.children = None;
+            node.balanced = false;
+            return;
+        }
+
+        if let Some(children) = node.children.as_mut() {
+            for child in children.iter_mut() {
+                Self::unbalance(child);
+            }
+        }
+    }
+
+    fn find_node<'a>(node: &'a mut PatchQuadNode, key: &PatchKey) -> Option<&'a mut PatchQuadNode> {
+        if node.key == *key {
+            return Some(node);
+        }
+
+        let min = node.key.world_index;
+        let max = min + (1 << node.key.lod_index);
+        if key.lod_index >= node.key.lod_index || key.world_index.cmplt(min).any() || key.world_index.cmpge(max).any() {
+            return None;
+        }
+
+        node.children
+            .as_mut()?
+            .iter_mut()
+            .find_map(|child| Self::find_node(child, key))
+    }
+
+    fn traverse_node(node: &PatchQuadNode, leafs: &mut Vec<PatchKey>) {
+        if node.children.is_none() {
+            leafs.push(node.key);
+            return;
+        }
+
+        for child in node.children.as_ref().unwrap().iter() {
+            Self::traverse_node(child, leafs);
+        }
+    }
+
+    fn traverse_visible_node(
+        node: &PatchQuadNode,
+        frustum: &Frustum,
+        bounds: &PatchBoundsTree,
+        scale: &TerrainScale,
+        leafs: &mut Vec<PatchKey>,
+    ) {
+        let (aabb_min, aabb_max) = bounds.aabb(&node.key, scale);
+        if !frustum.intersects_aabb(aabb_min, aabb_max) {
+            return;
+        }
+
+        if node.children.is_none() {
+            leafs.push(node.key);
+            return;
+        }
+
+        for child in node.children.as_ref().unwrap().iter() {
+            Self::traverse_visible_node(child, frustum, bounds, scale, leafs);
+        }
+    }
+}
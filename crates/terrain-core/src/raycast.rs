@@ -0,0 +1,241 @@
+use glam::{IVec2, UVec2, Vec2, Vec3, Vec3Swizzles};
+
+use crate::{HeightRange, PatchKey, TerrainHeightField, TerrainScale, height_map_pixel_size, patch_quad_triangles};
+
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainHit {
+    pub position: Vec3,
+    pub normal: Vec3,
+    // Leaf patch that is rendered at the hit position.
+    pub key: PatchKey,
+    pub distance: f32,
+    // Set when the leaf was not resident and a coarser patch was intersected instead.
+    pub is_fallback: bool,
+}
+
+// Ray in rendered world space, the XZ part is also kept in unscaled patch space to walk the patch grids. Scaling is
+// affine so both share the same ray parameter.
+struct TerrainRay {
+    origin: Vec3,
+    dir: Vec3,
+    patch_origin: Vec2,
+    patch_dir: Vec2,
+    scale: TerrainScale,
+}
+
+impl TerrainRay {
+    // Entry and exit parameter of the ray in the XZ square `min..max` (patch space), clipped to `t_range`.
+    fn clip(&self, min: Vec2, max: Vec2, t_range: (f32, f32)) -> Option<(f32, f32)> {
+        let (mut t0, mut t1) = t_range;
+
+        for axis in 0..2 {
+            let (o, d) = (self.patch_origin[axis], self.patch_dir[axis]);
+
+            if d.abs() < 1e-12 {
+                if o < min[axis] || o > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let (a, b) = ((min[axis] - o) / d, (max[axis] - o) / d);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+
+        (t0 <= t1).then_some((t0, t1))
+    }
+
+    fn height_at(&self, t: f32) -> f32 {
+        self.origin.y + self.dir.y * t
+    }
+
+    // Whether the ray can touch heights in `range` between `t0` and `t1`.
+    fn overlaps(&self, t0: f32, t1: f32, range: HeightRange) -> bool {
+        let (y0, y1) = (self.height_at(t0), self.height_at(t1));
+        y0.min(y1) <= range.max * self.scale.height_scale && y0.max(y1) >= range.min * self.scale.height_scale
+    }
+
+    // Two sided Möller-Trumbore, returns the ray parameter and the upward facing normal.
+    fn intersect_triangle(&self, v: [Vec3; 3]) -> Option<(f32, Vec3)> {
+        let e1 = v[1] - v[0];
+        let e2 = v[2] - v[0];
+
+        let p = self.dir.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - v[0];
+        let u = s.dot(p) * inv_det;
+        if !(-1e-5..=1.0 + 1e-5).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let w = self.dir.dot(q) * inv_det;
+        if w < -1e-5 || u + w > 1.0 + 1e-5 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+        let normal = e1.cross(e2).normalize();
+
+        Some((t, if normal.y < 0.0 { -normal } else { normal }))
+    }
+}
+
+impl TerrainHeightField {
+    // `dir` does not need to be normalized, `distance` in the hit is measured in its units.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3, max_distance: f32, scale: &TerrainScale) -> Option<TerrainHit> {
+        let dir = dir.try_normalize()?;
+
+        let ray = TerrainRay {
+            origin,
+            dir,
+            patch_origin: origin.xz() / scale.world_scale,
+            patch_dir: dir.xz() / scale.world_scale,
+            scale: *scale,
+        };
+
+        let mut roots = self
+            .root_nodes()
+            .filter_map(|key| Some((*key, self.clip_node(&ray, key, (0.0, max_distance))?)))
+            .collect::<Vec<_>>();
+        roots.sort_unstable_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+
+        roots
+            .into_iter()
+            .find_map(|(key, t_range)| self.raycast_node(&ray, &key, t_range))
+    }
+
+    // Line of sight helper, hits between `from` and `to` only.
+    pub fn segment_cast(&self, from: Vec3, to: Vec3, scale: &TerrainScale) -> Option<TerrainHit> {
+        self.raycast(from, to - from, (to - from).length(), scale)
+    }
+
+    fn clip_node(&self, ray: &TerrainRay, key: &PatchKey, t_range: (f32, f32)) -> Option<(f32, f32)> {
+        let min = key.world_pos().as_vec2();
+        ray.clip(min, min + key.world_size() as f32, t_range)
+    }
+
+    fn raycast_node(&self, ray: &TerrainRay, key: &PatchKey, t_range: (f32, f32)) -> Option<TerrainHit> {
+        if self.is_leaf(key) {
+            return self.raycast_leaf(ray, key, t_range);
+        }
+
+        if key.lod_index == 0 || !self.is_tree_node(key) {
+            return None;
+        }
+
+        let lod_index = key.lod_index - 1;
+        let offset = 1 << lod_index;
+
+        // Children are disjoint, visiting them in entry order makes the first hit the closest one.
+        let mut children = [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]
+            .map(|o| PatchKey {
+                world_index: key.world_index + o * offset,
+                lod_index,
+            })
+            .into_iter()
+            .filter_map(|child| Some((child, self.clip_node(ray, &child, t_range)?)))
+            .collect::<Vec<_>>();
+        children.sort_unstable_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+
+        children
+            .into_iter()
+            .find_map(|(child, child_t_range)| self.raycast_node(ray, &child, child_t_range))
+    }
+
+    fn raycast_leaf(&self, ray: &TerrainRay, leaf: &PatchKey, t_range: (f32, f32)) -> Option<TerrainHit> {
+        // Same choice as the height queries: the leaf itself or the closest coarser patch that covers it.
+        let data_key = (leaf.lod_index..self.lod_count())
+            .map(|lod_index| PatchKey {
+                world_index: (leaf.world_index >> lod_index) << lod_index,
+                lod_index,
+            })
+            .find(|key| self.patch(key).is_some())?;
+
+        let height_ranges = self.patch_height_ranges(&data_key)?;
+        if !ray.overlaps(t_range.0, t_range.1, height_ranges.range()) {
+            return None;
+        }
+
+        let heights = self.patch(&data_key)?;
+        let patch_pos = data_key.world_pos().as_vec2();
+        let pixel_size = height_map_pixel_size(heights);
+        let pixel_world_size = data_key.world_size() as f32 / pixel_size as f32;
+
+        let vertex = |v: UVec2| {
+            let xz = (patch_pos + v.as_vec2() * pixel_world_size) * ray.scale.world_scale;
+            let h = heights[(v.y * (pixel_size + 1) + v.x) as usize] * ray.scale.height_scale;
+            Vec3::new(xz.x, h, xz.y)
+        };
+
+        // 2D DDA over the quads of the patch grid, starting at the cell the ray enters the leaf in.
+        let to_grid = |t: f32| (ray.patch_origin + ray.patch_dir * t - patch_pos) / pixel_world_size;
+        let grid_dir = ray.patch_dir / pixel_world_size;
+
+        let max_cell = pixel_size as i32 - 1;
+        let start = to_grid(t_range.0);
+        let mut cell = start.floor().as_ivec2().clamp(IVec2::ZERO, IVec2::splat(max_cell));
+
+        let step = IVec2::new(grid_dir.x.signum() as i32, grid_dir.y.signum() as i32);
+        let t_delta = Vec2::new(1.0 / grid_dir.x.abs(), 1.0 / grid_dir.y.abs());
+        let next_boundary = |c: i32, s: i32| if s > 0 { (c + 1) as f32 } else { c as f32 };
+        let mut t_max = Vec2::new(
+            if grid_dir.x == 0.0 {
+                f32::INFINITY
+            } else {
+                t_range.0 + (next_boundary(cell.x, step.x) - start.x) / grid_dir.x
+            },
+            if grid_dir.y == 0.0 {
+                f32::INFINITY
+            } else {
+                t_range.0 + (next_boundary(cell.y, step.y) - start.y) / grid_dir.y
+            },
+        );
+
+        let mut cell_t0 = t_range.0;
+
+        loop {
+            // Quads whose height range the ray passes above or below are skipped without triangle tests.
+            let cell_t1 = t_max.min_element().min(t_range.1);
+            let closest = ray
+                .overlaps(cell_t0, cell_t1, height_ranges.cell(0, cell))
+                .then(|| {
+                    patch_quad_triangles(cell.x as u32, cell.y as u32)
+                        .into_iter()
+                        .filter_map(|triangle| ray.intersect_triangle(triangle.map(vertex)))
+                        .filter(|&(t, _)| t >= 0.0 && t <= t_range.1 + 1e-4 && t >= t_range.0 - 1e-4)
+                        .min_by(|a, b| a.0.total_cmp(&b.0))
+                })
+                .flatten();
+
+            if let Some((t, normal)) = closest {
+                return Some(TerrainHit {
+                    position: ray.origin + ray.dir * t,
+                    normal,
+                    key: *leaf,
+                    distance: t,
+                    is_fallback: data_key != *leaf,
+                });
+            }
+
+            let axis = if t_max.x < t_max.y { 0 } else { 1 };
+            if t_max[axis] > t_range.1 {
+                return None;
+            }
+
+            cell_t0 = t_max[axis];
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+
+            if cell.cmplt(IVec2::ZERO).any() || cell.cmpgt(IVec2::splat(max_cell)).any() {
+                return None;
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::UVec2;
+
+use crate::{ATLAS_PATCH_COUNT, PatchKey, PatchState};
+
+pub struct AtlasResidency {
+    free_slots: Vec<UVec2>,
+    last_used_frames: HashMap<PatchKey, u64>,
+    evicted_count: u64,
+}
+
+impl AtlasResidency {
+    // `atlas_patch_count` patches per atlas side.
+    pub fn new(atlas_patch_count: u32) -> Self {
+        let mut free_slots = Vec::with_capacity((atlas_patch_count * atlas_patch_count) as usize);
+        for y in (0..atlas_patch_count).rev() {
+            for x in (0..atlas_patch_count).rev() {
+                free_slots.push(UVec2::new(x, y));
+            }
+        }
+
+        Self {
+            free_slots,
+            last_used_frames: HashMap::new(),
+            evicted_count: 0,
+        }
+    }
+
+    pub fn free_slot_count(&self) -> usize {
+        self.free_slots.len()
+    }
+
+    pub fn evicted_count(&self) -> u64 {
+        self.evicted_count
+    }
+
+    pub fn touch(&mut self, key: PatchKey, frame_index: u64) {
+        self.last_used_frames.insert(key, frame_index);
+    }
+
+    pub fn allocate(&mut self) -> Option<UVec2> {
+        self.free_slots.pop()
+    }
+
+    // Frees up to `required_count` slots by dropping the least recently used resident patches and returns them. Patches
+    // in the current leaf set and patches drawn by frames the GPU has not finished yet are never evicted.
+    pub fn evict(
+        &mut self,
+        patch_cache: &mut HashMap<PatchKey, PatchState>,
+        leaf_patches: &HashSet<PatchKey>,
+        gpu_frame_index: u64,
+        required_count: usize,
+    ) -> Vec<PatchKey> {
+        let missing_count = required_count.saturating_sub(self.free_slots.len());
+        if missing_count == 0 {
+            return Vec::new();
+        }
+
+        let mut candidates = patch_cache
+            .iter()
+            .filter_map(|(key, state)| {
+                let PatchState::Resident(atlas_index) = state else {
+                    return None;
+                };
+
+                let last_used_frame = self.last_used_frames.get(key).copied().unwrap_or(0);
+                if leaf_patches.contains(key) || last_used_frame > gpu_frame_index {
+                    return None;
+                }
+
+                Some((*key, *atlas_index, last_used_frame))
+            })
+            .collect::<Vec<_>>();
+
+        candidates.sort_unstable_by_key(|&(_, _, last_used_frame)| last_used_frame);
+        candidates.truncate(missing_count);
+
+        for &(key, atlas_index, _) in &candidates {
+            patch_cache.remove(&key);
+            self.last_used_frames.remove(&key);
+            self.free_slots.push(atlas_index);
+        }
+
+        self.evicted_count += candidates.len() as u64;
+
+        candidates.into_iter().map(|(key, _, _)| key).collect()
+    }
+}
+
+impl Default for AtlasResidency {
+    fn default() -> Self {
+        Self::new(ATLAS_PATCH_COUNT)
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::{IVec2, Vec2};
+
+use crate::{MAX_PATCH_LOD_COUNT, PATCH_WORLD_SIZE, PatchKey, StitchMask, height_map_pixel_size};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl PatchEdge {
+    pub const ALL: [PatchEdge; 4] = [PatchEdge::Top, PatchEdge::Bottom, PatchEdge::Left, PatchEdge::Right];
+
+    pub fn direction(self) -> IVec2 {
+        match self {
+            PatchEdge::Top => IVec2::NEG_Y,
+            PatchEdge::Bottom => IVec2::Y,
+            PatchEdge::Left => IVec2::NEG_X,
+            PatchEdge::Right => IVec2::X,
+        }
+    }
+
+    pub fn stitch_flag(self) -> StitchMask {
+        match self {
+            PatchEdge::Top => StitchMask::TOP,
+            PatchEdge::Bottom => StitchMask::BOTTOM,
+            PatchEdge::Left => StitchMask::LEFT,
+            PatchEdge::Right => StitchMask::RIGHT,
+        }
+    }
+
+    pub fn finer_flag(self) -> StitchMask {
+        match self {
+            PatchEdge::Top => StitchMask::FINER_TOP,
+            PatchEdge::Bottom => StitchMask::FINER_BOTTOM,
+            PatchEdge::Left => StitchMask::FINER_LEFT,
+            PatchEdge::Right => StitchMask::FINER_RIGHT,
+        }
+    }
+
+    pub fn opposite(self) -> PatchEdge {
+        match self {
+            PatchEdge::Top => PatchEdge::Bottom,
+            PatchEdge::Bottom => PatchEdge::Top,
+            PatchEdge::Left => PatchEdge::Right,
+            PatchEdge::Right => PatchEdge::Left,
+        }
+    }
+
+    // Pixel of the `i`th vertex along the edge, vertices run along +X for top/bottom and along +Y for left/right.
+    fn pixel(self, i: u32, pixel_size: u32) -> (u32, u32) {
+        match self {
+            PatchEdge::Top => (i, 0),
+            PatchEdge::Bottom => (i, pixel_size),
+            PatchEdge::Left => (0, i),
+            PatchEdge::Right => (pixel_size, i),
+        }
+    }
+
+    fn along(self) -> Vec2 {
+        match self {
+            PatchEdge::Top | PatchEdge::Bottom => Vec2::X,
+            PatchEdge::Left | PatchEdge::Right => Vec2::Y,
+        }
+    }
+}
+
+fn height_index(x: u32, y: u32, pixel_size: u32) -> usize {
+    (y * (pixel_size + 1) + x) as usize
+}
+
+// Finds the leaf covering `world_pos`, leafs never overlap so there is at most one candidate per LOD. Probes every LOD
+// any config can have.
+pub fn find_leaf_at(leafs: &HashSet<PatchKey>, world_pos: Vec2) -> Option<PatchKey> {
+    let world_index = (world_pos / PATCH_WORLD_SIZE as f32).floor().as_ivec2();
+
+    (0..MAX_PATCH_LOD_COUNT)
+        .map(|lod_index| PatchKey {
+            world_index: (world_index >> lod_index) << lod_index,
+            lod_index,
+        })
+        .find(|key| leafs.contains(key))
+}
+
+// Leaf across `edge` of `key`, the one touching the middle of that edge when the neighbour side is finer.
+pub fn neighbor_across(leafs: &HashSet<PatchKey>, key: &PatchKey, edge: PatchEdge) -> Option<PatchKey> {
+    let probe = key.world_center().as_vec2() + edge.direction().as_vec2() * (key.world_size() as f32 * 0.5 + 0.5);
+    find_leaf_at(leafs, probe)
+}
+
+// Same as the planner: only edges towards a coarser neighbour are stitched.
+pub fn stitch_mask(leafs: &HashSet<PatchKey>, key: &PatchKey) -> StitchMask {
+    let mut mask = StitchMask::empty();
+
+    for edge in PatchEdge::ALL {
+        if neighbor_across(leafs, key, edge).is_some_and(|n| n.lod_index > key.lod_index) {
+            mask.insert(edge.stitch_flag());
+        }
+    }
+
+    mask
+}
+
+// The other side of `stitch_mask`, edges whose neighbour stitches onto this leaf. Balancing keeps that neighbour one
+// LOD finer, so probing the middle of the edge finds it.
+pub fn finer_edge_mask(leafs: &HashSet<PatchKey>, key: &PatchKey) -> StitchMask {
+    let mut mask = StitchMask::empty();
+
+    for edge in PatchEdge::ALL {
+        if neighbor_across(leafs, key, edge).is_some_and(|n| n.lod_index < key.lod_index) {
+            mask.insert(edge.finer_flag());
+        }
+    }
+
+    mask
+}
+
+// World position and height of the `i`th edge vertex as the vertex shader places it.
+fn edge_vertex(key: &PatchKey, heights: &[f32], edge: PatchEdge, i: u32, stitched: bool) -> (Vec2, f32) {
+    let pixel_size = height_map_pixel_size(heights);
+    let i = if stitched { i / 2 * 2 } else { i };
+    let (x, y) = edge.pixel(i, pixel_size);
+
+    let pixel_world_size = key.world_size() as f32 / pixel_size as f32;
+    let world_pos = key.world_pos().as_vec2() + Vec2::new(x as f32, y as f32) * pixel_world_size;
+
+    (world_pos, heights[height_index(x, y, pixel_size)])
+}
+
+// Height of the neighbour's edge polyline at `world_pos`, which has to lie on that edge.
+fn edge_height_at(key: &PatchKey, heights: &[f32], edge: PatchEdge, world_pos: Vec2) -> f32 {
+    let pixel_size = height_map_pixel_size(heights);
+    let pixel_world_size = key.world_size() as f32 / pixel_size as f32;
+    let t = ((world_pos - key.world_pos().as_vec2()) / pixel_world_size)
+        .dot(edge.along())
+        .clamp(0.0, pixel_size as f32);
+
+    let i0 = (t.floor() as u32).min(pixel_size - 1);
+    let frac = t - i0 as f32;
+
+    let (x0, y0) = edge.pixel(i0, pixel_size);
+    let (x1, y1) = edge.pixel(i0 + 1, pixel_size);
+
+    heights[height_index(x0, y0, pixel_size)] * (1.0 - frac) + heights[height_index(x1, y1, pixel_size)] * frac
+}
+
+#[derive(Clone, Debug)]
+pub struct EdgeCrack {
+    pub patch: PatchKey,
+    pub neighbor: PatchKey,
+    pub edge: PatchEdge,
+    pub max_delta: f32,
+    pub mean_delta: f32,
+}
+
+impl EdgeCrack {
+    pub fn lod_delta(&self) -> u32 {
+        self.neighbor.lod_index - self.patch.lod_index
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SeamReport {
+    pub edges: Vec<EdgeCrack>,
+    pub max_delta: f32,
+    pub mean_delta: f32,
+    pub missing_patches: usize,
+}
+
+impl SeamReport {
+    pub fn worst_edge(&self) -> Option<&EdgeCrack> {
+        self.edges.iter().max_by(|a, b| a.max_delta.total_cmp(&b.max_delta))
+    }
+}
+
+// Measures the height difference along every edge shared by two leafs. Every edge is measured once from its finer
+// (or, for equal LODs, its top/left) side against the polyline of the other side, with the fine side stitched like the
+// vertex shader does when `stitching` is set. Leafs without height data are skipped and counted.
+pub fn measure_seams(leafs: &[PatchKey], height_maps: &HashMap<PatchKey, Vec<f32>>, stitching: bool) -> SeamReport {
+    let leaf_set = leafs.iter().copied().collect::<HashSet<_>>();
+
+    let mut report = SeamReport::default();
+    let mut delta_sum = 0.0_f64;
+    let mut sample_count = 0_usize;
+
+    for key in leafs {
+        let Some(heights) = height_maps.get(key) else {
+            report.missing_patches += 1;
+            continue;
+        };
+
+        for edge in PatchEdge::ALL {
+            let Some(neighbor) = neighbor_across(&leaf_set, key, edge) else {
+                continue;
+            };
+
+            let is_measured_side = match neighbor.lod_index.cmp(&key.lod_index) {
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Equal => matches!(edge, PatchEdge::Bottom | PatchEdge::Right),
+                std::cmp::Ordering::Less => false,
+            };
+            if !is_measured_side {
+                continue;
+            }
+
+            let Some(neighbor_heights) = height_maps.get(&neighbor) else {
+                continue;
+            };
+
+            let stitched = stitching && neighbor.lod_index > key.lod_index;
+
+            let deltas = (0..=height_map_pixel_size(heights))
+                .map(|i| {
+                    let (world_pos, height) = edge_vertex(key, heights, edge, i, stitched);
+                    let neighbor_height = edge_height_at(&neighbor, neighbor_heights, edge.opposite(), world_pos);
+
+                    (height - neighbor_height).abs()
+                })
+                .collect::<Vec<_>>();
+
+            let max_delta = deltas.iter().copied().fold(0.0, f32::max);
+            let edge_sum = deltas.iter().map(|&d| d as f64).sum::<f64>();
+
+            report.max_delta = report.max_delta.max(max_delta);
+            delta_sum += edge_sum;
+            sample_count += deltas.len();
+
+            report.edges.push(EdgeCrack {
+                patch: *key,
+                neighbor,
+                edge,
+                max_delta,
+                mean_delta: (edge_sum / deltas.len() as f64) as f32,
+            });
+        }
+    }
+
+    if sample_count > 0 {
+        report.mean_delta = (delta_sum / sample_count as f64) as f32;
+    }
+
+    report
+}
+
+// Overwrites patch borders so neighbours agree exactly: equal LOD edges are averaged, finer edges take the heights of
+// the coarser polyline. LODs are fixed coarse to fine so their edges are final before finer patches copy them.
+pub fn fix_seams(leafs: &[PatchKey], height_maps: &mut HashMap<PatchKey, Vec<f32>>) {
+    let leaf_set = leafs.iter().copied().collect::<HashSet<_>>();
+
+    let mut lod_indices = leafs.iter().map(|key| key.lod_index).collect::<Vec<_>>();
+    lod_indices.sort_unstable_by_key(|&lod_index| std::cmp::Reverse(lod_index));
+    lod_indices.dedup();
+
+    for lod_index in lod_indices {
+        let level = leafs
+            .iter()
+            .filter(|key| key.lod_index == lod_index && height_maps.contains_key(key))
+            .copied()
+            .collect::<Vec<_>>();
+
+        // Up to four leafs share a corner, averaging edge by edge would leave each with a different value.
+        let corners = corner_averages(&level, height_maps);
+
+        for key in &level {
+            for edge in [PatchEdge::Bottom, PatchEdge::Right] {
+                if let Some(neighbor) = neighbor_across(&leaf_set, key, edge).filter(|n| n.lod_index == key.lod_index) {
+                    average_edge(key, &neighbor, edge, height_maps);
+                }
+            }
+        }
+
+        for key in &level {
+            let heights = height_maps.get_mut(key).unwrap();
+            let pixel_size = height_map_pixel_size(heights);
+
+            for (corner, x, y) in patch_corners(key, pixel_size) {
+                heights[height_index(x, y, pixel_size)] = corners[&corner];
+            }
+        }
+
+        for key in &level {
+            for edge in PatchEdge::ALL {
+                if let Some(neighbor) = neighbor_across(&leaf_set, key, edge).filter(|n| n.lod_index > key.lod_index) {
+                    snap_edge(key, &neighbor, edge, height_maps);
+                }
+            }
+        }
+    }
+}
+
+// World positions of the corners of a patch with their pixels.
+fn patch_corners(key: &PatchKey, pixel_size: u32) -> [(IVec2, u32, u32); 4] {
+    let size = key.world_size() as i32;
+    let pos = key.world_pos();
+
+    [
+        (pos, 0, 0),
+        (pos + IVec2::new(size, 0), pixel_size, 0),
+        (pos + IVec2::new(0, size), 0, pixel_size),
+        (pos + IVec2::splat(size), pixel_size, pixel_size),
+    ]
+}
+
+// Mean height of every corner over all `leafs` touching it, which have to share one LOD.
+fn corner_averages(leafs: &[PatchKey], height_maps: &HashMap<PatchKey, Vec<f32>>) -> HashMap<IVec2, f32> {
+    let mut sums = HashMap::<IVec2, (f32, u32)>::new();
+
+    for key in leafs {
+        let heights = &height_maps[key];
+        let pixel_size = height_map_pixel_size(heights);
+
+        for (corner, x, y) in patch_corners(key, pixel_size) {
+            let sum = sums.entry(corner).or_default();
+            sum.0 += heights[height_index(x, y, pixel_size)];
+            sum.1 += 1;
+        }
+    }
+
+    sums.into_iter()
+        .map(|(corner, (sum, count))| (corner, sum / count as f32))
+        .collect()
+}
+
+fn average_edge(key: &PatchKey, neighbor: &PatchKey, edge: PatchEdge, height_maps: &mut HashMap<PatchKey, Vec<f32>>) {
+    let (Some(heights), Some(neighbor_heights)) = (height_maps.get(key), height_maps.get(neighbor)) else {
+        return;
+    };
+
+    let pixel_size = height_map_pixel_size(heights);
+    let averaged = (0..=pixel_size)
+        .map(|i| {
+            let (x, y) = edge.pixel(i, pixel_size);
+            let (nx, ny) = edge.opposite().pixel(i, pixel_size);
+
+            (heights[height_index(x, y, pixel_size)] + neighbor_heights[height_index(nx, ny, pixel_size)]) * 0.5
+        })
+        .collect::<Vec<_>>();
+
+    for (patch, patch_edge) in [(*key, edge), (*neighbor, edge.opposite())] {
+        let heights = height_maps.get_mut(&patch).unwrap();
+        for (i, &h) in averaged.iter().enumerate() {
+            let (x, y) = patch_edge.pixel(i as u32, pixel_size);
+            heights[height_index(x, y, pixel_size)] = h;
+        }
+    }
+}
+
+// Moves the edge of `key` onto the polyline of its coarser `neighbor`.
+fn snap_edge(key: &PatchKey, neighbor: &PatchKey, edge: PatchEdge, height_maps: &mut HashMap<PatchKey, Vec<f32>>) {
+    let (Some(heights), Some(neighbor_heights)) = (height_maps.get(key), height_maps.get(neighbor)) else {
+        return;
+    };
+
+    let pixel_size = height_map_pixel_size(heights);
+    let snapped = (0..=pixel_size)
+        .map(|i| {
+            let (world_pos, _) = edge_vertex(key, heights, edge, i, false);
+            edge_height_at(neighbor, neighbor_heights, edge.opposite(), world_pos)
+        })
+        .collect::<Vec<_>>();
+
+    let heights = height_maps.get_mut(key).unwrap();
+    for (i, &h) in snapped.iter().enumerate() {
+        let (x, y) = edge.pixel(i as u32, pixel_size);
+        heights[height_index(x, y, pixel_size)] = h;
+    }
+}
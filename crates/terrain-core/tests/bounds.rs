@@ -0,0 +1,146 @@
+mod common;
+
+use glam::{IVec2, Vec3};
+use terrain_core::*;
+
+use common::key;
+
+#[test]
+fn pyramid_levels_bound_their_quads() {
+    let heights = (0..ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE)
+        .map(|i| {
+            let (x, y) = (i % ATLAS_PATCH_PIXEL_SIZE, i / ATLAS_PATCH_PIXEL_SIZE);
+            ((x * 7 + y * 13) % 31) as f32 / 31.0
+        })
+        .collect::<Vec<_>>();
+
+    let pyramid = HeightRangePyramid::new(&heights);
+    let heights = &heights;
+    assert_eq!(pyramid.level_count(), PATCH_PIXEL_SIZE.ilog2() + 1);
+    assert_eq!(pyramid.range(), HeightRange::from_heights(heights));
+
+    for level in 0..pyramid.level_count() {
+        let cell_size = PATCH_PIXEL_SIZE / pyramid.level_size(level);
+
+        for cell_y in 0..pyramid.level_size(level) {
+            for cell_x in 0..pyramid.level_size(level) {
+                let covered = (cell_y * cell_size..=(cell_y + 1) * cell_size)
+                    .flat_map(|y| {
+                        (cell_x * cell_size..=(cell_x + 1) * cell_size)
+                            .map(move |x| heights[(y * ATLAS_PATCH_PIXEL_SIZE + x) as usize])
+                    })
+                    .collect::<Vec<_>>();
+
+                let cell = IVec2::new(cell_x as i32, cell_y as i32);
+                assert_eq!(pyramid.cell(level, cell), HeightRange::from_heights(&covered));
+            }
+        }
+    }
+}
+
+#[test]
+fn tree_aggregates_children() {
+    let mut bounds = PatchBoundsTree::new();
+    let range = |min, max| HeightRange { min, max };
+
+    bounds.insert(key(0, 0, 0), range(0.1, 0.2));
+    bounds.insert(key(1, 0, 0), range(0.3, 0.4));
+    bounds.insert(key(0, 1, 0), range(0.0, 0.2));
+    assert_eq!(bounds.range(&key(0, 0, 1)), None);
+
+    bounds.insert(key(1, 1, 0), range(0.2, 0.6));
+    assert_eq!(bounds.range(&key(0, 0, 1)), Some(range(0.0, 0.6)));
+
+    // The parent's own patch adds to its children.
+    bounds.insert(key(0, 0, 1), range(0.05, 0.5));
+    assert_eq!(bounds.range(&key(0, 0, 1)), Some(range(0.0, 0.6)));
+
+    let (min, max) = bounds.aabb(&key(-2, 2, 1), &TerrainScale::default());
+    assert_eq!(min, Vec3::new(-128.0, 0.0, 128.0));
+    assert_eq!(max, Vec3::new(0.0, 100.0, 256.0));
+}
+
+#[test]
+fn ancestors_contain_finer_peaks() {
+    let mut bounds = PatchBoundsTree::new();
+    let range = |min, max| HeightRange { min, max };
+
+    bounds.insert(key(0, 0, 2), range(0.2, 0.3));
+    bounds.insert(key(0, 0, 3), range(0.2, 0.3));
+    assert_eq!(bounds.range(&key(0, 0, 2)), Some(range(0.2, 0.3)));
+
+    // A single fine peak the coarse patches smoothed away, its parent is not covered but the ancestors are.
+    bounds.insert(key(1, 2, 0), range(0.25, 0.9));
+    assert_eq!(bounds.range(&key(0, 2, 1)), None);
+    assert_eq!(bounds.range(&key(0, 0, 2)), Some(range(0.2, 0.9)));
+    assert_eq!(bounds.range(&key(0, 0, 3)), Some(range(0.2, 0.9)));
+    assert_eq!(bounds.range(&key(0, 0, 4)), None);
+
+    bounds.remove(&key(1, 2, 0));
+    assert_eq!(bounds.range(&key(0, 0, 2)), Some(range(0.2, 0.3)));
+    assert_eq!(bounds.range(&key(0, 0, 3)), Some(range(0.2, 0.3)));
+
+    // Removing everything leaves nothing behind.
+    bounds.remove(&key(0, 0, 2));
+    bounds.remove(&key(0, 0, 3));
+    assert!(bounds.is_empty());
+    assert_eq!(bounds.range(&key(0, 0, 3)), None);
+}
+
+#[test]
+fn revisions_track_changed_subtrees() {
+    let mut bounds = PatchBoundsTree::new();
+    let range = HeightRange { min: 0.1, max: 0.2 };
+
+    bounds.insert(key(5, 3, 0), range);
+    let revision = bounds.revision();
+    assert_eq!(bounds.subtree_revision(&key(5, 3, 0)), revision);
+    assert_eq!(bounds.subtree_revision(&key(4, 0, 2)), revision);
+    assert_eq!(bounds.subtree_revision(&key(0, 0, 2)), 0);
+
+    // Nothing changes, nothing is invalidated.
+    bounds.insert(key(5, 3, 0), range);
+    bounds.remove(&key(9, 9, 0));
+    assert_eq!(bounds.revision(), revision);
+
+    bounds.insert_decimation_error(key(0, 0, 0), 0.1);
+    assert_eq!(bounds.subtree_revision(&key(0, 0, 2)), bounds.revision());
+    assert_eq!(bounds.subtree_revision(&key(4, 0, 2)), revision);
+
+    // A removed patch stays changed for whoever saw it before.
+    bounds.remove(&key(5, 3, 0));
+    assert!(bounds.subtree_revision(&key(5, 3, 0)) > revision);
+}
+
+#[test]
+fn lod_selection_uses_height_bounds() {
+    let render_distance = 1024;
+    let cam_pos = Vec3::new(10.0, 250.0, 20.0);
+    let scale = TerrainScale::default();
+    let lod_policy = LodPolicy::Distance { lod_factor: 3.0 };
+
+    let unknown =
+        PatchQuadTree::new(&cam_pos, render_distance, &lod_policy, &PatchBoundsTree::new(), &scale).collect_leafs();
+
+    // Low flat terrain everywhere pushes every box away from the camera, only the finest LOD is inserted and the
+    // coarser nodes are aggregated from it.
+    let mut flat = PatchBoundsTree::new();
+    let extent = (render_distance * 2 / PATCH_WORLD_SIZE) as i32;
+    for y in -extent..extent {
+        for x in -extent..extent {
+            flat.insert(key(x, y, 0), HeightRange { min: 0.0, max: 0.01 });
+        }
+    }
+
+    let flat_leafs = PatchQuadTree::new(&cam_pos, render_distance, &lod_policy, &flat, &scale).collect_leafs();
+    assert!(flat_leafs.len() < unknown.len());
+    assert!(flat_leafs.iter().map(|l| l.lod_index).min() > unknown.iter().map(|l| l.lod_index).min());
+
+    // Mountains as high as the camera pull the boxes back in.
+    let mut high = PatchBoundsTree::new();
+    for leaf in &unknown {
+        high.insert(*leaf, HeightRange { min: 0.0, max: 3.0 });
+    }
+    let high_leafs = PatchQuadTree::new(&cam_pos, render_distance, &lod_policy, &high, &scale).collect_leafs();
+    assert!(high_leafs.len() >= unknown.len());
+}
@@ -0,0 +1,43 @@
+// Helpers shared by the integration tests, not every test uses all of them.
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use glam::IVec2;
+use terrain_core::{HeightRangePyramid, PatchKey, TerrainHeightField};
+
+// Small xorshift so randomized tests are the same on every run.
+pub struct Rng(pub u64);
+
+impl Rng {
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        min + ((max - min) as f32 * self.next_f32()) as i32
+    }
+
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[((self.next_f32() * items.len() as f32) as usize).min(items.len() - 1)]
+    }
+}
+
+pub fn key(x: i32, y: i32, lod_index: u32) -> PatchKey {
+    PatchKey {
+        world_index: IVec2::new(x, y),
+        lod_index,
+    }
+}
+
+pub fn insert_patch(height_field: &mut TerrainHeightField, key: PatchKey, heights: Arc<[f32]>) {
+    let height_ranges = HeightRangePyramid::new(&heights);
+    height_field.insert(key, heights, height_ranges);
+}
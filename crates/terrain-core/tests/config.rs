@@ -0,0 +1,278 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use glam::{Vec3, Vec3Swizzles};
+use terrain_core::*;
+
+use common::Rng;
+
+fn small_config() -> TerrainConfig {
+    TerrainConfig {
+        lod_count: 3,
+        patch_pixel_size: 16,
+        atlas_patch_count: 16,
+        indirection_slot_count: 32,
+    }
+}
+
+#[test]
+fn default_config_is_valid() {
+    let config = TerrainConfig::default();
+
+    assert_eq!(config.validate(2048), Ok(()));
+    assert_eq!(config.atlas_size(), ATLAS_SIZE);
+    assert_eq!(
+        config.height_map_len(),
+        (ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE) as usize
+    );
+    assert_eq!(small_config().validate(small_config().max_render_distance()), Ok(()));
+}
+
+#[test]
+fn invalid_configs_are_rejected() {
+    let config = TerrainConfig::default();
+    let render_distance = 1024;
+
+    let check = |config: TerrainConfig| config.validate(render_distance).unwrap_err();
+
+    assert_eq!(
+        check(TerrainConfig { lod_count: 0, ..config }),
+        TerrainConfigError::LodCount(0)
+    );
+    assert_eq!(
+        check(TerrainConfig {
+            lod_count: MAX_PATCH_LOD_COUNT + 1,
+            ..config
+        }),
+        TerrainConfigError::LodCount(MAX_PATCH_LOD_COUNT + 1)
+    );
+    assert_eq!(
+        check(TerrainConfig {
+            patch_pixel_size: 96,
+            ..config
+        }),
+        TerrainConfigError::PatchPixelSize(96)
+    );
+    assert_eq!(
+        check(TerrainConfig {
+            patch_pixel_size: 1,
+            ..config
+        }),
+        TerrainConfigError::PatchPixelSize(1)
+    );
+    assert_eq!(
+        check(TerrainConfig {
+            atlas_patch_count: 128,
+            ..config
+        }),
+        TerrainConfigError::AtlasSize(129 * 128)
+    );
+    assert_eq!(
+        check(TerrainConfig {
+            indirection_slot_count: 100,
+            ..config
+        }),
+        TerrainConfigError::IndirectionSlotCount(100)
+    );
+    // The coarsest LOD would be left with a single slot.
+    assert_eq!(
+        check(TerrainConfig {
+            indirection_slot_count: 16,
+            ..config
+        }),
+        TerrainConfigError::IndirectionSlotCount(16)
+    );
+
+    let max_render_distance = config.max_render_distance();
+    assert_eq!(
+        config.validate(max_render_distance + 1),
+        Err(TerrainConfigError::RenderDistance {
+            render_distance: max_render_distance + 1,
+            min_render_distance: config.min_render_distance(),
+            max_render_distance,
+        })
+    );
+}
+
+#[test]
+fn leafs_fit_into_indirection_at_max_render_distance() {
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    let scale = TerrainScale::default();
+    let lod_policy = LodPolicy::Distance { lod_factor: 3.0 };
+
+    for lod_count in 1..=MAX_PATCH_LOD_COUNT {
+        for indirection_slot_count in [4 << lod_count, 8 << lod_count] {
+            let config = TerrainConfig {
+                lod_count,
+                indirection_slot_count,
+                ..TerrainConfig::default()
+            };
+            if config.validate(config.min_render_distance()).is_err() {
+                continue;
+            }
+
+            let render_distance = config.max_render_distance();
+            assert_eq!(config.validate(render_distance), Ok(()));
+
+            for _ in 0..8 {
+                let cam_pos = Vec3::new(
+                    rng.range(-5000.0, 5000.0),
+                    rng.range(0.0, 300.0),
+                    rng.range(-5000.0, 5000.0),
+                );
+                let cam_world_index = cam_pos.xz().as_ivec2() / PATCH_WORLD_SIZE as i32;
+
+                let mut tree = PatchQuadTree::with_hysteresis(0.0).with_lod_count(lod_count);
+                tree.update(&cam_pos, render_distance, &lod_policy, &PatchBoundsTree::new(), &scale);
+
+                for leaf in tree.leafs() {
+                    assert!(leaf.lod_index < lod_count);
+                    assert!(
+                        config.indirection_slot(leaf, cam_world_index).is_some(),
+                        "{config:?} {cam_pos} {leaf:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn planner_follows_config() {
+    let config = small_config();
+    let camera_pos = Vec3::new(0.0, 100.0, 0.0);
+    let mut planner = TerrainPlanner::new(
+        config,
+        config.max_render_distance(),
+        LodPolicy::Distance { lod_factor: 3.0 },
+        Arc::new(FbmHeightSource::default()),
+        None,
+    );
+
+    let start = Instant::now();
+    let mut frame_index = 0;
+
+    let plan = loop {
+        frame_index += 1;
+
+        let plan = planner.plan_frame(&camera_pos, None, frame_index, frame_index - 1);
+        if plan.patches.len() == planner.leaf_patches().len() {
+            break plan;
+        }
+
+        assert!(start.elapsed() < Duration::from_secs(60), "patch generation timed out");
+        std::thread::sleep(Duration::from_millis(1));
+    };
+
+    assert!(
+        planner
+            .leaf_patches()
+            .iter()
+            .any(|l| l.lod_index == config.lod_count - 1)
+    );
+    assert_eq!(planner.morph_ranges().len(), config.lod_count as usize);
+    assert_eq!(planner.indirection().lod_count(), config.lod_count);
+    assert!(plan.patches.len() as u32 <= planner.coverage().unwrap().patch_capacity);
+
+    for patch in &plan.patches {
+        assert!(patch.key.lod_index < config.lod_count);
+        assert_eq!(
+            planner.height_field().patch(&patch.key).unwrap().len(),
+            config.height_map_len()
+        );
+
+        let lod_index = patch.key.lod_index;
+        let slot = config.indirection_slot(&patch.key, planner.cam_world_index()).unwrap();
+        let slot_count = planner.indirection().slot_count(lod_index);
+
+        let atlas_index = planner.indirection().lod(lod_index)[(slot.y * slot_count + slot.x) as usize];
+        assert!(atlas_index.cmplt(glam::UVec2::splat(config.atlas_patch_count)).all());
+    }
+}
+
+#[test]
+fn max_leaf_count_bounds_moving_camera() {
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+
+    for _ in 0..24 {
+        let config = TerrainConfig {
+            lod_count: 1 + (rng.next_f32() * MAX_PATCH_LOD_COUNT as f32) as u32,
+            ..TerrainConfig::default()
+        };
+        let render_distance = rng.range(32.0, 4096.0) as u32;
+        let lod_policy = LodPolicy::Distance {
+            lod_factor: rng.range(0.5, 6.0),
+        };
+        let scale = TerrainScale {
+            world_scale: rng.range(0.5, 2.0),
+            ..TerrainScale::default()
+        };
+
+        let max_leaf_count = config.max_leaf_count(render_distance, &lod_policy, LOD_HYSTERESIS, &scale);
+        let mut tree = PatchQuadTree::with_hysteresis(LOD_HYSTERESIS).with_lod_count(config.lod_count);
+        let mut cam_pos = Vec3::new(rng.range(-5000.0, 5000.0), 50.0, rng.range(-5000.0, 5000.0));
+
+        for _ in 0..16 {
+            cam_pos += Vec3::new(rng.range(-100.0, 100.0), 0.0, rng.range(-100.0, 100.0));
+            tree.update(&cam_pos, render_distance, &lod_policy, &PatchBoundsTree::new(), &scale);
+
+            assert!(
+                tree.leafs().len() as u32 <= max_leaf_count,
+                "{config:?} {render_distance} {lod_policy:?} {scale:?}: {} > {max_leaf_count}",
+                tree.leafs().len()
+            );
+        }
+    }
+}
+
+#[test]
+fn coverage_grows_indirection_up_to_limit() {
+    let config = small_config();
+    let lod_policy = LodPolicy::Distance { lod_factor: 3.0 };
+    let scale = TerrainScale::default();
+
+    let coverage = |render_distance| config.coverage(render_distance, &lod_policy, LOD_HYSTERESIS, &scale);
+
+    let fitting = coverage(config.max_render_distance()).unwrap();
+    assert_eq!(fitting.indirection_slot_count, config.indirection_slot_count);
+    assert!(fitting.patch_capacity <= fitting.max_leaf_count);
+    assert!(fitting.patch_capacity <= config.atlas_patch_count.pow(2));
+
+    let grown = coverage(config.max_render_distance() * 4).unwrap();
+    assert!(grown.indirection_slot_count > config.indirection_slot_count);
+    assert!(grown.max_leaf_count > fitting.max_leaf_count);
+
+    let grown_config = TerrainConfig {
+        indirection_slot_count: grown.indirection_slot_count,
+        ..config
+    };
+    assert!(grown_config.max_render_distance() >= grown.render_distance);
+    assert_eq!(grown_config.validate(grown.render_distance), Ok(()));
+
+    // Only the smallest power of two that reaches the render distance.
+    let smaller_config = TerrainConfig {
+        indirection_slot_count: grown.indirection_slot_count / 2,
+        ..config
+    };
+    assert!(smaller_config.max_render_distance() < grown.render_distance);
+
+    for render_distance in [0, config.min_render_distance() - 1, u32::MAX / 4] {
+        let clamped = config.clamp_render_distance(render_distance);
+        assert!(matches!(
+            coverage(render_distance),
+            Err(TerrainConfigError::RenderDistance { .. })
+        ));
+        assert!(coverage(clamped).unwrap().indirection_slot_count <= MAX_INDIRECTION_SLOT_COUNT);
+    }
+
+    let screen_space_error = LodPolicy::ScreenSpaceError {
+        max_pixel_error: 2.0,
+        projection_scale: 1000.0,
+        fallback_lod_factor: 3.0,
+    };
+    let render_distance = 2048;
+    let max_leaf_count = config.max_leaf_count(render_distance, &screen_space_error, LOD_HYSTERESIS, &scale);
+    assert_eq!(max_leaf_count, (render_distance * 2 / PATCH_WORLD_SIZE).pow(2));
+}
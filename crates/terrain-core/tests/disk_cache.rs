@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+
+use glam::IVec2;
+use terrain_core::*;
+
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("terrain-core-disk-cache-{}-{}", name, std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+
+    dir
+}
+
+fn key(x: i32) -> PatchKey {
+    PatchKey {
+        world_index: IVec2::new(x, -3),
+        lod_index: 2,
+    }
+}
+
+fn height_map(seed: f32) -> Vec<f32> {
+    (0..ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE)
+        .map(|i| (i as f32 * 0.01 + seed).sin() * 0.5 + 0.5)
+        .collect()
+}
+
+fn patch_files(dir: &PathBuf) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "patch"))
+        .collect()
+}
+
+#[test]
+fn round_trips_patches() {
+    let dir = cache_dir("round-trip");
+    let cache = DiskPatchCache::new(&dir, 42, u64::MAX).unwrap();
+
+    assert!(cache.load(&key(0), PATCH_PIXEL_SIZE).unwrap().is_none());
+
+    cache.store(&key(0), &height_map(1.0)).unwrap();
+    assert_eq!(cache.load(&key(0), PATCH_PIXEL_SIZE).unwrap(), Some(height_map(1.0)));
+    assert!(cache.load(&key(1), PATCH_PIXEL_SIZE).unwrap().is_none());
+
+    // Compression should at least beat the raw float data.
+    assert!(cache.total_bytes() < (ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE * 4) as u64);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.writes), (1, 2, 1));
+
+    // A different source hash must not see the patches of another source.
+    let other_cache = DiskPatchCache::new(&dir, 43, u64::MAX).unwrap();
+    assert!(other_cache.load(&key(0), PATCH_PIXEL_SIZE).unwrap().is_none());
+}
+
+#[test]
+fn detects_corruption() {
+    let dir = cache_dir("corruption");
+    let cache = DiskPatchCache::new(&dir, 42, u64::MAX).unwrap();
+
+    cache.store(&key(0), &height_map(1.0)).unwrap();
+
+    let path = patch_files(&dir).pop().unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = bytes.len() - 10;
+    bytes[last] ^= 0xff;
+    std::fs::write(&path, bytes).unwrap();
+
+    assert!(cache.load(&key(0), PATCH_PIXEL_SIZE).unwrap().is_none());
+    assert_eq!(cache.stats().corrupted, 1);
+    assert!(!path.exists());
+}
+
+#[test]
+fn trims_to_size_limit() {
+    let dir = cache_dir("size-limit");
+
+    let patch_size = {
+        let cache = DiskPatchCache::new(&dir, 42, u64::MAX).unwrap();
+        cache.store(&key(0), &height_map(0.0)).unwrap();
+        cache.total_bytes()
+    };
+
+    let cache = DiskPatchCache::new(&dir, 42, patch_size * 4).unwrap();
+    assert_eq!(cache.total_bytes(), patch_size);
+
+    for x in 1..10 {
+        cache.store(&key(x), &height_map(0.0)).unwrap();
+        assert!(cache.total_bytes() <= patch_size * 4);
+    }
+
+    assert!(cache.stats().evicted > 0);
+    assert_eq!(patch_files(&dir).len() as u64 * patch_size, cache.total_bytes());
+    assert!(cache.load(&key(9), PATCH_PIXEL_SIZE).unwrap().is_some());
+}
+
+#[test]
+fn rejects_oversized_payloads() {
+    let dir = cache_dir("oversized");
+    let cache = DiskPatchCache::new(&dir, 42, u64::MAX).unwrap();
+
+    cache.store(&key(0), &height_map(1.0)).unwrap();
+
+    // Keeps the valid header but swaps in a payload that inflates to far more than a patch.
+    let path = patch_files(&dir).pop().unwrap();
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.truncate(40);
+
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+    std::io::Write::write_all(&mut encoder, &vec![0; 16 << 20]).unwrap();
+    let payload = encoder.finish().unwrap();
+    bytes[36..40].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend(payload);
+    std::fs::write(&path, bytes).unwrap();
+
+    assert!(cache.load(&key(0), PATCH_PIXEL_SIZE).unwrap().is_none());
+    assert_eq!(cache.stats().corrupted, 1);
+}
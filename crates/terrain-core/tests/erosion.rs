@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use glam::{IVec2, UVec2};
+use terrain_core::*;
+
+fn test_params() -> ErosionParams {
+    ErosionParams {
+        iterations: 2,
+        thermal_iterations: 8,
+        ..Default::default()
+    }
+}
+
+fn cone(size: UVec2) -> Vec<f32> {
+    let center = size.as_vec2() * 0.5;
+
+    (0..size.x * size.y)
+        .map(|i| {
+            let p = UVec2::new(i % size.x, i / size.x).as_vec2();
+            (1.0 - p.distance(center) / center.x).max(0.0)
+        })
+        .collect()
+}
+
+#[test]
+fn erosion_is_deterministic_and_changes_terrain() {
+    let size = UVec2::splat(64);
+    let original = cone(size);
+
+    let mut a = original.clone();
+    let mut b = original.clone();
+    erode_height_map(&mut a, size, IVec2::ZERO, 1.0, &test_params());
+    erode_height_map(&mut b, size, IVec2::ZERO, 1.0, &test_params());
+
+    assert_eq!(a, b);
+    assert_ne!(a, original);
+    assert!(a.iter().all(|h| h.is_finite()));
+
+    // Erosion moves material around and loses some at the borders, it must never create noticeable mass.
+    let mass = |h: &[f32]| h.iter().sum::<f32>();
+    assert!(mass(&a) <= mass(&original) * 1.001);
+}
+
+#[test]
+fn thermal_erosion_limits_slopes() {
+    let size = UVec2::new(32, 2);
+    let mut heights = (0..size.x * size.y)
+        .map(|i| if i % size.x < 16 { 1.0 } else { 0.0 })
+        .collect::<Vec<_>>();
+
+    let params = ErosionParams {
+        iterations: 0,
+        thermal_iterations: 2000,
+        talus_slope: 0.1,
+        thermal_rate: 1.0,
+        ..Default::default()
+    };
+    erode_height_map(&mut heights, size, IVec2::ZERO, 1.0, &params);
+
+    let max_slope = heights[..size.x as usize]
+        .windows(2)
+        .map(|w| (w[0] - w[1]).abs())
+        .fold(0.0, f32::max);
+    assert!(max_slope < 0.11, "max slope {}", max_slope);
+}
+
+// Largest height difference along the right and bottom edge of `key` to its neighbours there.
+fn max_seam_diff(source: &ErosionHeightSource, key: PatchKey, pixel_size: u32) -> f32 {
+    let step = 1 << key.lod_index;
+    let right = PatchKey {
+        world_index: key.world_index + IVec2::X * step,
+        ..key
+    };
+    let bottom = PatchKey {
+        world_index: key.world_index + IVec2::Y * step,
+        ..key
+    };
+
+    let patch = source.sample_patch(&key, pixel_size);
+    let right_patch = source.sample_patch(&right, pixel_size);
+    let bottom_patch = source.sample_patch(&bottom, pixel_size);
+
+    let row = pixel_size as usize + 1;
+    let edge = pixel_size as usize;
+
+    let mut max_diff = 0.0_f32;
+    for i in 0..row {
+        max_diff = max_diff.max((patch[i * row + edge] - right_patch[i * row]).abs());
+        max_diff = max_diff.max((patch[edge * row + i] - bottom_patch[i]).abs());
+    }
+
+    max_diff
+}
+
+#[test]
+fn eroded_patches_stay_seamless() {
+    let source = ErosionHeightSource::new(Arc::new(FbmHeightSource::default()), test_params());
+
+    let key = PatchKey {
+        world_index: IVec2::new(4, -2),
+        lod_index: 1,
+    };
+    assert_eq!(max_seam_diff(&source, key, PATCH_PIXEL_SIZE), 0.0);
+
+    assert!(source.cache_key().is_some());
+}
+
+#[test]
+fn erosion_reaching_past_a_patch_stays_seamless() {
+    let params = test_params();
+    let pixel_size = 8;
+    assert!(params.influence_radius() > 2 * pixel_size);
+
+    let source = ErosionHeightSource::new(Arc::new(FbmHeightSource::default()), params);
+    let key = PatchKey {
+        world_index: IVec2::new(-3, 5),
+        lod_index: 0,
+    };
+    assert_eq!(max_seam_diff(&source, key, pixel_size), 0.0);
+}
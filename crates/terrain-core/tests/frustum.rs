@@ -0,0 +1,113 @@
+use glam::{Mat4, Vec3};
+use terrain_core::*;
+
+fn camera_world_to_clip(position: Vec3, dir: Vec3) -> Mat4 {
+    let view_to_clip = Mat4::perspective_infinite_reverse_lh(90_f32.to_radians(), 16.0 / 9.0, 0.1);
+    view_to_clip * Mat4::look_to_lh(position, dir, Vec3::Y)
+}
+
+#[test]
+fn reverse_infinite_projection_has_no_far_plane() {
+    let frustum = Frustum::from_world_to_clip(&camera_world_to_clip(Vec3::ZERO, Vec3::Z));
+    assert_eq!(frustum.planes().len(), 5);
+
+    assert!(frustum.contains_point(Vec3::new(0.0, 0.0, 1.0)));
+    assert!(frustum.contains_point(Vec3::new(0.0, 0.0, 1.0e6)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 0.05)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -1.0)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 10.0, 1.0)));
+
+    let finite = Mat4::perspective_lh(90_f32.to_radians(), 1.0, 0.1, 100.0);
+    let frustum = Frustum::from_world_to_clip(&finite);
+    assert_eq!(frustum.planes().len(), 6);
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 200.0)));
+}
+
+#[test]
+fn aabbs_straddling_planes_are_visible() {
+    let frustum = Frustum::from_world_to_clip(&camera_world_to_clip(Vec3::ZERO, Vec3::Z));
+
+    assert!(frustum.intersects_aabb(Vec3::new(-1.0, -1.0, 5.0), Vec3::new(1.0, 1.0, 6.0)));
+    assert!(frustum.intersects_aabb(Vec3::new(-100.0, -1.0, -5.0), Vec3::new(100.0, 1.0, 6.0)));
+    assert!(!frustum.intersects_aabb(Vec3::new(-1.0, -1.0, -6.0), Vec3::new(1.0, 1.0, -5.0)));
+    assert!(!frustum.intersects_aabb(Vec3::new(50.0, -1.0, 5.0), Vec3::new(60.0, 1.0, 6.0)));
+}
+
+#[test]
+fn culls_leafs_behind_the_camera() {
+    let cam_pos = Vec3::new(30.0, 40.0, -20.0);
+    let scale = TerrainScale::default();
+    let bounds = PatchBoundsTree::new();
+
+    let qtree = PatchQuadTree::new(
+        &cam_pos,
+        1024,
+        &LodPolicy::Distance { lod_factor: 3.0 },
+        &bounds,
+        &scale,
+    );
+    let leafs = qtree.collect_leafs();
+
+    let frustum = Frustum::from_world_to_clip(&camera_world_to_clip(cam_pos, Vec3::new(1.0, -0.3, 0.0)));
+    let visible = qtree.collect_visible_leafs(&frustum, &bounds, &scale);
+
+    assert!(!visible.is_empty());
+    assert!(visible.len() < leafs.len() / 2);
+    assert!(visible.iter().all(|l| leafs.contains(l)));
+
+    // Every leaf that was dropped is outside, every leaf completely behind the camera is dropped.
+    for leaf in &leafs {
+        let (min, max) = bounds.aabb(leaf, &scale);
+
+        if !visible.contains(leaf) {
+            assert!(!frustum.intersects_aabb(min, max));
+        }
+
+        if max.x < cam_pos.x - 1.0 {
+            assert!(!visible.contains(leaf));
+        }
+    }
+}
+
+#[test]
+fn tall_leafs_under_flat_parents_stay_visible() {
+    let cam_pos = Vec3::new(0.0, 150.0, 10.0);
+    let scale = TerrainScale::default();
+
+    let qtree = PatchQuadTree::new(
+        &cam_pos,
+        1024,
+        &LodPolicy::Distance { lod_factor: 3.0 },
+        &PatchBoundsTree::new(),
+        &scale,
+    );
+    let leafs = qtree.collect_leafs();
+
+    // Flat leafs with flat coarse patches all the way up.
+    let flat = HeightRange { min: 0.0, max: 0.01 };
+    let mut bounds = PatchBoundsTree::new();
+    for leaf in &leafs {
+        let mut key = *leaf;
+        while key.lod_index <= PATCH_LOD_COUNT {
+            bounds.insert(key, flat);
+            key = PatchKey {
+                world_index: (key.world_index >> (key.lod_index + 1)) << (key.lod_index + 1),
+                lod_index: key.lod_index + 1,
+            };
+        }
+    }
+
+    // Looking level, so flat ground this close is below the frustum but a peak on it reaches into view.
+    let frustum = Frustum::from_world_to_clip(&camera_world_to_clip(cam_pos, Vec3::X));
+    let peak = *leafs
+        .iter()
+        .find(|l| {
+            let (min, max) = bounds.aabb(l, &scale);
+            min.x >= 0.0 && max.x <= 128.0 && (min.z..max.z).contains(&cam_pos.z)
+        })
+        .unwrap();
+    assert!(!qtree.collect_visible_leafs(&frustum, &bounds, &scale).contains(&peak));
+
+    bounds.insert(peak, HeightRange { min: 0.0, max: 1.0 });
+    assert!(qtree.collect_visible_leafs(&frustum, &bounds, &scale).contains(&peak));
+}
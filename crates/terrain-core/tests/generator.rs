@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use glam::IVec2;
+use terrain_core::*;
+
+const PIXEL_SIZE: u32 = 4;
+
+// Flat source that logs which patches it was asked for, and waits while the test holds `gate`.
+#[derive(Default)]
+struct LoggingSource {
+    gate: Mutex<()>,
+    sampled: Mutex<Vec<PatchKey>>,
+}
+
+impl HeightSource for LoggingSource {
+    fn sample_patch(&self, key: &PatchKey, pixel_size: u32) -> Vec<f32> {
+        drop(self.gate.lock().unwrap());
+        self.sampled.lock().unwrap().push(*key);
+
+        vec![0.0; (pixel_size as usize + 1).pow(2)]
+    }
+}
+
+fn key(x: i32, lod_index: u32) -> PatchKey {
+    PatchKey {
+        world_index: IVec2::new(x, 0),
+        lod_index,
+    }
+}
+
+// A single worker that is busy until `run` releases it, so everything requested before queues up.
+fn paused_pool(requests: &[(PatchKey, f32)], run: impl FnOnce(&PatchGenPool)) -> Vec<PatchKey> {
+    let source = Arc::new(LoggingSource::default());
+    let pool = PatchGenPool::with_worker_count(source.clone(), None, PIXEL_SIZE, 1);
+
+    let gate = source.gate.lock().unwrap();
+    let blocker = key(-1, 0);
+    pool.requst_patch_generation(blocker, 0.0);
+    while pool.queued_count() > 0 {
+        std::thread::yield_now();
+    }
+
+    for &(request, distance) in requests {
+        pool.requst_patch_generation(request, distance);
+    }
+    run(&pool);
+
+    let expected_count = pool.queued_count() + 1;
+    drop(gate);
+
+    let start = Instant::now();
+    let mut result_count = 0;
+    while result_count < expected_count {
+        result_count += pool.drain_results().count();
+        assert!(start.elapsed() < Duration::from_secs(10), "patch generation timed out");
+        std::thread::yield_now();
+    }
+
+    let sampled = source.sampled.lock().unwrap().clone();
+    assert_eq!(sampled[0], blocker);
+    sampled[1..].to_vec()
+}
+
+#[test]
+fn requests_pop_by_distance_in_patch_sizes() {
+    let requests = [
+        (key(0, 0), 3.0 * PATCH_WORLD_SIZE as f32),
+        (key(1, 0), PATCH_WORLD_SIZE as f32),
+        (key(2, 2), 3.0 * PATCH_WORLD_SIZE as f32),
+        (key(3, 1), PATCH_WORLD_SIZE as f32),
+        // Two patch sizes away like `key(5, 0)`, the coarser one goes first.
+        (key(4, 1), 4.0 * PATCH_WORLD_SIZE as f32),
+        (key(5, 0), 2.0 * PATCH_WORLD_SIZE as f32),
+    ];
+
+    let sampled = paused_pool(&requests, |_| {});
+
+    assert_eq!(
+        sampled,
+        [key(3, 1), key(2, 2), key(1, 0), key(4, 1), key(5, 0), key(0, 0)]
+    );
+}
+
+#[test]
+fn reprioritizing_reorders_and_cancels() {
+    let requests = [(key(0, 0), 1.0), (key(1, 0), 2.0), (key(2, 0), 3.0), (key(3, 0), 4.0)];
+
+    let sampled = paused_pool(&requests, |pool| {
+        // The camera moved to the other end, and patch 1 is not needed anymore.
+        let cancelled = pool.reprioritize_requests(|request| match request.world_index.x {
+            -1 => panic!("requests picked up by a worker can not be reprioritized"),
+            1 => None,
+            x => Some(10.0 - x as f32),
+        });
+
+        assert_eq!(cancelled, [key(1, 0)]);
+        assert_eq!(pool.cancelled_count(), 1);
+        assert_eq!(pool.queued_count(), 3);
+    });
+
+    assert_eq!(sampled, [key(3, 0), key(2, 0), key(0, 0)]);
+}
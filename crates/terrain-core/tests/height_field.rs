@@ -0,0 +1,82 @@
+mod common;
+
+use std::sync::Arc;
+
+use glam::Vec2;
+use terrain_core::*;
+
+use common::{insert_patch, key};
+
+// Height rises linearly along world X with `slope` height units per world unit.
+fn ramp_patch(key: &PatchKey, slope: f32) -> Arc<[f32]> {
+    let pixel_world_size = key.world_size() as f32 / PATCH_PIXEL_SIZE as f32;
+
+    (0..ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE)
+        .map(|i| {
+            let x = (i % ATLAS_PATCH_PIXEL_SIZE) as f32 * pixel_world_size + key.world_pos().x as f32;
+            0.5 + x * slope
+        })
+        .collect()
+}
+
+#[test]
+fn interpolates_and_scales_like_the_vertex_shader() {
+    let fine = key(0, 0, 0);
+    let slope = 0.001;
+
+    let mut height_field = TerrainHeightField::new(PATCH_LOD_COUNT);
+    insert_patch(&mut height_field, fine, ramp_patch(&fine, slope));
+    height_field.set_leaf_patches(&[fine]);
+
+    let scale = TerrainScale {
+        world_scale: 2.0,
+        height_scale: 50.0,
+    };
+
+    // World positions are scaled before they reach the patch grid, so 30 rendered units are 15 patch units.
+    let sample = height_field.height_at(Vec2::new(30.0, 10.0), &scale).unwrap();
+    assert!((sample.value - (0.5 + 15.0 * slope) * 50.0).abs() < 1e-4);
+    assert_eq!(sample.lod_index, 0);
+    assert!(!sample.is_fallback);
+
+    let rise_per_unit = slope * scale.height_scale / scale.world_scale;
+    let normal = height_field.normal_at(Vec2::new(30.0, 10.0), &scale).unwrap().value;
+    assert!((normal.x + rise_per_unit / (1.0 + rise_per_unit * rise_per_unit).sqrt()).abs() < 1e-4);
+    assert!(normal.z.abs() < 1e-5);
+
+    let slope_angle = height_field.slope_at(Vec2::new(30.0, 10.0), &scale).unwrap().value;
+    assert!((slope_angle - rise_per_unit.atan()).abs() < 1e-4);
+
+    // Borders fall back to one sided differences but still see the ramp.
+    let border_normal = height_field.normal_at(Vec2::ZERO, &scale).unwrap().value;
+    assert!((border_normal - normal).length() < 1e-4);
+
+    assert!(height_field.height_at(Vec2::new(-1.0, 10.0), &scale).is_none());
+}
+
+#[test]
+fn prefers_finest_lod_and_reports_fallback() {
+    let fine = key(0, 0, 0);
+    let coarse = key(0, 0, 1);
+    let scale = TerrainScale::default();
+
+    let mut height_field = TerrainHeightField::new(PATCH_LOD_COUNT);
+    insert_patch(&mut height_field, coarse, ramp_patch(&coarse, 0.002));
+    height_field.set_leaf_patches(&[fine, key(1, 0, 0), key(0, 1, 0), key(1, 1, 0)]);
+
+    let sample = height_field.height_at(Vec2::new(20.0, 20.0), &scale).unwrap();
+    assert_eq!(sample.lod_index, 1);
+    assert!(sample.is_fallback);
+
+    insert_patch(&mut height_field, fine, ramp_patch(&fine, 0.002));
+
+    let sample = height_field.height_at(Vec2::new(20.0, 20.0), &scale).unwrap();
+    assert_eq!(sample.lod_index, 0);
+    assert!(!sample.is_fallback);
+    assert!((sample.value - (0.5 + 20.0 * 0.002) * scale.height_scale).abs() < 1e-3);
+
+    // Outside the fine patch only the coarse one is left.
+    let sample = height_field.height_at(Vec2::new(100.0, 20.0), &scale).unwrap();
+    assert_eq!(sample.lod_index, 1);
+    assert!(sample.is_fallback);
+}
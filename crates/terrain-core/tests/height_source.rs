@@ -0,0 +1,68 @@
+use glam::{IVec2, UVec2};
+use terrain_core::*;
+
+const ATLAS_PATCH_PIXEL_COUNT: usize = (ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE) as usize;
+
+fn assert_overlap_matches_neighbor(source: &dyn HeightSource) {
+    let key = PatchKey {
+        world_index: IVec2::new(-2, 1),
+        lod_index: 1,
+    };
+    let right = PatchKey {
+        world_index: key.world_index + IVec2::X * 2,
+        ..key
+    };
+
+    let patch = source.sample_patch(&key, PATCH_PIXEL_SIZE);
+    let right_patch = source.sample_patch(&right, PATCH_PIXEL_SIZE);
+    assert_eq!(patch.len(), ATLAS_PATCH_PIXEL_COUNT);
+
+    for y in 0..ATLAS_PATCH_PIXEL_SIZE as usize {
+        let row = y * ATLAS_PATCH_PIXEL_SIZE as usize;
+        let overlap = patch[row + PATCH_PIXEL_SIZE as usize];
+
+        assert!((overlap - right_patch[row]).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn fbm_patches_overlap() {
+    assert_overlap_matches_neighbor(&FbmHeightSource::default());
+}
+
+#[test]
+fn noise_graph_patches_overlap() {
+    let graph = NoiseNode::Terraces {
+        source: Box::new(NoiseNode::DomainWarp {
+            source: Box::new(NoiseNode::RidgedMulti(FractalParams::default())),
+            seed: 7,
+            frequency: 2.0,
+            strength: 0.1,
+        }),
+        control_points: vec![-1.0, 0.0, 0.5, 1.0],
+    };
+
+    assert_overlap_matches_neighbor(&NoiseGraphHeightSource::new(&graph));
+}
+
+#[test]
+fn heightmap_patches_overlap() {
+    let size = UVec2::new(300, 200);
+    let heights = (0..size.x * size.y)
+        .map(|i| ((i % size.x) as f32 * 0.1).sin() * 0.25 + 0.25 + (i / size.x) as f32 / size.y as f32 * 0.5)
+        .collect();
+
+    let source = HeightmapSource::new(size, heights);
+
+    assert_overlap_matches_neighbor(&source);
+    let key = PatchKey {
+        world_index: IVec2::ZERO,
+        lod_index: 0,
+    };
+    assert!(
+        source
+            .sample_patch(&key, PATCH_PIXEL_SIZE)
+            .iter()
+            .all(|h| (0.0..=1.0).contains(h))
+    );
+}
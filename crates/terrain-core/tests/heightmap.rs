@@ -0,0 +1,288 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use glam::{DVec2, IVec2, UVec2};
+use terrain_core::*;
+
+const SIZE: UVec2 = UVec2::new(37, 21);
+
+// Deleted with all its files when the test ends, passed or not.
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(test_name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("terrain-core-heightmap-{}-{}", test_name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        Self(dir)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn test_samples() -> Vec<u16> {
+    (0..SIZE.x * SIZE.y)
+        .map(|i| ((i % SIZE.x) * 1000 + (i / SIZE.x) * 700) as u16)
+        .collect()
+}
+
+fn assert_matches_samples(source: &HeightmapSource, scale: f32) {
+    assert_eq!(source.size(), SIZE);
+
+    let samples = test_samples();
+    for (i, &sample) in samples.iter().enumerate() {
+        let pixel = UVec2::new(i as u32 % SIZE.x, i as u32 / SIZE.x);
+        let world_pos = source.world_origin + pixel.as_vec2() * source.world_pixel_size;
+
+        let expected = sample as f32 / scale;
+        assert!((source.sample(world_pos.as_dvec2(), 0) - expected).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn loads_raw_with_sidecar() {
+    let dir = TestDir::new("raw");
+    let path = dir.path("map.r16");
+    let bytes = test_samples().iter().flat_map(|s| s.to_le_bytes()).collect::<Vec<_>>();
+    std::fs::write(&path, bytes).unwrap();
+    std::fs::write(dir.path("map.r16.size"), format!("{}x{}", SIZE.x, SIZE.y)).unwrap();
+
+    assert_matches_samples(&HeightmapSource::open(&path).unwrap(), u16::MAX as f32);
+}
+
+#[test]
+fn rejects_raw_without_sidecar() {
+    let dir = TestDir::new("no-sidecar");
+    let path = dir.path("no_sidecar.raw");
+    std::fs::write(&path, vec![0; (SIZE.x * SIZE.y * 2) as usize]).unwrap();
+
+    assert!(HeightmapSource::open(&path).is_err());
+}
+
+#[test]
+fn loads_png16() {
+    let dir = TestDir::new("png16");
+    let path = dir.path("map.png");
+
+    let mut encoder = png::Encoder::new(std::fs::File::create(&path).unwrap(), SIZE.x, SIZE.y);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+
+    let bytes = test_samples().iter().flat_map(|s| s.to_be_bytes()).collect::<Vec<_>>();
+    encoder.write_header().unwrap().write_image_data(&bytes).unwrap();
+
+    assert_matches_samples(&HeightmapSource::open(&path).unwrap(), u16::MAX as f32);
+}
+
+// Minimal little endian TIFF with one IFD, `data` is placed right after the header.
+fn write_tiff(path: &PathBuf, tags: &[(u16, u16, Vec<u32>)], data: &[u8]) {
+    let mut bytes = b"II".to_vec();
+    bytes.extend(42_u16.to_le_bytes());
+
+    let ifd_offset = 8 + data.len() as u32;
+    bytes.extend(ifd_offset.to_le_bytes());
+    bytes.extend(data);
+
+    let entries_size = 2 + tags.len() * 12 + 4;
+    let mut extra = Vec::new();
+
+    bytes.extend((tags.len() as u16).to_le_bytes());
+    for (tag, field_type, values) in tags {
+        bytes.extend(tag.to_le_bytes());
+        bytes.extend(field_type.to_le_bytes());
+        bytes.extend((values.len() as u32).to_le_bytes());
+
+        let encoded = values
+            .iter()
+            .flat_map(|&v| match field_type {
+                3 => (v as u16).to_le_bytes().to_vec(),
+                _ => v.to_le_bytes().to_vec(),
+            })
+            .collect::<Vec<_>>();
+
+        if encoded.len() <= 4 {
+            let mut inline = encoded;
+            inline.resize(4, 0);
+            bytes.extend(inline);
+        } else {
+            let offset = ifd_offset as usize + entries_size + extra.len();
+            bytes.extend((offset as u32).to_le_bytes());
+            extra.extend(encoded);
+        }
+    }
+    bytes.extend(0_u32.to_le_bytes());
+    bytes.extend(extra);
+
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn loads_stripped_geotiff() {
+    let dir = TestDir::new("stripped-geotiff");
+    let path = dir.path("map.tif");
+
+    let data = test_samples().iter().flat_map(|s| s.to_le_bytes()).collect::<Vec<_>>();
+    let rows_per_strip = 8;
+    let strip_size = SIZE.x * rows_per_strip * 2;
+    let strip_count = SIZE.y.div_ceil(rows_per_strip);
+
+    write_tiff(
+        &path,
+        &[
+            (256, 3, vec![SIZE.x]),
+            (257, 3, vec![SIZE.y]),
+            (258, 3, vec![16]),
+            (259, 3, vec![1]),
+            (273, 4, (0..strip_count).map(|i| 8 + i * strip_size).collect()),
+            (277, 3, vec![1]),
+            (278, 3, vec![rows_per_strip]),
+        ],
+        &data,
+    );
+
+    assert_matches_samples(&HeightmapSource::open(&path).unwrap(), u16::MAX as f32);
+}
+
+#[test]
+fn loads_tiled_float_geotiff() {
+    let dir = TestDir::new("tiled-geotiff");
+    let path = dir.path("map_tiled.tiff");
+
+    let tile_size = 16;
+    let tiles = SIZE / tile_size + 1;
+    let samples = test_samples();
+
+    let mut data = Vec::new();
+    for tile_y in 0..tiles.y {
+        for tile_x in 0..tiles.x {
+            for y in 0..tile_size {
+                for x in 0..tile_size {
+                    let pixel = UVec2::new(tile_x, tile_y) * tile_size + UVec2::new(x, y);
+                    let sample = match pixel.cmplt(SIZE).all() {
+                        true => samples[(pixel.y * SIZE.x + pixel.x) as usize] as f32,
+                        false => -1.0,
+                    };
+                    data.extend(sample.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    let tile_bytes = tile_size * tile_size * 4;
+    write_tiff(
+        &path,
+        &[
+            (256, 3, vec![SIZE.x]),
+            (257, 3, vec![SIZE.y]),
+            (258, 3, vec![32]),
+            (322, 3, vec![tile_size]),
+            (323, 3, vec![tile_size]),
+            (324, 4, (0..tiles.x * tiles.y).map(|i| 8 + i * tile_bytes).collect()),
+            (339, 3, vec![3]),
+        ],
+        &data,
+    );
+
+    let max = *samples.iter().max().unwrap() as f32;
+    assert_eq!(*samples.iter().min().unwrap(), 0);
+    assert_matches_samples(&HeightmapSource::open(&path).unwrap(), max);
+}
+
+#[test]
+fn rejects_bad_dimensions() {
+    let dir = TestDir::new("bad-dimensions");
+    let is_invalid_data = |path: &PathBuf| {
+        HeightmapSource::open(path)
+            .err()
+            .is_some_and(|e| e.kind() == ErrorKind::InvalidData)
+    };
+
+    // Empty, and too many samples for the byte count to fit.
+    for (name, size) in [("empty.r16", "0x21"), ("huge.r16", "4294967295x4294967295")] {
+        let path = dir.path(name);
+        std::fs::write(&path, [0; 8]).unwrap();
+        std::fs::write(dir.path(&format!("{}.size", name)), size).unwrap();
+        assert!(is_invalid_data(&path), "{}", name);
+    }
+
+    let strip = |width: u32, height: u32, bits: u32| {
+        vec![
+            (256, 4, vec![width]),
+            (257, 4, vec![height]),
+            (258, 3, vec![bits]),
+            (273, 4, vec![8]),
+        ]
+    };
+    let tiled = vec![
+        (256, 3, vec![SIZE.x]),
+        (257, 3, vec![SIZE.y]),
+        (258, 3, vec![16]),
+        (322, 3, vec![0]),
+        (323, 3, vec![16]),
+        (324, 4, vec![8]),
+    ];
+
+    let data = vec![0; (SIZE.x * SIZE.y * 2) as usize];
+    for (name, tags) in [
+        ("empty.tif", strip(0, SIZE.y, 16)),
+        ("huge.tif", strip(u32::MAX, u32::MAX, 16)),
+        ("double.tif", strip(SIZE.x, SIZE.y, 64)),
+        ("bits.tif", strip(SIZE.x, SIZE.y, 0)),
+        ("empty_tiles.tif", tiled),
+    ] {
+        let path = dir.path(name);
+        write_tiff(&path, &tags, &data);
+        assert!(is_invalid_data(&path), "{}", name);
+    }
+}
+
+#[test]
+fn coarse_lods_match_fine_lods() {
+    let size = UVec2::splat(1024);
+    let heights = (0..size.x * size.y)
+        .map(|i| {
+            let p = UVec2::new(i % size.x, i / size.x).as_vec2() / size.as_vec2();
+            (p.x * 6.0).sin() * (p.y * 4.0).cos() * 0.25 + 0.5 + if i % 2 == 0 { 0.05 } else { -0.05 }
+        })
+        .collect();
+
+    let source = HeightmapSource::new(size, heights);
+    assert_eq!(source.mip_count(), 11);
+
+    let fine = PatchKey {
+        world_index: IVec2::new(-2, 0),
+        lod_index: 0,
+    };
+    let coarse = PatchKey {
+        world_index: IVec2::new(-2, 0),
+        lod_index: 1,
+    };
+
+    let fine_patch = source.sample_patch(&fine, PATCH_PIXEL_SIZE);
+    let coarse_patch = source.sample_patch(&coarse, PATCH_PIXEL_SIZE);
+
+    // The coarse patch averages out the per pixel noise, its texels land on every other fine texel.
+    for y in 0..PATCH_PIXEL_SIZE / 2 {
+        for x in 0..PATCH_PIXEL_SIZE / 2 {
+            let fine_avg = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .iter()
+                .map(|&(dx, dy)| fine_patch[((y * 2 + dy) * ATLAS_PATCH_PIXEL_SIZE + x * 2 + dx) as usize])
+                .sum::<f32>()
+                / 4.0;
+            let coarse_sample = coarse_patch[(y * ATLAS_PATCH_PIXEL_SIZE + x) as usize];
+
+            assert!((fine_avg - coarse_sample).abs() < 0.01);
+        }
+    }
+
+    let world_pos = DVec2::new(-100.0, 20.0);
+    assert!((source.sample(world_pos, 0) - source.sample(world_pos, 1)).abs() < 0.1);
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::collections::HashMap;
+
+use glam::{IVec2, UVec2};
+use terrain_core::*;
+
+use common::Rng;
+
+fn small_config() -> TerrainConfig {
+    TerrainConfig {
+        lod_count: 3,
+        indirection_slot_count: 16,
+        ..TerrainConfig::default()
+    }
+}
+
+// Stands in for the GPU copy, only ever written through the dirty rows.
+fn apply_updates(tables: &mut IndirectionTables, gpu_lods: &mut [Vec<UVec2>]) -> usize {
+    let updates = tables.take_dirty_rows();
+
+    for update in &updates {
+        let slot_count = tables.slot_count(update.lod_index);
+        let row_offset = (update.row * slot_count) as usize;
+        let columns = row_offset + update.columns.start as usize..row_offset + update.columns.end as usize;
+
+        gpu_lods[update.lod_index as usize][columns.clone()].copy_from_slice(&tables.lod(update.lod_index)[columns]);
+    }
+
+    updates.iter().map(|u| u.columns.len()).sum()
+}
+
+#[test]
+fn incremental_updates_match_full_rebuild() {
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    let config = small_config();
+
+    let mut tables = IndirectionTables::new(config);
+    let mut gpu_lods = (0..config.lod_count)
+        .map(|lod_index| vec![UVec2::ZERO; config.indirection_slot_count(lod_index).pow(2) as usize])
+        .collect::<Vec<_>>();
+    let mut resident = HashMap::<PatchKey, UVec2>::new();
+    let mut cam_world_index = IVec2::ZERO;
+
+    for step in 0..300 {
+        // Mostly small steps, sometimes a jump farther than the window.
+        cam_world_index += if step % 50 == 49 {
+            IVec2::new(rng.range_i32(-40, 40), rng.range_i32(-40, 40))
+        } else {
+            IVec2::new(rng.range_i32(-2, 3), rng.range_i32(-2, 3))
+        };
+
+        // Residency changes inside and outside of the window, before it moves like in the planner.
+        for _ in 0..8 {
+            let lod_index = rng.range_i32(0, config.lod_count as i32) as u32;
+            let world_index = ((cam_world_index >> lod_index)
+                + IVec2::new(rng.range_i32(-12, 12), rng.range_i32(-12, 12)))
+                << lod_index;
+            let key = PatchKey { world_index, lod_index };
+
+            if resident.remove(&key).is_some() {
+                tables.remove(&key);
+            } else {
+                let atlas_index = UVec2::new(step, rng.range_i32(0, 1024) as u32);
+                resident.insert(key, atlas_index);
+                tables.insert(&key, atlas_index);
+            }
+        }
+
+        tables.recenter(cam_world_index, |key| resident.get(key).copied());
+        apply_updates(&mut tables, &mut gpu_lods);
+
+        for lod_index in 0..config.lod_count {
+            let slot_count = config.indirection_slot_count(lod_index) as i32;
+            let origin = (cam_world_index >> lod_index) - slot_count / 2;
+
+            for y in 0..slot_count {
+                for x in 0..slot_count {
+                    let key = PatchKey {
+                        world_index: (origin + IVec2::new(x, y)) << lod_index,
+                        lod_index,
+                    };
+                    let expected = resident.get(&key).copied();
+
+                    let slot = config.indirection_slot(&key, cam_world_index).unwrap();
+                    let slot_offset = (slot.y * slot_count as u32 + slot.x) as usize;
+
+                    assert_eq!(tables.atlas_index(&key), expected, "step {step} {key:?}");
+                    assert_eq!(
+                        gpu_lods[lod_index as usize][slot_offset],
+                        expected.unwrap_or(EMPTY_ATLAS_INDEX),
+                        "step {step} {key:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn updates_are_proportional_to_change() {
+    let config = small_config();
+    let slot_count = config.indirection_slot_count;
+    let mut tables = IndirectionTables::new(config);
+    let mut gpu_lods = (0..config.lod_count)
+        .map(|lod_index| vec![UVec2::ZERO; config.indirection_slot_count(lod_index).pow(2) as usize])
+        .collect::<Vec<_>>();
+
+    // Every patch is resident in a slot of its own.
+    let atlas_index = |key: &PatchKey| Some((key.world_index + 1000).as_uvec2());
+
+    tables.recenter(IVec2::ZERO, atlas_index);
+    let full_count = apply_updates(&mut tables, &mut gpu_lods);
+    assert_eq!(
+        full_count,
+        (0..config.lod_count).map(|l| (slot_count >> l).pow(2) as usize).sum()
+    );
+
+    // Nothing changed.
+    tables.recenter(IVec2::ZERO, atlas_index);
+    assert!(tables.take_dirty_rows().is_empty());
+
+    // One patch to the right only scrolls a column into the finest LOD, the coarser windows stay where they are.
+    tables.recenter(IVec2::X, atlas_index);
+    let updates = tables.take_dirty_rows();
+    assert_eq!(updates.len(), slot_count as usize);
+    assert!(updates.iter().all(|u| u.lod_index == 0 && u.columns.len() == 1));
+
+    // One patch down scrolls a row in.
+    tables.recenter(IVec2::ONE, atlas_index);
+    let updates = tables.take_dirty_rows();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].columns, 0..slot_count);
+
+    // A residency change rewrites a single slot, unless the patch is outside of the window.
+    let key = PatchKey {
+        world_index: IVec2::new(4, -2),
+        lod_index: 1,
+    };
+    tables.insert(&key, UVec2::new(7, 7));
+    assert_eq!(tables.atlas_index(&key), Some(UVec2::new(7, 7)));
+
+    let updates = tables.take_dirty_rows();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].lod_index, 1);
+    assert_eq!(updates[0].columns.len(), 1);
+
+    let outside = PatchKey {
+        world_index: IVec2::new(slot_count as i32 * 4, 0),
+        lod_index: 0,
+    };
+    tables.insert(&outside, UVec2::new(1, 1));
+    tables.remove(&outside);
+    assert!(tables.take_dirty_rows().is_empty());
+    assert_eq!(tables.atlas_index(&outside), None);
+}
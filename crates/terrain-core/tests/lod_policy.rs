@@ -0,0 +1,146 @@
+use glam::{IVec2, Mat4, Vec2, Vec3};
+use terrain_core::*;
+
+const RENDER_DISTANCE: u32 = 512;
+const VIEWPORT_HEIGHT: u32 = 1080;
+
+// Gentle hills everywhere, small ridges only on the +X half.
+fn height(p: Vec2) -> f32 {
+    let hills = 0.3 + 0.1 * (p.x * 0.004).sin() * (p.y * 0.005).cos();
+    let ridges = if p.x > 0.0 {
+        0.01 * (p.x * 0.35).sin() * (p.y * 0.29).sin()
+    } else {
+        0.0
+    };
+
+    hills + ridges
+}
+
+// Bounds and errors of every patch under the quadtree root, as the planner would have them once everything is
+// generated.
+fn build_bounds() -> PatchBoundsTree {
+    let mut bounds = PatchBoundsTree::new();
+    let root_size = (RENDER_DISTANCE * 2 / PATCH_WORLD_SIZE) as i32;
+    let root_index = -((RENDER_DISTANCE / PATCH_WORLD_SIZE) as i32);
+
+    for lod_index in 0..PATCH_LOD_COUNT {
+        let step = 1 << lod_index;
+
+        for y in (0..root_size).step_by(step) {
+            for x in (0..root_size).step_by(step) {
+                let key = PatchKey {
+                    world_index: IVec2::new(root_index + x, root_index + y),
+                    lod_index,
+                };
+
+                let pixel_world_size = key.world_size() as f32 / PATCH_PIXEL_SIZE as f32;
+                let heights = (0..ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE)
+                    .map(|i| {
+                        let pixel = Vec2::new((i % ATLAS_PATCH_PIXEL_SIZE) as f32, (i / ATLAS_PATCH_PIXEL_SIZE) as f32);
+                        height(key.world_pos().as_vec2() + pixel * pixel_world_size)
+                    })
+                    .collect::<Vec<_>>();
+
+                bounds.insert(key, HeightRangePyramid::new(&heights).range());
+                bounds.insert_decimation_error(key, patch_decimation_error(&heights));
+            }
+        }
+    }
+
+    bounds
+}
+
+// Recorded fly-over, mirrored around X = 0 so every position over the ridges has a twin over the hills.
+fn camera_path() -> Vec<Vec3> {
+    [-400.0, -250.0, -100.0, -20.0, 20.0, 100.0, 250.0, 400.0]
+        .into_iter()
+        .map(|x| Vec3::new(x, 60.0, 35.0))
+        .collect()
+}
+
+fn view_to_clip() -> Mat4 {
+    Mat4::perspective_infinite_reverse_lh(90_f32.to_radians(), 16.0 / 9.0, 0.1)
+}
+
+fn screen_space_error(max_pixel_error: f32) -> LodPolicy {
+    LodPolicy::screen_space_error(max_pixel_error, &view_to_clip(), VIEWPORT_HEIGHT, 3.0)
+}
+
+fn record_leafs(policy: &LodPolicy, bounds: &PatchBoundsTree) -> Vec<Vec<PatchKey>> {
+    let scale = TerrainScale::default();
+
+    camera_path()
+        .iter()
+        .map(|cam_pos| PatchQuadTree::new(cam_pos, RENDER_DISTANCE, policy, bounds, &scale).collect_leafs())
+        .collect()
+}
+
+fn leaf_counts(leafs: &[Vec<PatchKey>]) -> Vec<usize> {
+    leafs.iter().map(|l| l.len()).collect()
+}
+
+#[test]
+fn projection_scale_matches_fov() {
+    assert!((projection_scale(&view_to_clip(), VIEWPORT_HEIGHT) - 540.0).abs() < 1e-3);
+}
+
+#[test]
+fn recorded_paths_are_deterministic() {
+    let bounds = build_bounds();
+
+    for policy in [LodPolicy::Distance { lod_factor: 3.0 }, screen_space_error(2.0)] {
+        assert_eq!(record_leafs(&policy, &bounds), record_leafs(&policy, &build_bounds()));
+    }
+}
+
+#[test]
+fn screen_space_error_follows_roughness() {
+    let bounds = build_bounds();
+
+    let distance = leaf_counts(&record_leafs(&LodPolicy::Distance { lod_factor: 3.0 }, &bounds));
+    let sse_leafs = record_leafs(&screen_space_error(2.0), &bounds);
+    let sse = leaf_counts(&sse_leafs);
+    let sse_strict = leaf_counts(&record_leafs(&screen_space_error(0.5), &bounds));
+
+    // Leaf counts along `camera_path`, the second half flies over the ridges.
+    assert_eq!(distance, [73, 91, 100, 103, 103, 100, 91, 73]);
+    assert_eq!(sse, [4, 4, 4, 10, 16, 16, 19, 13]);
+    assert_eq!(sse_strict, [4, 10, 22, 22, 28, 28, 34, 28]);
+
+    // Positions over the ridges need more leafs than their twins over the hills, the distance policy barely notices.
+    let path_len = sse.len();
+    for i in 0..path_len / 2 {
+        let (hills, ridges) = (i, path_len - 1 - i);
+        assert!(sse[ridges] > sse[hills]);
+        assert!(sse[hills] < distance[hills]);
+    }
+
+    // A stricter threshold never removes detail.
+    for i in 0..path_len {
+        assert!(sse_strict[i] >= sse[i]);
+    }
+
+    // No leaf above the finest LOD projects to more than the threshold.
+    let scale = TerrainScale::default();
+    let projection_scale = projection_scale(&view_to_clip(), VIEWPORT_HEIGHT);
+
+    for (cam_pos, leafs) in camera_path().iter().zip(&sse_leafs) {
+        for leaf in leafs.iter().filter(|l| l.lod_index > 0) {
+            let (aabb_min, aabb_max) = bounds.aabb(leaf, &scale);
+            let distance = (cam_pos.clamp(aabb_min, aabb_max) - cam_pos).length();
+            let error = bounds.geometric_error(leaf).unwrap();
+
+            assert!(error * scale.height_scale * projection_scale / distance <= 2.0);
+        }
+    }
+}
+
+#[test]
+fn unknown_errors_fall_back_to_distance() {
+    let bounds = PatchBoundsTree::new();
+
+    assert_eq!(
+        record_leafs(&screen_space_error(2.0), &bounds),
+        record_leafs(&LodPolicy::Distance { lod_factor: 3.0 }, &bounds)
+    );
+}
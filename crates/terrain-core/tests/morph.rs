@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use glam::{IVec2, UVec2, Vec2};
+use terrain_core::*;
+
+fn height(p: Vec2) -> f32 {
+    0.5 + 0.2 * (p.x * 0.05).sin() * (p.y * 0.07).cos()
+}
+
+fn sample_patch(key: &PatchKey) -> Vec<f32> {
+    let pixel_world_size = key.world_size() as f32 / PATCH_PIXEL_SIZE as f32;
+
+    (0..ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE)
+        .map(|i| {
+            let pixel = Vec2::new((i % ATLAS_PATCH_PIXEL_SIZE) as f32, (i / ATLAS_PATCH_PIXEL_SIZE) as f32);
+            height(key.world_pos().as_vec2() + pixel * pixel_world_size)
+        })
+        .collect()
+}
+
+#[test]
+fn morph_moves_only_odd_vertices() {
+    for z in 0..=4 {
+        for x in 0..=4 {
+            let vertex = UVec2::new(x, z);
+            assert_eq!(morph_vertex(vertex, 0.0), vertex.as_vec2());
+
+            let morphed = morph_vertex(vertex, 1.0);
+            assert_eq!(morphed.as_uvec2() % 2, UVec2::ZERO);
+            assert!(morphed.distance(vertex.as_vec2()) <= 2_f32.sqrt());
+        }
+    }
+
+    assert_eq!(morph_vertex(UVec2::new(3, 2), 0.25), Vec2::new(2.75, 2.0));
+}
+
+#[test]
+fn fully_morphed_patch_matches_parent() {
+    let parent = PatchKey {
+        world_index: IVec2::new(-4, 2),
+        lod_index: 2,
+    };
+    let parent_heights = sample_patch(&parent);
+    let half = PATCH_PIXEL_SIZE / 2;
+
+    for offset in [UVec2::ZERO, UVec2::X, UVec2::Y, UVec2::ONE] {
+        let child = PatchKey {
+            world_index: parent.world_index + offset.as_ivec2() * 2,
+            lod_index: 1,
+        };
+        let child_heights = sample_patch(&child);
+
+        // Collapsed child triangles in parent grid coordinates, flattened ones dropped.
+        let mut morphed = HashSet::new();
+        for z in 0..PATCH_PIXEL_SIZE {
+            for x in 0..PATCH_PIXEL_SIZE {
+                for triangle in patch_quad_triangles(x, z) {
+                    let collapsed = triangle.map(|v| morph_vertex(v, 1.0).as_uvec2());
+                    if collapsed[0] == collapsed[1] || collapsed[1] == collapsed[2] || collapsed[0] == collapsed[2] {
+                        continue;
+                    }
+
+                    for v in collapsed {
+                        let parent_vertex = offset * half + v / 2;
+                        let parent_height =
+                            parent_heights[(parent_vertex.y * ATLAS_PATCH_PIXEL_SIZE + parent_vertex.x) as usize];
+                        assert!((morphed_height(&child_heights, v.as_vec2()) - parent_height).abs() < 1e-5);
+                    }
+
+                    morphed.insert(collapsed.map(|v| offset * half + v / 2));
+                }
+            }
+        }
+
+        let parent_triangles = (0..half)
+            .flat_map(|z| (0..half).map(move |x| (x, z)))
+            .flat_map(|(x, z)| patch_quad_triangles(offset.x * half + x, offset.y * half + z))
+            .collect::<HashSet<_>>();
+
+        assert_eq!(morphed, parent_triangles);
+    }
+}
+
+// World position and height of a vertex as the vertex shader draws it.
+fn placed_vertex(key: &PatchKey, heights: &[f32], vertex: UVec2, stitch_mask: StitchMask, morph: f32) -> (Vec2, f32) {
+    let grid_pos = place_vertex(vertex, PATCH_PIXEL_SIZE, stitch_mask, morph);
+    let pixel_world_size = key.world_size() as f32 / PATCH_PIXEL_SIZE as f32;
+
+    (
+        key.world_pos().as_vec2() + grid_pos * pixel_world_size,
+        morphed_height(heights, grid_pos),
+    )
+}
+
+#[test]
+fn morphing_keeps_stitched_seams_closed() {
+    // Two fine patches along the left edge of a coarse one, every side morphing by its own amount.
+    let coarse = PatchKey {
+        world_index: IVec2::new(2, 0),
+        lod_index: 1,
+    };
+    let coarse_heights = sample_patch(&coarse);
+    let coarse_edge = |stitch_mask: StitchMask, morph: f32| {
+        (0..=PATCH_PIXEL_SIZE)
+            .map(|i| placed_vertex(&coarse, &coarse_heights, UVec2::new(0, i), stitch_mask, morph))
+            .collect::<Vec<_>>()
+    };
+    let matches = |a: &(Vec2, f32), b: &(Vec2, f32)| a.0.distance(b.0) < 1e-4 && (a.1 - b.1).abs() < 1e-5;
+
+    for offset in [0, 1] {
+        let fine = PatchKey {
+            world_index: IVec2::new(1, offset),
+            lod_index: 0,
+        };
+        let fine_heights = sample_patch(&fine);
+        let fine_span = fine.world_pos().y as f32..=(fine.world_pos().y + fine.world_size() as i32) as f32;
+
+        for (fine_morph, coarse_morph) in [(0.0, 0.5), (0.3, 0.7), (1.0, 1.0)] {
+            let fine_edge = (0..=PATCH_PIXEL_SIZE)
+                .map(|i| {
+                    let vertex = UVec2::new(PATCH_PIXEL_SIZE, i);
+                    placed_vertex(&fine, &fine_heights, vertex, StitchMask::RIGHT, fine_morph)
+                })
+                .collect::<Vec<_>>();
+            let coarse_edge = coarse_edge(StitchMask::FINER_LEFT, coarse_morph)
+                .into_iter()
+                .filter(|(pos, _)| fine_span.contains(&pos.y))
+                .collect::<Vec<_>>();
+
+            assert!(fine_edge.iter().all(|f| coarse_edge.iter().any(|c| matches(f, c))));
+            assert!(coarse_edge.iter().all(|c| fine_edge.iter().any(|f| matches(f, c))));
+        }
+    }
+
+    // Morphing the coarse edge like its interior would open the seam.
+    let fine = PatchKey {
+        world_index: IVec2::new(1, 0),
+        lod_index: 0,
+    };
+    let fine_heights = sample_patch(&fine);
+    let fine_edge = (0..=PATCH_PIXEL_SIZE)
+        .map(|i| {
+            placed_vertex(
+                &fine,
+                &fine_heights,
+                UVec2::new(PATCH_PIXEL_SIZE, i),
+                StitchMask::RIGHT,
+                0.0,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        fine_edge
+            .iter()
+            .any(|f| !coarse_edge(StitchMask::empty(), 0.5).iter().any(|c| matches(f, c)))
+    );
+}
+
+#[test]
+fn morph_ranges_sit_between_split_distances() {
+    let lod_factor = 3.0;
+    let ranges = lod_morph_ranges(&LodPolicy::Distance { lod_factor }, LOD_HYSTERESIS, PATCH_LOD_COUNT);
+    let split_distance = |lod_index: u32| (PATCH_WORLD_SIZE << lod_index) as f32 * 0.5 * lod_factor;
+
+    for lod_index in 0..PATCH_LOD_COUNT - 1 {
+        let range = ranges[lod_index as usize];
+
+        assert!(range.start >= split_distance(lod_index) * (1.0 + LOD_HYSTERESIS));
+        assert!(range.end <= split_distance(lod_index + 1) * (1.0 - LOD_HYSTERESIS));
+        assert!(range.start < range.end);
+
+        assert_eq!(morph_factor(range.start, &range), 0.0);
+        assert_eq!(morph_factor(range.end, &range), 1.0);
+
+        let samples = (0..=16).map(|i| morph_factor(range.start + (range.end - range.start) * i as f32 / 16.0, &range));
+        assert!(samples.collect::<Vec<_>>().windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    assert_eq!(ranges[PATCH_LOD_COUNT as usize - 1], MorphRange::NONE);
+    assert_eq!(morph_factor(1e9, &MorphRange::NONE), 0.0);
+
+    let sse = LodPolicy::ScreenSpaceError {
+        max_pixel_error: 2.0,
+        projection_scale: 540.0,
+        fallback_lod_factor: 3.0,
+    };
+    assert!(
+        lod_morph_ranges(&sse, LOD_HYSTERESIS, PATCH_LOD_COUNT)
+            .iter()
+            .all(|r| *r == MorphRange::NONE)
+    );
+}
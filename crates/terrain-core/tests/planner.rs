@@ -0,0 +1,62 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use glam::{IVec2, Vec3};
+use terrain_core::*;
+
+#[test]
+fn leafs_tile_render_area() {
+    let render_distance = 2048;
+    let qtree = PatchQuadTree::new(
+        &Vec3::new(100.0, 50.0, -300.0),
+        render_distance,
+        &LodPolicy::Distance { lod_factor: 3.0 },
+        &PatchBoundsTree::new(),
+        &TerrainScale::default(),
+    );
+
+    let leafs = qtree.collect_leafs();
+    let covered_area: u64 = leafs.iter().map(|l| (l.world_size() as u64).pow(2)).sum();
+
+    assert_eq!(covered_area, (render_distance as u64 * 2).pow(2));
+    assert!(leafs.iter().all(|l| l.lod_index < PATCH_LOD_COUNT));
+}
+
+#[test]
+fn planned_patches_become_resident() {
+    let camera_pos = Vec3::new(0.0, 100.0, 0.0);
+    let mut planner = TerrainPlanner::new(
+        TerrainConfig::default(),
+        512,
+        LodPolicy::Distance { lod_factor: 3.0 },
+        Arc::new(FbmHeightSource::default()),
+        None,
+    );
+
+    let start = Instant::now();
+    let mut frame_index = 0;
+
+    let plan = loop {
+        frame_index += 1;
+
+        // Pretend the GPU is always one frame behind.
+        let plan = planner.plan_frame(&camera_pos, None, frame_index, frame_index - 1);
+        if plan.patches.len() == planner.leaf_patches().len() {
+            break plan;
+        }
+
+        assert!(start.elapsed() < Duration::from_secs(60), "patch generation timed out");
+        std::thread::sleep(Duration::from_millis(1));
+    };
+
+    assert_eq!(planner.cam_world_index(), IVec2::ZERO);
+
+    for patch in &plan.patches {
+        let lod_index = patch.key.lod_index;
+        let slot_count = planner.indirection().slot_count(lod_index) as i32;
+        let slot = (patch.key.world_index >> lod_index).rem_euclid(IVec2::splat(slot_count));
+
+        let atlas_index = planner.indirection().lod(lod_index)[(slot.y * slot_count + slot.x) as usize];
+        assert_ne!(atlas_index, EMPTY_ATLAS_INDEX);
+    }
+}
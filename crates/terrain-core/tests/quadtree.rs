@@ -0,0 +1,306 @@
+mod common;
+
+use std::collections::HashSet;
+use std::time::Instant;
+
+use glam::{IVec2, Vec3};
+use terrain_core::*;
+
+use common::Rng;
+
+fn assert_balanced(leafs: &[PatchKey], render_distance: u32) {
+    let covered_area: u64 = leafs.iter().map(|l| (l.world_size() as u64).pow(2)).sum();
+    assert_eq!(covered_area, (render_distance as u64 * 2).pow(2));
+
+    let leaf_set = leafs.iter().copied().collect::<HashSet<_>>();
+    for leaf in leafs {
+        for edge in PatchEdge::ALL {
+            if let Some(neighbor) = neighbor_across(&leaf_set, leaf, edge) {
+                assert!(
+                    neighbor.lod_index.abs_diff(leaf.lod_index) <= 1,
+                    "{leaf:?} and {neighbor:?} across {edge:?}"
+                );
+            }
+        }
+    }
+}
+
+// Error per finest patch that jumps between flat and very rough, which makes the screen space policy refine in
+// isolated spots.
+fn spiky_bounds(rng: &mut Rng, render_distance: u32) -> PatchBoundsTree {
+    let mut bounds = PatchBoundsTree::new();
+    let extent = (render_distance * 2 / PATCH_WORLD_SIZE) as i32;
+
+    for y in -extent..extent {
+        for x in -extent..extent {
+            let key = PatchKey {
+                world_index: IVec2::new(x, y),
+                lod_index: 0,
+            };
+            let error = if rng.next_f32() < 0.05 { 0.05 } else { 0.0 };
+
+            bounds.insert(key, HeightRange { min: 0.0, max: 0.5 });
+            bounds.insert_decimation_error(key, error);
+        }
+    }
+
+    bounds
+}
+
+#[test]
+fn leafs_are_balanced_for_random_cameras() {
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    let scale = TerrainScale::default();
+
+    for _ in 0..64 {
+        let render_distance = rng.pick(&[256, 512, 1024, 2048, 4096]);
+        let lod_policy = LodPolicy::Distance {
+            lod_factor: rng.pick(&[0.25, 0.5, 1.0, 3.0, 8.0]),
+        };
+        let cam_pos = Vec3::new(
+            rng.range(-5000.0, 5000.0),
+            rng.range(0.0, 300.0),
+            rng.range(-5000.0, 5000.0),
+        );
+
+        let leafs =
+            PatchQuadTree::new(&cam_pos, render_distance, &lod_policy, &PatchBoundsTree::new(), &scale).collect_leafs();
+        assert_balanced(&leafs, render_distance);
+    }
+}
+
+#[test]
+fn screen_space_error_leafs_are_balanced() {
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+    let scale = TerrainScale::default();
+
+    for _ in 0..16 {
+        let render_distance = rng.pick(&[256, 512, 1024]);
+        let bounds = spiky_bounds(&mut rng, render_distance);
+        let lod_policy = LodPolicy::ScreenSpaceError {
+            max_pixel_error: rng.pick(&[0.5, 2.0, 8.0]),
+            projection_scale: 540.0,
+            fallback_lod_factor: 3.0,
+        };
+        let cam_pos = Vec3::new(
+            rng.range(-400.0, 400.0),
+            rng.range(0.0, 100.0),
+            rng.range(-400.0, 400.0),
+        );
+
+        let leafs = PatchQuadTree::new(&cam_pos, render_distance, &lod_policy, &bounds, &scale).collect_leafs();
+        assert_balanced(&leafs, render_distance);
+    }
+}
+
+// The per leaf linear search the planner used before `PatchLeafIndex`.
+fn linear_stitch_mask(leafs: &[PatchKey], key: &PatchKey) -> StitchMask {
+    let mut mask = StitchMask::empty();
+
+    for edge in PatchEdge::ALL {
+        let probe = key.world_center() + edge.direction() * key.world_size() as i32;
+        let neighbor_lod_index = leafs
+            .iter()
+            .find(|l| (l.world_center() - probe).length_squared() < key.world_size().pow(2) as i32)
+            .map_or(key.lod_index, |l| l.lod_index);
+
+        if neighbor_lod_index > key.lod_index {
+            mask.insert(edge.stitch_flag());
+        }
+    }
+
+    mask
+}
+
+fn large_leaf_set() -> Vec<PatchKey> {
+    PatchQuadTree::new(
+        &Vec3::new(130.0, 80.0, -70.0),
+        8192,
+        &LodPolicy::Distance { lod_factor: 8.0 },
+        &PatchBoundsTree::new(),
+        &TerrainScale::default(),
+    )
+    .collect_leafs()
+}
+
+#[test]
+fn leaf_index_finds_neighbours() {
+    let leafs = large_leaf_set();
+    let index = PatchLeafIndex::new(&leafs);
+
+    for leaf in &leafs {
+        assert_eq!(index.leaf_at(leaf.world_center().as_vec2()), Some(*leaf));
+
+        for edge in PatchEdge::ALL {
+            let Some(neighbor) = index.neighbor(leaf, edge) else {
+                continue;
+            };
+
+            // The neighbour starts where the leaf ends along the edge direction.
+            let (min, max) = (leaf.world_pos(), leaf.world_pos() + leaf.world_size() as i32);
+            let (n_min, n_max) = (
+                neighbor.world_pos(),
+                neighbor.world_pos() + neighbor.world_size() as i32,
+            );
+            let touches = match edge {
+                PatchEdge::Top => n_max.y == min.y,
+                PatchEdge::Bottom => n_min.y == max.y,
+                PatchEdge::Left => n_max.x == min.x,
+                PatchEdge::Right => n_min.x == max.x,
+            };
+            assert!(touches, "{leaf:?} {neighbor:?} {edge:?}");
+        }
+    }
+}
+
+#[test]
+fn leaf_index_matches_linear_search() {
+    let leafs = large_leaf_set();
+    let index = PatchLeafIndex::new(&leafs);
+
+    for leaf in &leafs {
+        assert_eq!(index.stitch_mask(leaf), linear_stitch_mask(&leafs, leaf), "{leaf:?}");
+    }
+}
+
+// Run with `cargo test --release -p terrain-core --test quadtree -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_stitch_masks() {
+    let leafs = large_leaf_set();
+
+    let start = Instant::now();
+    let linear = leafs.iter().map(|l| linear_stitch_mask(&leafs, l)).collect::<Vec<_>>();
+    let linear_time = start.elapsed();
+
+    let start = Instant::now();
+    let index = PatchLeafIndex::new(&leafs);
+    let indexed = leafs.iter().map(|l| index.stitch_mask(l)).collect::<Vec<_>>();
+    let indexed_time = start.elapsed();
+
+    println!(
+        "{} leafs: linear {:.2} ms, indexed {:.2} ms",
+        leafs.len(),
+        linear_time.as_secs_f64() * 1000.0,
+        indexed_time.as_secs_f64() * 1000.0
+    );
+    assert_eq!(linear, indexed);
+}
+
+fn sorted(mut leafs: Vec<PatchKey>) -> Vec<PatchKey> {
+    leafs.sort_unstable_by_key(|k| (k.lod_index, k.world_index.x, k.world_index.y));
+    leafs
+}
+
+#[test]
+fn incremental_updates_match_rebuilds() {
+    let mut rng = Rng(0x853c49e6748fea9b);
+    let scale = TerrainScale::default();
+    let bounds = PatchBoundsTree::new();
+    let lod_policy = LodPolicy::Distance { lod_factor: 3.0 };
+
+    let mut tree = PatchQuadTree::with_hysteresis(0.0);
+    let mut leafs = HashSet::new();
+    let mut cam_pos = Vec3::new(0.0, 50.0, 0.0);
+
+    for _ in 0..64 {
+        cam_pos += Vec3::new(rng.range(-40.0, 40.0), rng.range(-5.0, 5.0), rng.range(-40.0, 40.0));
+
+        let diff = tree.update(&cam_pos, 1024, &lod_policy, &bounds, &scale);
+        for key in &diff.removed {
+            assert!(leafs.remove(key));
+        }
+        for key in &diff.added {
+            assert!(leafs.insert(*key));
+        }
+
+        let rebuilt = PatchQuadTree::new(&cam_pos, 1024, &lod_policy, &bounds, &scale).collect_leafs();
+        assert_eq!(sorted(tree.collect_leafs()), sorted(rebuilt.clone()));
+        assert_eq!(sorted(leafs.iter().copied().collect()), sorted(rebuilt));
+    }
+}
+
+#[test]
+fn small_moves_skip_most_nodes() {
+    let scale = TerrainScale::default();
+    let bounds = PatchBoundsTree::new();
+    let lod_policy = LodPolicy::Distance { lod_factor: 3.0 };
+
+    let mut tree = PatchQuadTree::with_hysteresis(0.1);
+    let cam_pos = Vec3::new(10.0, 40.0, 20.0);
+
+    let diff = tree.update(&cam_pos, 2048, &lod_policy, &bounds, &scale);
+    let full_count = tree.evaluated_node_count();
+    assert_eq!(diff.added.len(), tree.leafs().len());
+
+    let diff = tree.update(&cam_pos, 2048, &lod_policy, &bounds, &scale);
+    assert!(diff.is_empty());
+    assert_eq!(tree.evaluated_node_count(), 0);
+
+    tree.update(&(cam_pos + Vec3::X), 2048, &lod_policy, &bounds, &scale);
+    assert!(tree.evaluated_node_count() < full_count / 4);
+
+    // Changed inputs evaluate everything again.
+    tree.update(
+        &(cam_pos + Vec3::X),
+        2048,
+        &LodPolicy::Distance { lod_factor: 2.0 },
+        &bounds,
+        &scale,
+    );
+    assert!(tree.evaluated_node_count() > full_count / 2);
+}
+
+#[test]
+fn bounds_changes_revisit_only_their_ancestors() {
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    let scale = TerrainScale::default();
+    let lod_policy = LodPolicy::ScreenSpaceError {
+        max_pixel_error: 2.0,
+        projection_scale: 540.0,
+        fallback_lod_factor: 3.0,
+    };
+    let mut bounds = spiky_bounds(&mut rng, 1024);
+
+    let mut tree = PatchQuadTree::with_hysteresis(0.0);
+    let cam_pos = Vec3::new(10.0, 40.0, 20.0);
+    tree.update(&cam_pos, 1024, &lod_policy, &bounds, &scale);
+    let full_count = tree.evaluated_node_count();
+
+    // A newly streamed rough patch near the camera.
+    let key = PatchKey {
+        world_index: IVec2::new(2, 1),
+        lod_index: 0,
+    };
+    bounds.insert_decimation_error(key, 0.5);
+    tree.update(&cam_pos, 1024, &lod_policy, &bounds, &scale);
+
+    assert!(tree.evaluated_node_count() > 0);
+    assert!(tree.evaluated_node_count() < full_count / 4);
+
+    let rebuilt = PatchQuadTree::new(&cam_pos, 1024, &lod_policy, &bounds, &scale).collect_leafs();
+    assert_eq!(sorted(tree.collect_leafs()), sorted(rebuilt));
+}
+
+#[test]
+fn hysteresis_stops_lod_popping() {
+    let scale = TerrainScale::default();
+    let bounds = PatchBoundsTree::new();
+    let lod_policy = LodPolicy::Distance { lod_factor: 3.0 };
+
+    // Unknown bounds span heights 0..100, so the LOD 1 node below the camera splits at 192 above them.
+    let low = Vec3::new(32.0, 290.0, 32.0);
+    let high = Vec3::new(32.0, 294.0, 32.0);
+
+    let mut exact = PatchQuadTree::with_hysteresis(0.0);
+    let mut damped = PatchQuadTree::with_hysteresis(0.1);
+    exact.update(&low, 1024, &lod_policy, &bounds, &scale);
+    damped.update(&low, 1024, &lod_policy, &bounds, &scale);
+
+    for i in 0..8 {
+        let cam_pos = if i % 2 == 0 { high } else { low };
+
+        assert!(!exact.update(&cam_pos, 1024, &lod_policy, &bounds, &scale).is_empty());
+        assert!(damped.update(&cam_pos, 1024, &lod_policy, &bounds, &scale).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::sync::Arc;
+
+use glam::{UVec2, Vec2, Vec3};
+use terrain_core::*;
+
+use common::{insert_patch, key};
+
+fn patch(f: impl Fn(UVec2) -> f32) -> Arc<[f32]> {
+    (0..ATLAS_PATCH_PIXEL_SIZE * ATLAS_PATCH_PIXEL_SIZE)
+        .map(|i| f(UVec2::new(i % ATLAS_PATCH_PIXEL_SIZE, i / ATLAS_PATCH_PIXEL_SIZE)))
+        .collect()
+}
+
+#[test]
+fn index_buffer_uses_quad_triangles() {
+    let indices = patch_indices(PATCH_PIXEL_SIZE);
+    assert_eq!(indices.len(), (PATCH_PIXEL_SIZE * PATCH_PIXEL_SIZE * 6) as usize);
+
+    // Neighbouring quads split along opposite diagonals.
+    let quad = |x: u32, z: u32| &indices[((z * PATCH_PIXEL_SIZE + x) * 6) as usize..][..6];
+    assert!(quad(0, 0).contains(&0) && quad(0, 0).contains(&(ATLAS_PATCH_PIXEL_SIZE + 1)));
+    assert_eq!(
+        quad(1, 0).iter().filter(|&&i| i == ATLAS_PATCH_PIXEL_SIZE + 1).count(),
+        2
+    );
+    assert_eq!(quad(1, 0).iter().filter(|&&i| i == 2).count(), 2);
+}
+
+#[test]
+fn hits_the_rendered_triangles() {
+    let leaf = key(0, 0, 0);
+    let scale = TerrainScale {
+        world_scale: 2.0,
+        height_scale: 10.0,
+    };
+
+    // Raising a corner that is not on the diagonal of quad (0, 0) leaves the diagonal and thus the quad center flat.
+    let mut height_field = TerrainHeightField::new(PATCH_LOD_COUNT);
+    insert_patch(
+        &mut height_field,
+        leaf,
+        patch(|v| if v == UVec2::new(1, 0) { 1.0 } else { 0.0 }),
+    );
+    height_field.set_leaf_patches(&[leaf]);
+
+    let pixel_size = leaf.world_size() as f32 / PATCH_PIXEL_SIZE as f32 * scale.world_scale;
+    let quad_center = Vec2::splat(0.5 * pixel_size);
+
+    let hit = height_field
+        .raycast(Vec3::new(quad_center.x, 5.0, quad_center.y), Vec3::NEG_Y, 100.0, &scale)
+        .unwrap();
+    assert!(hit.position.y.abs() < 1e-4);
+    assert!((hit.distance - 5.0).abs() < 1e-4);
+    assert_eq!(hit.key, leaf);
+    assert!(!hit.is_fallback);
+
+    // Off the diagonal the triangle containing the raised corner is planar, bilinear filtering would give 0.5625.
+    let hit = height_field
+        .raycast(
+            Vec3::new(0.75 * pixel_size, 5.0, 0.25 * pixel_size),
+            Vec3::NEG_Y,
+            100.0,
+            &scale,
+        )
+        .unwrap();
+    assert!((hit.position.y - 5.0).abs() < 1e-4);
+    assert!(hit.normal.y > 0.0);
+}
+
+#[test]
+fn oblique_rays_cross_patches() {
+    let leafs = [key(0, 0, 0), key(1, 0, 0), key(0, 1, 0), key(1, 1, 0), key(2, 0, 1)];
+    let scale = TerrainScale::default();
+
+    // A plane rising along X, sampled per patch so patches of both LODs describe the same surface.
+    let slope = 0.002;
+    let mut height_field = TerrainHeightField::new(PATCH_LOD_COUNT);
+    for leaf in &leafs {
+        let pixel_world_size = leaf.world_size() as f32 / PATCH_PIXEL_SIZE as f32;
+        let origin = leaf.world_pos().x as f32;
+        insert_patch(
+            &mut height_field,
+            *leaf,
+            patch(|v| 0.1 + (origin + v.x as f32 * pixel_world_size) * slope),
+        );
+    }
+    height_field.set_leaf_patches(&leafs);
+
+    let origin = Vec3::new(1.0, 80.0, 30.0);
+    let dir = Vec3::new(1.0, -0.2, 0.05);
+
+    // Analytic intersection with y = (0.1 + x * slope) * height_scale.
+    let dir_n = dir.normalize();
+    let t = (80.0 - (0.1 + origin.x * slope) * scale.height_scale) / (slope * scale.height_scale * dir_n.x - dir_n.y);
+    let expected = origin + dir_n * t;
+
+    let hit = height_field.raycast(origin, dir, 1000.0, &scale).unwrap();
+    assert!(
+        (hit.position - expected).length() < 1e-2,
+        "{:?} {:?}",
+        hit.position,
+        expected
+    );
+    assert_eq!(hit.key, key(2, 0, 1));
+
+    assert!(
+        height_field
+            .segment_cast(origin, origin + dir_n * (t - 1.0), &scale)
+            .is_none()
+    );
+    assert!(
+        height_field
+            .segment_cast(origin, origin + dir_n * (t + 1.0), &scale)
+            .is_some()
+    );
+    assert!(height_field.raycast(origin, Vec3::Y, 1000.0, &scale).is_none());
+}
+
+#[test]
+fn falls_back_to_coarser_patches() {
+    let leafs = [key(0, 0, 0), key(1, 0, 0), key(0, 1, 0), key(1, 1, 0)];
+    let coarse = key(0, 0, 1);
+    let scale = TerrainScale::default();
+
+    let mut height_field = TerrainHeightField::new(PATCH_LOD_COUNT);
+    insert_patch(&mut height_field, coarse, patch(|_| 0.25));
+    height_field.set_leaf_patches(&leafs);
+
+    let hit = height_field
+        .raycast(Vec3::new(80.0, 50.0, 20.0), Vec3::NEG_Y, 100.0, &scale)
+        .unwrap();
+    assert!((hit.position.y - 25.0).abs() < 1e-4);
+    assert_eq!(hit.key, key(1, 0, 0));
+    assert!(hit.is_fallback);
+}
@@ -0,0 +1,68 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::IVec2;
+use terrain_core::*;
+
+fn key(x: i32) -> PatchKey {
+    PatchKey {
+        world_index: IVec2::new(x, 0),
+        lod_index: 0,
+    }
+}
+
+fn fill_atlas(residency: &mut AtlasResidency) -> HashMap<PatchKey, PatchState> {
+    let mut patch_cache = HashMap::new();
+
+    let mut x = 0;
+    while let Some(atlas_index) = residency.allocate() {
+        patch_cache.insert(key(x), PatchState::Resident(atlas_index));
+        residency.touch(key(x), x as u64 + 1);
+        x += 1;
+    }
+
+    patch_cache
+}
+
+#[test]
+fn evicts_least_recently_used_first() {
+    let mut residency = AtlasResidency::new(ATLAS_PATCH_COUNT);
+    let mut patch_cache = fill_atlas(&mut residency);
+    assert_eq!(residency.free_slot_count(), 0);
+
+    let evicted = residency.evict(&mut patch_cache, &HashSet::new(), u64::MAX, 3);
+
+    assert_eq!(evicted.len(), 3);
+    assert_eq!(residency.free_slot_count(), 3);
+    assert_eq!(residency.evicted_count(), 3);
+    assert!((0..3).all(|x| !patch_cache.contains_key(&key(x))));
+    assert!(patch_cache.contains_key(&key(3)));
+}
+
+#[test]
+fn keeps_leaf_and_in_flight_patches() {
+    let mut residency = AtlasResidency::new(ATLAS_PATCH_COUNT);
+    let mut patch_cache = fill_atlas(&mut residency);
+    let slot_count = patch_cache.len();
+
+    let leaf_patches = HashSet::from([key(0)]);
+
+    // Only patches last used by frames 1..=3 are finished on the GPU, key(0) is still a leaf.
+    let evicted = residency.evict(&mut patch_cache, &leaf_patches, 3, slot_count);
+
+    assert_eq!(evicted.len(), 2);
+    assert!(patch_cache.contains_key(&key(0)));
+    assert!(!patch_cache.contains_key(&key(1)));
+    assert!(!patch_cache.contains_key(&key(2)));
+    assert!(patch_cache.contains_key(&key(3)));
+}
+
+#[test]
+fn no_eviction_while_slots_are_free() {
+    let mut residency = AtlasResidency::new(ATLAS_PATCH_COUNT);
+    let mut patch_cache = HashMap::from([(key(0), PatchState::Resident(residency.allocate().unwrap()))]);
+
+    let evicted = residency.evict(&mut patch_cache, &HashSet::new(), u64::MAX, 1);
+
+    assert_eq!(evicted.len(), 0);
+    assert_eq!(patch_cache.len(), 1);
+}
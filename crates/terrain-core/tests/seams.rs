@@ -0,0 +1,124 @@
+mod common;
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+use terrain_core::*;
+
+use common::key;
+
+const CRACK_TOLERANCE: f32 = 1e-4;
+
+fn generate_leafs(source: &dyn HeightSource) -> (Vec<PatchKey>, HashMap<PatchKey, Vec<f32>>) {
+    let leafs = PatchQuadTree::new(
+        &Vec3::new(40.0, 0.0, -70.0),
+        256,
+        &LodPolicy::Distance { lod_factor: 3.0 },
+        &PatchBoundsTree::new(),
+        &TerrainScale::default(),
+    )
+    .collect_leafs();
+    let height_maps = leafs
+        .iter()
+        .map(|key| (*key, source.sample_patch(key, PATCH_PIXEL_SIZE)))
+        .collect();
+
+    (leafs, height_maps)
+}
+
+#[test]
+fn stitched_seams_have_no_cracks() {
+    let (leafs, height_maps) = generate_leafs(&FbmHeightSource::default());
+    let report = measure_seams(&leafs, &height_maps, true);
+
+    assert_eq!(report.missing_patches, 0);
+    assert!(report.edges.iter().any(|e| e.lod_delta() == 1));
+
+    // Stitching only handles a 2:1 LOD difference, larger steps are reported but not held to the tolerance.
+    for edge in report.edges.iter().filter(|e| e.lod_delta() <= 1) {
+        assert!(
+            edge.max_delta <= CRACK_TOLERANCE,
+            "crack of {} between {:?} and {:?}",
+            edge.max_delta,
+            edge.patch,
+            edge.neighbor
+        );
+    }
+
+    // Without stitching the odd fine vertices float above or below the coarse edge.
+    let unstitched = measure_seams(&leafs, &height_maps, false);
+    assert!(unstitched.max_delta > CRACK_TOLERANCE);
+}
+
+#[test]
+fn fixed_seams_agree_exactly() {
+    let (leafs, mut height_maps) = generate_leafs(&FbmHeightSource::default());
+    fix_seams(&leafs, &mut height_maps);
+
+    for stitching in [false, true] {
+        let report = measure_seams(&leafs, &height_maps, stitching);
+        assert!(report.max_delta <= 1e-6, "{:?}", report.worst_edge());
+    }
+}
+
+// Every patch at its own height, coarse ones sloping along X, so every shared edge and corner starts out cracked.
+fn disagreeing_height_maps(leafs: &[PatchKey]) -> HashMap<PatchKey, Vec<f32>> {
+    let row_len = PATCH_PIXEL_SIZE as usize + 1;
+
+    leafs
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let heights = (0..row_len * row_len)
+                .map(|p| (i + 1) as f32 + (p % row_len) as f32 * 0.1 * key.lod_index as f32)
+                .collect();
+            (*key, heights)
+        })
+        .collect()
+}
+
+#[test]
+fn fixed_corners_agree_between_four_patches() {
+    let leafs = [key(0, 0, 0), key(1, 0, 0), key(0, 1, 0), key(1, 1, 0)];
+    let mut height_maps = disagreeing_height_maps(&leafs);
+    fix_seams(&leafs, &mut height_maps);
+
+    let last = PATCH_PIXEL_SIZE as usize;
+    let row_len = last + 1;
+    let shared_corners = [
+        height_maps[&leafs[0]][last * row_len + last],
+        height_maps[&leafs[1]][last * row_len],
+        height_maps[&leafs[2]][last],
+        height_maps[&leafs[3]][0],
+    ];
+    assert_eq!(shared_corners, [2.5; 4]);
+
+    for stitching in [false, true] {
+        let report = measure_seams(&leafs, &height_maps, stitching);
+        assert_eq!(report.edges.len(), 4);
+        assert!(report.max_delta <= 1e-6, "{:?}", report.worst_edge());
+    }
+}
+
+#[test]
+fn fixed_seams_agree_across_lods() {
+    // A coarse patch with four finer ones to its right and one of its own size below.
+    let leafs = [
+        key(0, 0, 1),
+        key(2, 0, 0),
+        key(3, 0, 0),
+        key(2, 1, 0),
+        key(3, 1, 0),
+        key(0, 2, 1),
+    ];
+    let mut height_maps = disagreeing_height_maps(&leafs);
+    assert!(measure_seams(&leafs, &height_maps, true).max_delta > 0.5);
+
+    fix_seams(&leafs, &mut height_maps);
+
+    for stitching in [false, true] {
+        let report = measure_seams(&leafs, &height_maps, stitching);
+        assert!(report.edges.iter().any(|e| e.lod_delta() == 1));
+        assert!(report.max_delta <= 1e-5, "{:?}", report.worst_edge());
+    }
+}